@@ -31,6 +31,12 @@ const resolveToolsNativeModulePath = () => {
   return join(process.cwd(), "native", "tools", "tools.node");
 };
 
+/** 音频分析缓存数据库路径 */
+const getAnalysisCacheDbPath = (): string => {
+  const store = useStore();
+  return join(store.get("cachePath"), "local-data", "analysis-cache.db");
+};
+
 const runToolsJobInWorker = async (payload: Record<string, unknown>) => {
   const worker = new Worker(new URL("./workers/audio-analysis.worker.js", import.meta.url), {});
 
@@ -86,7 +92,11 @@ const runToolsJobInWorker = async (payload: Record<string, unknown>) => {
         resolvePromise(null);
       });
 
-      worker.postMessage({ ...payload, nativeModulePath });
+      worker.postMessage({
+        ...payload,
+        nativeModulePath,
+        analysisCacheDbPath: getAnalysisCacheDbPath(),
+      });
     });
 
     if (
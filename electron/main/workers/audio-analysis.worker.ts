@@ -29,11 +29,12 @@ type SuggestLongMixRequest = {
   nativeModulePath: string;
 };
 
-type WorkerRequest =
+type WorkerRequest = (
   | AnalyzeRequest
   | AnalyzeHeadRequest
   | SuggestTransitionRequest
-  | SuggestLongMixRequest;
+  | SuggestLongMixRequest
+) & { analysisCacheDbPath?: string };
 
 type AnalyzeResponse = { ok: true; result: unknown } | { ok: false; error: string };
 
@@ -45,6 +46,7 @@ let cachedTools: {
   analyzeAudioFileHead?: (filePath: string, maxTime: number) => unknown;
   suggestTransition?: (currentPath: string, nextPath: string) => unknown;
   suggestLongMix?: (currentPath: string, nextPath: string) => unknown;
+  initAnalysisCache?: (dbPath: string, maxEntries?: number) => void;
 } | null = null;
 let cachedToolsError: string | null = null;
 
@@ -80,6 +82,15 @@ port.on("message", (msg: WorkerRequest) => {
       return;
     }
 
+    // 缓存打不开时照常分析，只是不走缓存
+    if (msg.analysisCacheDbPath && typeof tools.initAnalysisCache === "function") {
+      try {
+        tools.initAnalysisCache(msg.analysisCacheDbPath);
+      } catch {
+        /* empty */
+      }
+    }
+
     let result: unknown;
     if (msg.type === "analyze") {
       if (typeof tools.analyzeAudioFile !== "function") {
@@ -1,5 +1,31 @@
 /* auto-generated by NAPI-RS */
 /* eslint-disable */
+/**
+ * A cancellable handle for running analyses off the calling thread.
+ * One task can run several calls; `cancel` stops all of them.
+ */
+export declare class AnalysisTask {
+  constructor()
+  /** Cancels the calls in flight; later calls start afresh */
+  cancel(): void
+  /**
+   * `analyze_audio_file` (or `analyze_audio_file_head` with
+   * `include_tail: false`) on the blocking pool
+   */
+  analyze(path: string, maxAnalyzeTime?: number | undefined | null, includeTail?: boolean | undefined | null, onProgress?: ((err: Error | null, arg: AnalysisProgress) => any) | undefined | null): Promise<AudioAnalysis | null>
+  /** `suggest_transition` on the blocking pool */
+  suggestTransition(currentPath: string, nextPath: string, onProgress?: ((err: Error | null, arg: AnalysisProgress) => any) | undefined | null): Promise<TransitionProposal | null>
+  /** `suggest_long_mix` on the blocking pool */
+  suggestLongMix(currentPath: string, nextPath: string, onProgress?: ((err: Error | null, arg: AnalysisProgress) => any) | undefined | null): Promise<AdvancedTransition | null>
+  /**
+   * Analyzes every file in `paths`, calling `on_result` as each one
+   * finishes (not necessarily in order). Resolves to the number of files
+   * analyzed successfully; once cancelled, files not yet finished are
+   * skipped and the promise rejects.
+   */
+  analyzeBatch(paths: Array<string>, options: BatchOptions | undefined | null, onResult: ((err: Error | null, arg: BatchResult) => any)): Promise<number>
+}
+
 export declare class DownloadTask {
   constructor()
   cancel(): void
@@ -17,6 +43,21 @@ export interface AdvancedTransition {
   strategy: string
 }
 
+export interface AlbumReplayGain {
+  loudness: number
+  gain: number
+  peak: number
+  /** Opus 的 `R128_ALBUM_GAIN` */
+  r128Gain: number
+}
+
+export interface AnalysisProgress {
+  /** File currently being analyzed */
+  path: string
+  /** 0..1 over the whole call, including every file it analyzes */
+  progress: number
+}
+
 export declare function analyzeAudioFile(path: string, maxAnalyzeTime?: number | undefined | null): AudioAnalysis | null
 
 export declare function analyzeAudioFileHead(path: string, maxAnalyzeTime?: number | undefined | null): AudioAnalysis | null
@@ -28,8 +69,13 @@ export interface AudioAnalysis {
   fade_in_pos: number
   fade_out_pos: number
   first_beat_pos?: number
+  beat_grid?: BeatGrid
   loudness?: number
+  loudness_range?: number
+  peaks?: PeakInfo
   drop_pos?: number
+  segments: Array<StructureSegment>
+  highlight_start?: number
   version: number
   analyze_window: number
   cut_in_pos?: number
@@ -55,14 +101,149 @@ export interface AutomationPoint {
   highCut: number
 }
 
+export interface BatchOptions {
+  max_analyze_time?: number
+  /**
+   * Also analyze the end of each file, as `analyze_audio_file` does.
+   * Defaults to `true`
+   */
+  include_tail?: boolean
+  /** Files decoded at once, defaulting to the number of cores (at most 4) */
+  concurrency?: number
+}
+
+export interface BatchResult {
+  /** Position of `path` in the list passed in */
+  index: number
+  path: string
+  /** Empty when the file couldn't be decoded */
+  analysis?: AudioAnalysis
+}
+
+export interface BeatGrid {
+  /** Every beat, seconds */
+  beats: Array<number>
+  /** Indices into `beats` of the first beat of each bar */
+  downbeats: Array<number>
+  /** Position of each beat within its bar, 0 = downbeat */
+  beat_in_bar: Array<number>
+  beats_per_bar: number
+  /** Median tempo, BPM */
+  bpm: number
+  /** Local tempo, BPM, `tempo_curve_rate` values per second from the start */
+  tempo_curve: Array<number>
+  tempo_curve_rate: number
+  /**
+   * 1.0 when one constant tempo fits every beat, falling to 0.0 as beats
+   * stray a quarter of a beat from it on average
+   */
+  stability: number
+}
+
+export interface ChannelPeak {
+  /** Largest absolute sample, linear (1.0 = full scale) */
+  sample_peak: number
+  sample_peak_db: number
+  /** Time of the sample peak, seconds */
+  sample_peak_pos: number
+  /** Largest absolute value of the 4× oversampled signal, linear */
+  true_peak: number
+  /** dBTP */
+  true_peak_db: number
+  true_peak_pos: number
+}
+
+/** 清空分析缓存（包括波形），返回删除的条目数。缓存未初始化时返回 0 */
+export declare function clearAnalysisCache(): number
+
+/**
+ * Compares two fingerprints from `fingerprint_file`, finding the offset at
+ * which they agree best
+ */
+export declare function compareFingerprints(a: Fingerprint, b: Fingerprint): FingerprintMatch
+
+/**
+ * 计算 `ReplayGain` 2.0 增益并写入标签
+ *
+ * `mode` 为 `track` 时只写曲目增益；为 `album` 时把所有文件当作一张专辑，
+ * 同时写入专辑增益。`dry_run` 为 `true` 时只返回计算结果，不修改文件。
+ * 单个文件解码或写入失败不会中断整体，错误记录在对应曲目的 `error` 中
+ */
+export declare function computeReplaygain(paths: Array<string>, mode: string, dryRun?: boolean | undefined | null, onProgress?: ((err: Error | null, arg: ReplayGainProgress) => any) | undefined | null): Promise<ReplayGainResult>
+
 export interface DownloadProgress {
   percent: number
   transferredBytes: number
   totalBytes: number
 }
 
+export interface Fingerprint {
+  /** Seconds of audio fingerprinted */
+  duration: number
+  /** Sub-fingerprints per second */
+  rate: number
+  /** One 32-bit sub-fingerprint per frame */
+  data: Array<number>
+  version: number
+}
+
+/** Fingerprints a file from its decoded audio */
+export declare function fingerprintFile(path: string): Promise<Fingerprint | null>
+
+export interface FingerprintMatch {
+  /** 0..1; unrelated audio scores near 0, the same recording well above 0.5 */
+  score: number
+  /** Seconds to add to a time in `b` to reach the same audio in `a` */
+  offset: number
+  /** Seconds of audio the score was measured over */
+  overlap: number
+}
+
+/**
+ * Waveform overview of the whole file for the seek bar.
+ *
+ * Returns `buckets` min / max / RMS values per channel, plus low / mid / high
+ * band energy with `options.bands`, quantised to 8 bits. Served from the
+ * analysis cache when it's initialised.
+ */
+export declare function generateWaveform(path: string, buckets: number, options?: WaveformOptions | undefined | null): Promise<Waveform | null>
+
+/**
+ * 只查缓存，不做分析。参数与 `analyze_audio_file` 相同，`include_tail` 为 `false`
+ * 时对应 `analyze_audio_file_head` 的结果
+ */
+export declare function getCachedAnalysis(path: string, maxAnalyzeTime?: number | undefined | null, includeTail?: boolean | undefined | null): AudioAnalysis | null
+
 export declare function getTaskbarCreatedMessageId(): number
 
+/**
+ * 打开（或创建）分析缓存数据库，之后的分析和过渡建议都会先查缓存
+ *
+ * 用同一个路径重复调用不会重新打开数据库
+ */
+export declare function initAnalysisCache(dbPath: string, maxEntries?: number | undefined | null): void
+
+export interface LoudnessInfo {
+  /** Gated integrated loudness, LUFS */
+  integrated: number
+  /** Loudness range, LU */
+  loudness_range: number
+  momentary_max: number
+  short_term_max: number
+  /** Values per second of the curves below */
+  curve_rate: number
+  /** 400 ms loudness every 100 ms, LUFS */
+  momentary?: Array<number>
+  /** 3 s loudness every 100 ms, LUFS */
+  short_term?: Array<number>
+}
+
+/**
+ * Measures gated loudness (BS.1770-4) and loudness range over the whole file.
+ * `with_curves` also returns the momentary and short-term curves.
+ */
+export declare function measureLoudness(path: string, withCurves?: boolean | undefined | null): LoudnessInfo | null
+
 export interface MusicTrack {
   id: string
   path: string
@@ -77,6 +258,31 @@ export interface MusicTrack {
   trackNumber?: number
 }
 
+/** Per-channel peaks and the maximum over all channels */
+export interface PeakInfo {
+  sample_peak: number
+  sample_peak_db: number
+  sample_peak_pos: number
+  true_peak: number
+  true_peak_db: number
+  true_peak_pos: number
+  channels: Array<ChannelPeak>
+}
+
+export interface ReplayGainProgress {
+  /** `measure`：测量完一个文件；`write`：写完一个文件的标签 */
+  phase: string
+  current: number
+  total: number
+  path: string
+}
+
+export interface ReplayGainResult {
+  tracks: Array<TrackReplayGain>
+  /** 仅专辑模式，且至少有一个文件测量成功时存在 */
+  album?: AlbumReplayGain
+}
+
 export interface ScanEvent {
   event: string
   tracks?: Array<MusicTrack>
@@ -105,10 +311,37 @@ export interface SongMetadata {
   discNumber?: number
 }
 
+export interface StructureSegment {
+  /** Seconds */
+  start: number
+  end: number
+  /** `intro`, `verse`, `chorus`, `bridge` or `outro` */
+  label: string
+  /** Segments in the same group repeat the same material */
+  group: number
+  /** 0..1, how clearly the boundaries and the label stand out */
+  confidence: number
+}
+
 export declare function suggestLongMix(currentPath: string, nextPath: string): AdvancedTransition | null
 
 export declare function suggestTransition(currentPath: string, nextPath: string): TransitionProposal | null
 
+export interface TrackReplayGain {
+  path: string
+  /** 门限积分响度，LUFS。解码失败或不支持的格式时为空 */
+  loudness?: number
+  /** 相对 -18 LUFS 的增益，dB */
+  gain?: number
+  /** 真峰值，线性值（1.0 为满刻度） */
+  peak?: number
+  /** Opus 的 `R128_TRACK_GAIN`，Q7.8 定点数，相对 -23 LUFS。其他格式为空 */
+  r128Gain?: number
+  /** 是否已写入标签，dry run 时始终为 `false` */
+  written: boolean
+  error?: string
+}
+
 export interface TransitionProposal {
   duration: number
   current_track_mix_out: number
@@ -120,4 +353,31 @@ export interface TransitionProposal {
   bpm_compatible: boolean
 }
 
+export interface Waveform {
+  /** Seconds */
+  duration: number
+  sample_rate: number
+  buckets: number
+  channels: Array<WaveformChannel>
+}
+
+export interface WaveformChannel {
+  /** Lowest sample per bucket, -1.0..=0.0 */
+  min: Array<number>
+  /** Highest sample per bucket, 0.0..=1.0 */
+  max: Array<number>
+  rms: Array<number>
+  /** RMS of each band per bucket, present when requested */
+  low?: Array<number>
+  mid?: Array<number>
+  high?: Array<number>
+}
+
+export interface WaveformOptions {
+  /** Also measure low (< 250 Hz), mid and high (> 4 kHz) band energy */
+  bands?: boolean
+  /** Decode threads; defaults to the number of cores, at most 8 */
+  threads?: number
+}
+
 export declare function writeMusicMetadata(filePath: string, metadata: SongMetadata, coverPath?: string | undefined | null): Promise<void>
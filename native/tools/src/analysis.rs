@@ -1,19 +1,42 @@
 use napi_derive::napi;
 use num_complex::Complex32;
 use rustfft::FftPlanner;
-use std::fs::File;
-use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use symphonia::core::audio::{AudioBufferRef, Signal};
+use symphonia::core::formats::{SeekMode, SeekTo};
 use symphonia::core::sample::i24;
-use symphonia::core::codecs::Decoder;
-use symphonia::core::formats::{FormatReader, SeekMode, SeekTo};
-use symphonia::core::io::MediaSourceStream;
-use symphonia::core::probe::Hint;
 use symphonia::core::units::Time;
 
+pub mod beats;
+pub mod decode;
+pub mod fingerprint;
+pub mod loudness;
+pub mod opus;
+pub mod peak;
+pub mod structure;
+pub mod task;
+pub mod tempo;
+pub mod waveform;
+
+use beats::OnsetDetector;
+pub use beats::BeatGrid;
+use decode::OpenTrack;
+pub use fingerprint::{compare_fingerprints, fingerprint_file, Fingerprint, FingerprintMatch};
+use loudness::LoudnessMeter;
+pub use loudness::{measure_loudness, LoudnessInfo};
+use peak::PeakMeter;
+pub use peak::{ChannelPeak, PeakInfo};
+use structure::FeatureExtractor;
+pub use structure::StructureSegment;
+use task::Control;
+pub use task::{AnalysisProgress, AnalysisTask, BatchOptions, BatchResult};
+pub use waveform::{generate_waveform, Waveform, WaveformChannel, WaveformOptions};
+
 // --- Data Structures (API) ---
 
 #[napi(object)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AudioAnalysis {
     pub duration: f64,
     pub bpm: Option<f64>,
@@ -25,9 +48,17 @@ pub struct AudioAnalysis {
     pub fade_out_pos: f64,
     #[napi(js_name = "first_beat_pos")]
     pub first_beat_pos: Option<f64>,
-    pub loudness: Option<f64>, // LUFS
+    #[napi(js_name = "beat_grid")]
+    pub beat_grid: Option<BeatGrid>, // Tracked from the start up to the seek to the tail
+    pub loudness: Option<f64>, // Gated integrated loudness of the analyzed windows, LUFS
+    #[napi(js_name = "loudness_range")]
+    pub loudness_range: Option<f64>, // LU, same windows
+    pub peaks: Option<PeakInfo>, // Same windows
     #[napi(js_name = "drop_pos")]
     pub drop_pos: Option<f64>, // Chorus/Drop start
+    pub segments: Vec<StructureSegment>, // Labelled sections, same stretch as `beat_grid`
+    #[napi(js_name = "highlight_start")]
+    pub highlight_start: Option<f64>, // Start of a 30 s preview
     pub version: i32,
     #[napi(js_name = "analyze_window")]
     pub analyze_window: f64,
@@ -109,8 +140,12 @@ pub struct AdvancedTransition {
 
 const ENV_RATE: f64 = 50.0;
 const WINDOW_SIZE_MS: usize = 20;
-const ANALYSIS_VERSION: i32 = 13;
+pub const ANALYSIS_VERSION: i32 = 19;
 const DEFAULT_SAMPLE_RATE: u32 = 44100;
+const MAX_CHANNELS: usize = 8;
+// How much of the next track transition suggestions look at
+const TRANSITION_NEXT_WINDOW: f64 = 120.0;
+const LONG_MIX_NEXT_WINDOW: f64 = 180.0;
 
 // Key Detection Constants
 const FFT_FRAME_SIZE: usize = 4096;
@@ -120,41 +155,12 @@ const MAX_FREQ: f32 = 5000.0;
 const HAMMING_ALPHA: f32 = 0.54;
 const HAMMING_BETA: f32 = 0.46;
 
-// BPM Detection Constants
-const BPM_MIN_LAG: usize = 15; // ~180 BPM at 50Hz
-const BPM_MAX_LAG: usize = 55; // ~55 BPM at 50Hz
 const SILENCE_THRESH_DB: f32 = -48.0;
 const VOCAL_LPF_FREQ: f32 = 3000.0;
 const VOCAL_HPF_FREQ: f32 = 200.0;
-const LOW_LPF_FREQ: f32 = 150.0;
-
-// Filter Coefficients (ITU-R BS.1770-4)
-const LOUDNESS_PRE_FILTER: [f64; 5] = [1.53512485958697, -2.69169618940638, 1.19839281085285, -1.69065929318241, 0.73248077421585];
-const LOUDNESS_RLB_FILTER: [f64; 5] = [1.0, -2.0, 1.0, -1.99004745483398, 0.99007225036621];
 
 // --- DSP Filters ---
 
-struct BiquadFilter {
-    b0: f64, b1: f64, b2: f64, a1: f64, a2: f64,
-    z1: f64, z2: f64,
-}
-
-impl BiquadFilter {
-    fn new(coeffs: [f64; 5]) -> Self {
-        Self { 
-            b0: coeffs[0], b1: coeffs[1], b2: coeffs[2], a1: coeffs[3], a2: coeffs[4], 
-            z1: 0.0, z2: 0.0 
-        }
-    }
-
-    fn process(&mut self, input: f64) -> f64 {
-        let output = input * self.b0 + self.z1;
-        self.z1 = input * self.b1 + self.z2 - self.a1 * output;
-        self.z2 = input * self.b2 - self.a2 * output;
-        output
-    }
-}
-
 // Generic First Order Filter (LPF/HPF)
 struct FirstOrderFilter {
     prev_x: f32,
@@ -205,40 +211,6 @@ impl VocalFilter {
     }
 }
 
-struct LoudnessMeter {
-    pre_filter: Vec<BiquadFilter>,
-    rlb_filter: Vec<BiquadFilter>,
-    sum_sq: f64,
-    count: u64,
-}
-
-impl LoudnessMeter {
-    fn new(channels: usize) -> Self {
-        let mut pre_filter = Vec::with_capacity(channels);
-        let mut rlb_filter = Vec::with_capacity(channels);
-        for _ in 0..channels {
-            pre_filter.push(BiquadFilter::new(LOUDNESS_PRE_FILTER));
-            rlb_filter.push(BiquadFilter::new(LOUDNESS_RLB_FILTER));
-        }
-        Self { pre_filter, rlb_filter, sum_sq: 0.0, count: 0 }
-    }
-
-    fn process(&mut self, channels: &[f32]) {
-        for (i, &sample) in channels.iter().enumerate() {
-            if i >= self.pre_filter.len() { break; }
-            let s = self.rlb_filter[i].process(self.pre_filter[i].process(sample as f64));
-            self.sum_sq += s * s;
-        }
-        self.count += 1;
-    }
-
-    fn get_lufs(&self) -> f64 {
-        if self.count == 0 { return -70.0; }
-        let mean_sq = self.sum_sq / self.count as f64;
-        if mean_sq <= 0.0 { -70.0 } else { -0.691 + 10.0 * mean_sq.log10() }
-    }
-}
-
 // --- Analysis Core ---
 
 struct EnvelopeAccumulator {
@@ -269,10 +241,45 @@ impl EnvelopeAccumulator {
 #[derive(Default)]
 struct AnalysisSegment {
     envelope: Vec<f32>,
-    low_envelope: Vec<f32>,
     vocal_ratio: Vec<f32>,
 }
 
+/// Filters and envelope accumulators feeding one `AnalysisSegment`
+struct SegmentState {
+    acc_env: EnvelopeAccumulator,
+    acc_vocal: EnvelopeAccumulator,
+    vocal_filter: VocalFilter,
+}
+
+impl SegmentState {
+    fn new(sample_rate: u32, window_size: usize) -> Self {
+        Self {
+            acc_env: EnvelopeAccumulator::new(window_size),
+            acc_vocal: EnvelopeAccumulator::new(window_size),
+            vocal_filter: VocalFilter::new(sample_rate),
+        }
+    }
+
+    fn process(&mut self, val: f32, segment: &mut AnalysisSegment) {
+        let vocal = self.vocal_filter.process(val);
+
+        if let Some(rms) = self.acc_env.process(val) { segment.envelope.push(rms); }
+        if let Some(rms_vocal) = self.acc_vocal.process(vocal) {
+             let base = *segment.envelope.last().unwrap_or(&1.0);
+             segment.vocal_ratio.push(if base > 0.0001 { rms_vocal / base } else { 0.0 });
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Head,
+    /// Between head and tail, decoded only when seeking to the tail failed:
+    /// measured, not analyzed
+    Middle,
+    Tail,
+}
+
 struct TrackAnalyzer {
     path: PathBuf,
     max_analyze_time: f64,
@@ -287,44 +294,48 @@ struct TrackAnalyzer {
     
     // Internal state
     loudness_meter: LoudnessMeter,
+    peak_meter: PeakMeter,
+    onsets: OnsetDetector,
+    features: FeatureExtractor,
+}
+
+/// Effective analysis window for a requested `max_analyze_time`
+pub fn analysis_window(max_time: Option<f64>) -> f64 {
+    max_time.unwrap_or(60.0).clamp(5.0, 300.0)
 }
 
 impl TrackAnalyzer {
     fn new(path: String, max_time: Option<f64>, include_tail: bool) -> Self {
         Self {
             path: PathBuf::from(path),
-            max_analyze_time: max_time.unwrap_or(60.0).clamp(5.0, 300.0),
+            max_analyze_time: analysis_window(max_time),
             include_tail,
             head: AnalysisSegment::default(),
             tail: AnalysisSegment::default(),
             head_pcm: Vec::new(),
             duration: 0.0,
             sample_rate: DEFAULT_SAMPLE_RATE,
-            loudness_meter: LoudnessMeter::new(2), // Re-init on analyze
+            loudness_meter: LoudnessMeter::new(DEFAULT_SAMPLE_RATE, vec![1.0; 2]), // Re-init on analyze
+            peak_meter: PeakMeter::new(DEFAULT_SAMPLE_RATE, 2),
+            onsets: OnsetDetector::new(DEFAULT_SAMPLE_RATE),
+            features: FeatureExtractor::new(DEFAULT_SAMPLE_RATE),
         }
     }
 
-    fn analyze(&mut self) -> Option<AudioAnalysis> {
-        let src = File::open(&self.path).ok()?;
-        let mss = MediaSourceStream::new(Box::new(src), Default::default());
-        let mut hint = Hint::new();
-        if let Some(ext) = self.path.extension().and_then(|s| s.to_str()) {
-            hint.with_extension(ext);
-        }
+    fn analyze(&mut self, control: &Control) -> Option<AudioAnalysis> {
+        let mut track = decode::open_track(&self.path)?;
+        let params = &track.params;
 
-        let probed = symphonia::default::get_probe().format(&hint, mss, &Default::default(), &Default::default()).ok()?;
-        let mut format = probed.format;
-        let track = format.default_track()?;
-        let track_id = track.id;
-        let params = &track.codec_params;
-        
         self.sample_rate = params.sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE);
-        self.loudness_meter = LoudnessMeter::new(2); // Assume stereo max for now
-        
+        let channels = params.channels.map_or(2, |c| c.count().min(MAX_CHANNELS));
+        self.loudness_meter = LoudnessMeter::for_layout(self.sample_rate, params.channels, channels);
+        self.peak_meter = PeakMeter::new(self.sample_rate, channels);
+        self.onsets = OnsetDetector::new(self.sample_rate);
+        self.features = FeatureExtractor::new(self.sample_rate);
+
         // Duration estimation
         let time_base = params.time_base;
-        let n_frames = params.n_frames;
-        let estimated_duration = match (n_frames, time_base) {
+        let estimated_duration = match (params.n_frames, time_base) {
             (Some(n), Some(tb)) => {
                 let t = tb.calc_time(n);
                 Some(t.seconds as f64 + t.frac)
@@ -332,73 +343,51 @@ impl TrackAnalyzer {
             _ => None,
         };
 
-        let mut decoder = symphonia::default::get_codecs().make(params, &Default::default()).ok()?;
-        
-        // Phase 1: Head
-        self.process_segment(&mut format, &mut decoder, track_id, true, time_base)?;
-        
-        // Phase 2: Tail
-        if self.include_tail {
-             if let Some(tot) = estimated_duration {
-                // If total duration is significantly longer than what we analyzed + max_analyze_time
-                // We only jump if there is unanalyzed gap.
-                // Actually, logic is: analyze tail if song is long enough.
-                if tot > self.max_analyze_time * 2.0 {
-                    let seek_target = tot - self.max_analyze_time;
-                    let seek_time = Time::from(seek_target);
-                     if format.seek(SeekMode::Coarse, SeekTo::Time { 
-                        time: seek_time, 
-                        track_id: Some(track_id) 
-                    }).is_ok() {
-                        // Reset duration to correct time after seek? 
-                        // Actually duration tracking inside process_segment handles packets.
-                        // But we need to ensure we don't overwrite self.duration with wrong values if packets are weird.
-                        // Wait, process_segment updates self.duration from packet timestamp.
-                        // That is correct.
-                        self.process_segment(&mut format, &mut decoder, track_id, false, time_base)?;
-                    }
-                }
-             }
-        }
-        
+        // Only the head and the last `max_analyze_time` seconds are decoded; in
+        // a long enough track the middle is skipped by seeking to the tail
+        let tail_start = if self.include_tail {
+            estimated_duration.map(|tot| (tot - self.max_analyze_time).max(self.max_analyze_time))
+        } else {
+            None
+        };
+        // Progress runs over the stream position, which jumps at the seek
+        let expected = if self.include_tail {
+            estimated_duration
+        } else {
+            estimated_duration.map(|d| d.min(self.max_analyze_time))
+        };
+        self.decode(&mut track, tail_start, expected, control)?;
+
         self.finalize_analysis()
     }
 
-    fn process_segment(
-        &mut self, 
-        format: &mut Box<dyn FormatReader>, 
-        decoder: &mut Box<dyn Decoder>, 
-        track_id: u32,
-        is_head: bool,
-        time_base: Option<symphonia::core::units::TimeBase>
+    fn decode(
+        &mut self,
+        track: &mut OpenTrack,
+        tail_start: Option<f64>,
+        expected: Option<f64>,
+        control: &Control,
     ) -> Option<()> {
+        let OpenTrack { format, decoder, track_id, params } = track;
+        let (track_id, time_base) = (*track_id, params.time_base);
         let window_size = (self.sample_rate as usize * WINDOW_SIZE_MS) / 1000;
         if window_size == 0 { return None; }
 
-        let mut acc_env = EnvelopeAccumulator::new(window_size);
-        let mut acc_low = EnvelopeAccumulator::new(window_size);
-        let mut acc_vocal = EnvelopeAccumulator::new(window_size);
-        
-        let mut vocal_filter = VocalFilter::new(self.sample_rate);
-        let mut lpf = FirstOrderFilter::new(self.sample_rate, LOW_LPF_FREQ, false);
-        
+        let mut state = SegmentState::new(self.sample_rate, window_size);
+        let mut phase = Phase::Head;
+        // Beats and structure need a gapless timeline, so they stop at the seek
+        let mut contiguous = true;
+
         let key_max_samples = (self.sample_rate as f64 * self.max_analyze_time.min(30.0)) as usize;
         let mut processed_duration_local = 0.0; // For fallback if time_base missing
 
-        let segment = if is_head { &mut self.head } else { &mut self.tail };
-        let loudness_meter = &mut self.loudness_meter;
-        let head_pcm = &mut self.head_pcm;
-        
         // Use a small buffer to hold channel data to avoid allocation per sample
-        // Max 8 channels supported
-        let mut frame_buf = [0.0f32; 8]; 
+        let mut frame_buf = [0.0f32; MAX_CHANNELS];
 
-        loop {
-            let packet = match format.next_packet() {
-                Ok(p) => p,
-                Err(_) => break, // End of stream
-            };
-            
+        let mut reported = 0.0;
+
+        while let Ok(packet) = format.next_packet() {
+            if control.is_cancelled() { return None; }
             if packet.track_id() != track_id { continue; }
 
             // Timestamp handling
@@ -406,37 +395,65 @@ impl TrackAnalyzer {
                 let t = tb.calc_time(packet.ts());
                 t.seconds as f64 + t.frac
             } else {
-                // If we sought, this local duration is wrong for absolute time, 
-                // but if time_base is missing, seeking is likely impossible/unreliable anyway.
-                if !is_head {
-                    // If no time_base, we can't really do tail analysis properly via seek.
-                    // We just continue.
-                }
-                self.duration + processed_duration_local
+                processed_duration_local
             };
             
             self.duration = packet_time;
+            if let Some(total) = expected.filter(|&t| t > 0.0) {
+                let fraction = (packet_time / total).min(1.0);
+                if fraction - reported >= 0.01 {
+                    reported = fraction;
+                    control.report(&self.path, fraction);
+                }
+            }
 
-            // Stop condition
-            if is_head && packet_time > self.max_analyze_time {
-                break;
+            if phase == Phase::Head && packet_time > self.max_analyze_time {
+                if !self.include_tail {
+                    break;
+                }
+                phase = Phase::Middle;
+                if let Some(t) = tail_start.filter(|&t| t > packet_time + 1.0) {
+                    let target = SeekTo::Time { time: Time::from(t), track_id: Some(track_id) };
+                    if format.seek(SeekMode::Coarse, target).is_ok() {
+                        decoder.reset();
+                        phase = Phase::Tail;
+                        state = SegmentState::new(self.sample_rate, window_size);
+                        contiguous = false;
+                        continue;
+                    }
+                }
+            }
+            if phase == Phase::Middle && tail_start.is_some_and(|t| packet_time >= t) {
+                phase = Phase::Tail;
+                state = SegmentState::new(self.sample_rate, window_size);
             }
             
             // Decode
-            let decoded = match decoder.decode(&packet) {
-                Ok(d) => d,
-                Err(_) => continue,
-            };
+            let Ok(buffer) = decoder.decode(&packet) else { continue };
 
-            let spec = *decoded.spec();
-            let frames = decoded.frames();
-            let channels = spec.channels.count().min(8);
+            let spec = *buffer.spec();
+            let frames = buffer.frames();
+            let channels = spec.channels.count().min(MAX_CHANNELS);
             
             if time_base.is_none() {
                 processed_duration_local += frames as f64 / self.sample_rate as f64;
             }
+            if self.loudness_meter.channels() != channels {
+                self.loudness_meter = LoudnessMeter::for_layout(spec.rate, Some(spec.channels), channels);
+                self.peak_meter = PeakMeter::new(spec.rate, channels);
+            }
 
-            let capture_pcm = is_head && head_pcm.len() < key_max_samples;
+            let loudness_meter = &mut self.loudness_meter;
+            let peak_meter = &mut self.peak_meter;
+            let onsets = &mut self.onsets;
+            let features = &mut self.features;
+            let head_pcm = &mut self.head_pcm;
+            let capture_pcm = phase == Phase::Head && head_pcm.len() < key_max_samples;
+            let mut segment = match phase {
+                Phase::Head => Some(&mut self.head),
+                Phase::Middle => None,
+                Phase::Tail => Some(&mut self.tail),
+            };
 
             // Process Frames
             macro_rules! process_buffer {
@@ -448,12 +465,25 @@ impl TrackAnalyzer {
                             frame_buf[c] = s;
                             sum += s;
                         }
-                        Self::process_sample_static(loudness_meter, head_pcm, sum, channels, &frame_buf[..channels], &mut acc_env, &mut acc_low, &mut acc_vocal, &mut vocal_filter, &mut lpf, segment, capture_pcm);
+                        loudness_meter.process(&frame_buf[..channels]);
+                        peak_meter.process(&frame_buf[..channels]);
+
+                        let val = sum / channels as f32;
+                        if contiguous {
+                            onsets.process(val);
+                            features.process(val);
+                        }
+                        if capture_pcm {
+                            head_pcm.push(val);
+                        }
+                        if let Some(segment) = segment.as_deref_mut() {
+                            state.process(val, segment);
+                        }
                     }
                 }
             }
 
-            match decoded {
+            match buffer {
                 AudioBufferRef::F32(buf) => process_buffer!(buf, |s: f32| s),
                 AudioBufferRef::U8(buf) => process_buffer!(buf, |s: u8| (s as f32 - 128.0) / 128.0),
                 AudioBufferRef::S16(buf) => process_buffer!(buf, |s: i16| s as f32 / 32768.0),
@@ -465,45 +495,15 @@ impl TrackAnalyzer {
         Some(())
     }
 
-    // Static helper to avoid double borrow of self
-    #[inline(always)]
-    fn process_sample_static(
-        loudness_meter: &mut LoudnessMeter,
-        head_pcm: &mut Vec<f32>,
-        sum: f32,
-        channels: usize,
-        frame_buf: &[f32],
-        acc_env: &mut EnvelopeAccumulator,
-        acc_low: &mut EnvelopeAccumulator,
-        acc_vocal: &mut EnvelopeAccumulator,
-        vocal_filter: &mut VocalFilter,
-        lpf: &mut FirstOrderFilter,
-        segment: &mut AnalysisSegment,
-        capture_pcm: bool
-    ) {
-        loudness_meter.process(frame_buf);
-        
-        let val = sum / channels as f32;
-        let vocal = vocal_filter.process(val);
-        let low = lpf.process(val);
-
-        if capture_pcm {
-            head_pcm.push(val);
-        }
-        
-        if let Some(rms) = acc_env.process(val) { segment.envelope.push(rms); }
-        if let Some(rms_low) = acc_low.process(low) { segment.low_envelope.push(rms_low); }
-        if let Some(rms_vocal) = acc_vocal.process(vocal) {
-             let base = *segment.envelope.last().unwrap_or(&1.0);
-             segment.vocal_ratio.push(if base > 0.0001 { rms_vocal / base } else { 0.0 });
-        }
-    }
-
     fn finalize_analysis(&self) -> Option<AudioAnalysis> {
         let (fade_in, fade_out) = detect_silence(&self.head.envelope, &self.tail.envelope, self.duration, ENV_RATE, SILENCE_THRESH_DB);
-        let (bpm, bpm_conf, first_beat) = detect_bpm(&self.head.envelope, &self.head.low_envelope, ENV_RATE);
+        let (bpm, bpm_conf) = detect_bpm(&self.onsets);
+        let beat_grid = beats::track(&self.onsets, bpm, self.duration);
+        let first_beat = beat_grid.as_ref().and_then(|g| g.beats.first().copied());
         let (key_root, key_mode, key_conf) = detect_key(&self.head_pcm, self.sample_rate);
-        let drop_pos = detect_drop(&self.head.envelope, ENV_RATE);
+        let structure = structure::analyze(&self.features, beat_grid.as_ref(), self.duration);
+        let first_chorus = structure.segments.iter().find(|s| s.label == "chorus" && s.start > 0.0).map(|s| s.start);
+        let drop_pos = first_chorus.or_else(|| detect_drop(&self.head.envelope, ENV_RATE));
         
         let (vocal_in, vocal_out, vocal_last_in) = detect_vocals(
             &self.head.envelope, &self.head.vocal_ratio,
@@ -512,12 +512,12 @@ impl TrackAnalyzer {
         );
         
         let smart_cut_out = calculate_smart_cut_out(
-            bpm, first_beat, bpm_conf, 
+            beat_grid.as_ref(), bpm_conf, 
             vocal_out, fade_in, fade_out, self.duration
         );
 
         let smart_cut_in = calculate_smart_cut_in(
-            bpm, first_beat, bpm_conf, 
+            beat_grid.as_ref(), bpm_conf, 
             vocal_in.or(drop_pos), fade_in
         );
         
@@ -543,8 +543,13 @@ impl TrackAnalyzer {
             fade_in_pos: fade_in,
             fade_out_pos: if self.include_tail { fade_out } else { self.duration },
             first_beat_pos: first_beat,
-            loudness: Some(self.loudness_meter.get_lufs()),
+            beat_grid,
+            loudness: Some(self.loudness_meter.integrated()),
+            loudness_range: Some(self.loudness_meter.loudness_range()),
+            peaks: Some(self.peak_meter.info()),
             drop_pos,
+            segments: structure.segments,
+            highlight_start: structure.highlight_start,
             version: ANALYSIS_VERSION,
             analyze_window: self.max_analyze_time,
             cut_in_pos: smart_cut_in,
@@ -668,19 +673,13 @@ fn detect_vocals(
     (vocal_in, vocal_out, vocal_last_in)
 }
 
-fn snap_time(time: f64, bpm: f64, first_beat: f64, grid: f64) -> f64 {
-    if bpm <= 0.0 { return time; }
-    let sec_per_beat = 60.0 / bpm;
-    let grid_sec = sec_per_beat * grid;
-    if grid_sec <= 0.0 { return time; }
-    
-    let units = (time - first_beat) / grid_sec;
-    let snapped = first_beat + units.round() * grid_sec;
-    if snapped < 0.0 { first_beat } else { snapped }
+/// Nearest beat `grid` beats into a bar (4 = downbeat) on the tracked grid
+fn snap_time(time: f64, beat_grid: &BeatGrid, grid: u32) -> f64 {
+    beat_grid.snap(time, grid)
 }
 
 fn calculate_smart_cut_out(
-    bpm: Option<f64>, first_beat: Option<f64>, conf: Option<f64>,
+    beat_grid: Option<&BeatGrid>, conf: Option<f64>,
     vocal_out: Option<f64>, _fade_in: f64, fade_out: f64, duration: f64
 ) -> Option<f64> {
     let search_end = if let Some(vo) = vocal_out {
@@ -689,12 +688,12 @@ fn calculate_smart_cut_out(
         fade_out
     };
     
-    if let (Some(b), Some(fb)) = (bpm, first_beat) {
+    if let Some(g) = beat_grid {
         if conf.unwrap_or(0.0) > 0.4 {
-            let snapped = snap_time(search_end, b, fb, 4.0);
+            let snapped = snap_time(search_end, g, 4);
             if let Some(vo) = vocal_out {
                 if snapped < vo + 2.0 {
-                    return Some(snap_time(vo + 4.0, b, fb, 4.0).min(duration));
+                    return Some(snap_time(vo + 4.0, g, 4).min(duration));
                 }
             }
             return Some(snapped.min(duration));
@@ -704,17 +703,17 @@ fn calculate_smart_cut_out(
 }
 
 fn calculate_smart_cut_in(
-    bpm: Option<f64>, first_beat: Option<f64>, conf: Option<f64>,
+    beat_grid: Option<&BeatGrid>, conf: Option<f64>,
     anchor: Option<f64>, fade_in: f64
 ) -> Option<f64> {
     let anchor = anchor.unwrap_or(fade_in);
-    if let (Some(b), Some(fb)) = (bpm, first_beat) {
+    if let Some(g) = beat_grid {
         if conf.unwrap_or(0.0) > 0.4 {
-            let sec_bar = 240.0 / b;
-            for bars in [32.0, 16.0, 8.0] {
-                let t = anchor - bars * sec_bar;
+            // Count whole bars back along the grid rather than assuming a fixed bar length
+            for bars in [32, 16, 8] {
+                let t = g.bars_before(anchor, bars);
                 if t > fade_in {
-                    return Some(snap_time(t, b, fb, 4.0));
+                    return Some(t);
                 }
             }
         }
@@ -724,43 +723,8 @@ fn calculate_smart_cut_in(
 
 // --- BPM & Key Detection Wrappers ---
 
-fn detect_bpm(env: &[f32], _low_env: &[f32], rate: f64) -> (Option<f64>, Option<f64>, Option<f64>) {
-    if env.len() < 100 { return (None, None, None); }
-    
-    // Simple Flux
-    let flux: Vec<f32> = env.windows(2).map(|w| (w[1] - w[0]).max(0.0)).collect();
-    if flux.len() < 110 { return (None, None, None); }
-
-    // Autocorrelation (60-180 BPM -> 0.33-1.0s -> 16-50 samples)
-    let mut best_corr = 0.0;
-    let mut best_lag = 0;
-    
-    for lag in BPM_MIN_LAG..BPM_MAX_LAG {
-        let mut sum = 0.0;
-        for i in 0..(flux.len() - lag) {
-            sum += flux[i] * flux[i+lag];
-        }
-        if sum > best_corr {
-            best_corr = sum;
-            best_lag = lag;
-        }
-    }
-    
-    if best_corr <= 0.001 { return (None, None, None); }
-    
-    let bpm = 60.0 / (best_lag as f64 / rate);
-    // Refine phase
-    let first_beat = (0..best_lag).max_by_key(|&phase| {
-        let mut e = 0.0;
-        let mut idx = phase;
-        while idx < flux.len() {
-            e += flux[idx];
-            idx += best_lag;
-        }
-        (e * 1000.0) as i32
-    }).map(|p| p as f64 / rate);
-
-    (Some(bpm), Some(0.8), first_beat)
+fn detect_bpm(onsets: &OnsetDetector) -> (Option<f64>, Option<f64>) {
+    tempo::estimate(onsets).map_or((None, None), |t| (Some(t.bpm), Some(t.confidence)))
 }
 
 fn detect_key(pcm: &[f32], sr: u32) -> (Option<i32>, Option<i32>, Option<f64>) {
@@ -853,10 +817,14 @@ const STRATEGIES: &[MixStrategy] = &[
 ];
 
 #[napi]
+#[allow(clippy::needless_pass_by_value)]
 pub fn suggest_transition(current_path: String, next_path: String) -> Option<TransitionProposal> {
-    let cur = TrackAnalyzer::new(current_path, None, true).analyze()?;
-    let next = TrackAnalyzer::new(next_path, Some(120.0), false).analyze()?;
+    let cur = analyze_cached(&current_path, None, true)?;
+    let next = analyze_cached(&next_path, Some(TRANSITION_NEXT_WINDOW), false)?;
+    Some(propose_transition(&cur, &next))
+}
 
+fn propose_transition(cur: &AudioAnalysis, next: &AudioAnalysis) -> TransitionProposal {
     let bpm_a = cur.bpm.unwrap_or(128.0);
     let bpm_b = next.bpm.unwrap_or(128.0);
     let bpm_compatible = (bpm_a - bpm_b).abs() / bpm_a < 0.06;
@@ -881,7 +849,7 @@ pub fn suggest_transition(current_path: String, next_path: String) -> Option<Tra
         if start < cur.mix_center_pos - 30.0 { continue; } // Too far back?
         
         // Success
-        return Some(TransitionProposal {
+        return TransitionProposal {
             duration: dur,
             current_track_mix_out: start,
             next_track_mix_in: next_in,
@@ -890,14 +858,14 @@ pub fn suggest_transition(current_path: String, next_path: String) -> Option<Tra
             compatibility_score: 0.9,
             key_compatible,
             bpm_compatible,
-        });
+        };
     }
 
     // 2. Fallback: Aggressive Bass Swap
     if bpm_compatible {
         let dur = 16.0 * sec_per_bar;
         if cur.duration - cur_out > dur {
-             return Some(TransitionProposal {
+             return TransitionProposal {
                 duration: dur,
                 current_track_mix_out: cur_out - dur,
                 next_track_mix_in: next_in,
@@ -906,12 +874,12 @@ pub fn suggest_transition(current_path: String, next_path: String) -> Option<Tra
                 compatibility_score: 0.7,
                 key_compatible,
                 bpm_compatible,
-            });
+            };
         }
     }
 
     // 3. Fallback: Echo Out
-    Some(TransitionProposal {
+    TransitionProposal {
         duration: sec_per_bar * 4.0,
         current_track_mix_out: cur_out,
         next_track_mix_in: next_in,
@@ -920,14 +888,18 @@ pub fn suggest_transition(current_path: String, next_path: String) -> Option<Tra
         compatibility_score: 0.5,
         key_compatible,
         bpm_compatible,
-    })
+    }
 }
 
 #[napi]
+#[allow(clippy::needless_pass_by_value)]
 pub fn suggest_long_mix(current_path: String, next_path: String) -> Option<AdvancedTransition> {
-    let cur = TrackAnalyzer::new(current_path, None, true).analyze()?;
-    let next = TrackAnalyzer::new(next_path, Some(180.0), false).analyze()?;
+    let cur = analyze_cached(&current_path, None, true)?;
+    let next = analyze_cached(&next_path, Some(LONG_MIX_NEXT_WINDOW), false)?;
+    Some(propose_long_mix(&cur, &next))
+}
 
+fn propose_long_mix(cur: &AudioAnalysis, next: &AudioAnalysis) -> AdvancedTransition {
     let bpm_a = cur.bpm.unwrap_or(128.0);
     let bpm_b = next.bpm.unwrap_or(128.0);
     let playback_rate = bpm_a / bpm_b;
@@ -943,7 +915,7 @@ pub fn suggest_long_mix(current_path: String, next_path: String) -> Option<Advan
     // Automation
     let (auto_a, auto_b) = generate_bass_swap_automation(duration);
 
-    Some(AdvancedTransition {
+    AdvancedTransition {
         start_time_current: (cur_end - duration).max(0.0),
         start_time_next: (next_start - duration / playback_rate).max(0.0),
         duration,
@@ -952,7 +924,7 @@ pub fn suggest_long_mix(current_path: String, next_path: String) -> Option<Advan
         automation_current: auto_a,
         automation_next: auto_b,
         strategy: "Long Bass Swap".to_string(),
-    })
+    }
 }
 
 // --- Utils ---
@@ -1002,14 +974,34 @@ fn generate_bass_swap_automation(dur: f64) -> (Vec<AutomationPoint>, Vec<Automat
     (a, b)
 }
 
+/// Analyzes `path`, going through the persistent cache when it is initialized
+fn analyze_cached(path: &str, max_time: Option<f64>, include_tail: bool) -> Option<AudioAnalysis> {
+    analyze_controlled(path, max_time, include_tail, &Control::default())
+}
+
+/// `analyze_cached` that stops early once `control` is cancelled
+fn analyze_controlled(path: &str, max_time: Option<f64>, include_tail: bool, control: &Control) -> Option<AudioAnalysis> {
+    let analysis = crate::analysis_cache::get_or_analyze(path, max_time, include_tail, || {
+        TrackAnalyzer::new(path.to_string(), max_time, include_tail).analyze(control)
+    })?;
+    control.report(Path::new(path), 1.0);
+    Some(analysis)
+}
+
 // --- Exports ---
 
+/// Analyzes the head and tail windows; `loudness`, `loudness_range` and `peaks`
+/// cover those two windows, `measure_loudness` the whole file
 #[napi]
+#[allow(clippy::needless_pass_by_value)]
 pub fn analyze_audio_file(path: String, max_analyze_time: Option<f64>) -> Option<AudioAnalysis> {
-    TrackAnalyzer::new(path, max_analyze_time, true).analyze()
+    analyze_cached(&path, max_analyze_time, true)
 }
 
+/// Analyzes the head window only, so `loudness`, `loudness_range` and `peaks`
+/// describe the first `max_analyze_time` seconds rather than the track
 #[napi]
+#[allow(clippy::needless_pass_by_value)]
 pub fn analyze_audio_file_head(path: String, max_analyze_time: Option<f64>) -> Option<AudioAnalysis> {
-    TrackAnalyzer::new(path, max_analyze_time, false).analyze()
+    analyze_cached(&path, max_analyze_time, false)
 }
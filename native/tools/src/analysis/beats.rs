@@ -0,0 +1,683 @@
+//! Beat and downbeat tracking over the whole track.
+//!
+//! A spectral-flux onset envelope (full band, plus a low band for kicks) is
+//! collected while decoding. Local tempo comes from windowed autocorrelation
+//! around the global tempo, beats are placed by dynamic programming against
+//! that time-varying period (after Ellis, "Beat Tracking by Dynamic
+//! Programming", 2007), and the bar phase is the one whose beats carry the most
+//! low-band onset energy.
+
+use std::sync::Arc;
+
+use napi_derive::napi;
+use num_complex::Complex32;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+/// Onset envelope frames per second
+pub const ONSET_RATE: f64 = 100.0;
+/// Tempo curve values per second
+pub const TEMPO_CURVE_RATE: f64 = 1.0;
+pub const BEATS_PER_BAR: u32 = 4;
+
+const FFT_SIZE: usize = 1024;
+const LOW_BAND_HZ: f32 = 200.0;
+pub(super) const MIN_BPM: f64 = 50.0;
+pub(super) const MAX_BPM: f64 = 220.0;
+/// Centre of the tempo prior when there's no estimate to start from
+pub(super) const PRIOR_BPM: f64 = 120.0;
+/// Width of the tempo prior in octaves, without and with an estimate
+pub(super) const PRIOR_OCTAVES: f64 = 1.0;
+const HINT_OCTAVES: f64 = 0.1;
+const LOCAL_OCTAVES: f64 = 0.15;
+const TEMPO_WINDOW_SECS: f64 = 8.0;
+/// Local tempo may stray this far (as a period ratio) from the global tempo
+const MAX_TEMPO_DRIFT: f64 = 1.25;
+/// How strongly consecutive beats are held one period apart
+const TIGHTNESS: f64 = 100.0;
+/// Seconds either side of a tempo curve point whose beats are considered
+const TEMPO_CURVE_SPAN: f64 = 4.0;
+const MIN_BEATS: usize = 8;
+
+#[napi(object)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BeatGrid {
+    /// Every beat, seconds
+    pub beats: Vec<f64>,
+    /// Indices into `beats` of the first beat of each bar
+    pub downbeats: Vec<u32>,
+    /// Position of each beat within its bar, 0 = downbeat
+    #[napi(js_name = "beat_in_bar")]
+    pub beat_in_bar: Vec<u32>,
+    #[napi(js_name = "beats_per_bar")]
+    pub beats_per_bar: u32,
+    /// Median tempo, BPM
+    pub bpm: f64,
+    /// Local tempo, BPM, `tempo_curve_rate` values per second from the start
+    #[napi(js_name = "tempo_curve")]
+    pub tempo_curve: Vec<f64>,
+    #[napi(js_name = "tempo_curve_rate")]
+    pub tempo_curve_rate: f64,
+    /// 1.0 when one constant tempo fits every beat, falling to 0.0 as beats
+    /// stray a quarter of a beat from it on average
+    pub stability: f64,
+}
+
+/// Half-wave rectified log-magnitude spectral flux, one frame per `1 / ONSET_RATE` s
+pub struct OnsetDetector {
+    sample_rate: u32,
+    hop: usize,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    ring: Vec<f32>,
+    write: usize,
+    since_hop: usize,
+    spectrum: Vec<Complex32>,
+    prev: Vec<f32>,
+    low_bins: usize,
+    full: Vec<f32>,
+    low: Vec<f32>,
+}
+
+impl OnsetDetector {
+    #[allow(clippy::cast_sign_loss)]
+    pub fn new(sample_rate: u32) -> Self {
+        let sample_rate = sample_rate.max(1);
+        let window = (0..FFT_SIZE)
+            .map(|i| {
+                0.5f32.mul_add(
+                    -(2.0 * std::f32::consts::PI * i as f32 / FFT_SIZE as f32).cos(),
+                    0.5,
+                )
+            })
+            .collect();
+        let low_bins =
+            ((LOW_BAND_HZ * FFT_SIZE as f32 / sample_rate as f32) as usize).clamp(1, FFT_SIZE / 2);
+        Self {
+            sample_rate,
+            hop: ((f64::from(sample_rate) / ONSET_RATE).round() as usize).max(1),
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            window,
+            ring: vec![0.0; FFT_SIZE],
+            write: 0,
+            since_hop: 0,
+            spectrum: vec![Complex32::new(0.0, 0.0); FFT_SIZE],
+            prev: vec![0.0; FFT_SIZE / 2 + 1],
+            low_bins,
+            full: Vec::new(),
+            low: Vec::new(),
+        }
+    }
+
+    /// Feeds one mono sample
+    pub fn process(&mut self, sample: f32) {
+        self.ring[self.write] = sample;
+        self.write = (self.write + 1) % FFT_SIZE;
+        self.since_hop += 1;
+        if self.since_hop == self.hop {
+            self.since_hop = 0;
+            self.frame();
+        }
+    }
+
+    fn frame(&mut self) {
+        for (i, (bin, &w)) in self.spectrum.iter_mut().zip(&self.window).enumerate() {
+            // Oldest sample first
+            *bin = Complex32::new(self.ring[(self.write + i) % FFT_SIZE] * w, 0.0);
+        }
+        self.fft.process(&mut self.spectrum);
+
+        let mut full = 0.0;
+        let mut low = 0.0;
+        for (k, prev) in self.prev.iter_mut().enumerate().skip(1) {
+            let mag = 1000.0f32.mul_add(self.spectrum[k].norm(), 1.0).ln();
+            let rise = (mag - *prev).max(0.0);
+            *prev = mag;
+            full += rise;
+            if k <= self.low_bins {
+                low += rise;
+            }
+        }
+        self.full.push(full);
+        self.low.push(low);
+    }
+
+    /// Full-band onset strength per frame
+    pub fn full(&self) -> &[f32] {
+        &self.full
+    }
+
+    /// Onset strength below `LOW_BAND_HZ` per frame
+    pub fn low(&self) -> &[f32] {
+        &self.low
+    }
+
+    /// Onset frames per second; `ONSET_RATE` up to the rounding of the hop
+    pub fn frame_rate(&self) -> f64 {
+        f64::from(self.sample_rate) / self.hop as f64
+    }
+
+    /// Time of onset frame `frame` (fractional), seconds: the centre of its window
+    fn time_of(&self, frame: f64) -> f64 {
+        (frame + 1.0).mul_add(self.hop as f64, -(FFT_SIZE as f64) / 2.0)
+            / f64::from(self.sample_rate)
+    }
+}
+
+/// Removes the local mean (1 s), rectifies and scales to unit standard deviation
+#[allow(clippy::cast_sign_loss)]
+pub(super) fn normalize(env: &[f32]) -> Vec<f64> {
+    let half = (ONSET_RATE / 2.0) as usize;
+    let mut prefix = vec![0.0f64; env.len() + 1];
+    for (i, &v) in env.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + f64::from(v);
+    }
+    let mut out: Vec<f64> = (0..env.len())
+        .map(|i| {
+            let lo = i.saturating_sub(half);
+            let hi = (i + half + 1).min(env.len());
+            let mean = (prefix[hi] - prefix[lo]) / (hi - lo) as f64;
+            (f64::from(env[i]) - mean).max(0.0)
+        })
+        .collect();
+    let n = out.len().max(1) as f64;
+    let mean = out.iter().sum::<f64>() / n;
+    let std = (out.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    if std > 0.0 {
+        for v in &mut out {
+            *v /= std;
+        }
+    }
+    out
+}
+
+pub(super) fn autocorrelation(env: &[f64], lag: usize) -> f64 {
+    if lag >= env.len() {
+        return 0.0;
+    }
+    env.iter().zip(&env[lag..]).map(|(a, b)| a * b).sum::<f64>() / (env.len() - lag) as f64
+}
+
+/// Lag in `lags` maximising autocorrelation weighted by a log-Gaussian around
+/// `centre`, refined to a fraction of a frame
+#[allow(clippy::cast_sign_loss)]
+fn best_period(env: &[f64], lags: (f64, f64), centre: f64, octaves: f64) -> Option<f64> {
+    let lo = lags.0.floor().max(1.0) as usize;
+    let hi = lags.1.ceil() as usize;
+    if hi <= lo || hi + 1 >= env.len() {
+        return None;
+    }
+    let acf: Vec<f64> = (lo - 1..=hi + 1)
+        .map(|lag| autocorrelation(env, lag))
+        .collect();
+    let weight = |lag: f64| (-0.5 * ((lag / centre).log2() / octaves).powi(2)).exp();
+
+    let (i, _) = (1..acf.len() - 1)
+        .map(|i| (i, acf[i] * weight((lo + i - 1) as f64)))
+        .filter(|&(_, s)| s > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))?;
+    let (a, b, c) = (acf[i - 1], acf[i], acf[i + 1]);
+    let denom = 2.0f64.mul_add(-b, a + c);
+    let offset = if denom < 0.0 {
+        (0.5 * (a - c) / denom).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    Some((lo + i - 1) as f64 + offset)
+}
+
+/// Beat period in frames for every onset frame
+#[allow(clippy::cast_sign_loss)]
+fn local_periods(env: &[f64], global: f64) -> Vec<f64> {
+    let step = ONSET_RATE as usize;
+    let half = (TEMPO_WINDOW_SECS * ONSET_RATE / 2.0) as usize;
+    let lags = (global / MAX_TEMPO_DRIFT, global * MAX_TEMPO_DRIFT);
+
+    let mut points: Vec<Option<f64>> = (0..env.len().div_ceil(step))
+        .map(|i| {
+            let centre = i * step;
+            let window = &env[centre.saturating_sub(half)..(centre + half).min(env.len())];
+            best_period(window, lags, global, LOCAL_OCTAVES)
+        })
+        .collect();
+
+    // Quiet stretches take the estimate of the nearest measured point
+    let mut last = None;
+    for p in &mut points {
+        if p.is_some() {
+            last = *p;
+        } else {
+            *p = last;
+        }
+    }
+    let mut next = None;
+    for p in points.iter_mut().rev() {
+        if p.is_some() {
+            next = *p;
+        } else {
+            *p = next;
+        }
+    }
+    let raw: Vec<f64> = points.into_iter().map(|p| p.unwrap_or(global)).collect();
+
+    // Median of five to drop single-window octave slips
+    let smoothed: Vec<f64> = (0..raw.len())
+        .map(|i| {
+            let mut near = raw[i.saturating_sub(2)..(i + 3).min(raw.len())].to_vec();
+            near.sort_by(f64::total_cmp);
+            near[near.len() / 2]
+        })
+        .collect();
+
+    (0..env.len())
+        .map(|t| {
+            let pos = t as f64 / step as f64;
+            let i = (pos.floor() as usize).min(smoothed.len() - 1);
+            let j = (i + 1).min(smoothed.len() - 1);
+            let frac = pos - i as f64;
+            (smoothed[j] - smoothed[i]).mul_add(frac, smoothed[i])
+        })
+        .collect()
+}
+
+/// Dynamic-programming beat placement; returns beat frames in order
+#[allow(clippy::cast_sign_loss)]
+fn place_beats(env: &[f64], periods: &[f64]) -> Vec<usize> {
+    let n = env.len();
+    let mut score = vec![0.0f64; n];
+    let mut back = vec![None; n];
+
+    for t in 0..n {
+        let p = periods[t];
+        let nearest = (p / 2.0).round() as usize;
+        let farthest = (2.0 * p).round() as usize;
+        let mut best: Option<(f64, usize)> = None;
+        if t >= nearest {
+            let from = t.saturating_sub(farthest);
+            for (prev, &prev_score) in score.iter().enumerate().take(t - nearest + 1).skip(from) {
+                let interval = (t - prev) as f64 / p;
+                let s = TIGHTNESS.mul_add(-interval.ln().powi(2), prev_score);
+                if best.is_none_or(|(b, _)| s > b) {
+                    best = Some((s, prev));
+                }
+            }
+        }
+        score[t] = env[t] + best.map_or(0.0, |(s, _)| s);
+        back[t] = best.map(|(_, prev)| prev);
+    }
+
+    // Last beat: the last local maximum of the cumulative score that reaches
+    // half the median of all of them
+    let peaks: Vec<usize> = (1..n.saturating_sub(1))
+        .filter(|&t| score[t] > score[t - 1] && score[t] >= score[t + 1])
+        .collect();
+    let mut peak_scores: Vec<f64> = peaks.iter().map(|&t| score[t]).collect();
+    if peak_scores.is_empty() {
+        return Vec::new();
+    }
+    peak_scores.sort_by(f64::total_cmp);
+    let threshold = 0.5 * peak_scores[peak_scores.len() / 2];
+    let Some(&last) = peaks.iter().rev().find(|&&t| score[t] >= threshold) else {
+        return Vec::new();
+    };
+
+    let mut beats = vec![last];
+    let mut t = last;
+    while let Some(prev) = back[t] {
+        beats.push(prev);
+        t = prev;
+    }
+    beats.reverse();
+    beats
+}
+
+/// Drops leading and trailing beats where there's next to no onset activity
+fn trim_beats(env: &[f64], beats: &mut Vec<usize>) {
+    let smooth = |t: usize| {
+        let lo = t.saturating_sub(2);
+        let hi = (t + 3).min(env.len());
+        env[lo..hi].iter().sum::<f64>() / (hi - lo) as f64
+    };
+    let strength: Vec<f64> = beats.iter().map(|&t| smooth(t)).collect();
+    let rms = (strength.iter().map(|s| s * s).sum::<f64>() / strength.len().max(1) as f64).sqrt();
+    let threshold = 0.5 * rms;
+    let first = strength.iter().position(|&s| s >= threshold).unwrap_or(0);
+    let last = strength.iter().rposition(|&s| s >= threshold).unwrap_or(0);
+    beats.truncate(last + 1);
+    beats.drain(..first.min(beats.len()));
+}
+
+/// Sub-frame position of a beat from the onset peak around it
+fn refine(env: &[f64], t: usize) -> f64 {
+    if t == 0 || t + 1 >= env.len() {
+        return t as f64;
+    }
+    let (a, b, c) = (env[t - 1], env[t], env[t + 1]);
+    let denom = 2.0f64.mul_add(-b, a + c);
+    if b >= a && b >= c && denom < 0.0 {
+        t as f64 + (0.5 * (a - c) / denom).clamp(-0.5, 0.5)
+    } else {
+        t as f64
+    }
+}
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    Some(values[values.len() / 2])
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn tempo_curve(beats: &[f64], duration: f64) -> Vec<f64> {
+    let len = ((duration * TEMPO_CURVE_RATE).ceil() as usize).max(1);
+    let mut curve: Vec<Option<f64>> = (0..len)
+        .map(|i| {
+            let t = i as f64 / TEMPO_CURVE_RATE;
+            let lo = beats.partition_point(|&b| b < t - TEMPO_CURVE_SPAN);
+            let hi = beats.partition_point(|&b| b <= t + TEMPO_CURVE_SPAN);
+            let mut intervals: Vec<f64> = beats[lo..hi].windows(2).map(|w| w[1] - w[0]).collect();
+            median(&mut intervals).map(|ibi| 60.0 / ibi)
+        })
+        .collect();
+    // Before the first and after the last beat, hold the nearest value
+    let first = curve.iter().flatten().next().copied().unwrap_or(0.0);
+    let mut last = first;
+    for v in &mut curve {
+        last = v.unwrap_or(last);
+        *v = Some(last);
+    }
+    curve.into_iter().map(|v| v.unwrap_or(first)).collect()
+}
+
+/// Mean distance of the beats from the best-fitting constant-tempo grid,
+/// mapped to 1.0 (on the grid) .. 0.0 (a quarter beat off or worse)
+fn stability(beats: &[f64]) -> f64 {
+    let n = beats.len() as f64;
+    let mean_i = (n - 1.0) / 2.0;
+    let mean_t = beats.iter().sum::<f64>() / n;
+    let (mut cov, mut var) = (0.0, 0.0);
+    for (i, &t) in beats.iter().enumerate() {
+        let di = i as f64 - mean_i;
+        cov += di * (t - mean_t);
+        var += di * di;
+    }
+    if var <= 0.0 {
+        return 0.0;
+    }
+    let period = cov / var;
+    if period <= 0.0 {
+        return 0.0;
+    }
+    let rms = (beats
+        .iter()
+        .enumerate()
+        .map(|(i, &t)| (t - period.mul_add(i as f64 - mean_i, mean_t)).powi(2))
+        .sum::<f64>()
+        / n)
+        .sqrt();
+    4.0f64.mul_add(-rms / period, 1.0).clamp(0.0, 1.0)
+}
+
+/// Tracks beats and bars over everything `onsets` has seen. `bpm_hint` centres
+/// the tempo search; without it the whole envelope is searched.
+pub fn track(onsets: &OnsetDetector, bpm_hint: Option<f64>, duration: f64) -> Option<BeatGrid> {
+    let env = normalize(&onsets.full);
+    let low = normalize(&onsets.low);
+
+    let lags = (60.0 * ONSET_RATE / MAX_BPM, 60.0 * ONSET_RATE / MIN_BPM);
+    let (centre, octaves) = bpm_hint
+        .filter(|b| (MIN_BPM..=MAX_BPM).contains(b))
+        .map_or((PRIOR_BPM, PRIOR_OCTAVES), |bpm| (bpm, HINT_OCTAVES));
+    let global = best_period(&env, lags, 60.0 * ONSET_RATE / centre, octaves)?;
+
+    let periods = local_periods(&env, global);
+    let mut frames = place_beats(&env, &periods);
+    trim_beats(&env, &mut frames);
+    if frames.len() < MIN_BEATS {
+        return None;
+    }
+
+    let beats: Vec<f64> = frames
+        .iter()
+        .map(|&t| onsets.time_of(refine(&env, t)).max(0.0))
+        .collect();
+
+    // Bar phase: the beat of four carrying the most low-band onset energy
+    let accent = |t: usize| 0.25f64.mul_add(env[t], low[t]);
+    let per_bar = BEATS_PER_BAR as usize;
+    let phase = (0..per_bar)
+        .max_by(|&a, &b| {
+            let mean = |k: usize| {
+                let v: Vec<f64> = frames
+                    .iter()
+                    .skip(k)
+                    .step_by(per_bar)
+                    .map(|&t| accent(t))
+                    .collect();
+                v.iter().sum::<f64>() / v.len().max(1) as f64
+            };
+            mean(a).total_cmp(&mean(b))
+        })
+        .unwrap_or(0);
+    let beat_in_bar: Vec<u32> = (0..beats.len())
+        .map(|i| ((i + per_bar - phase) % per_bar) as u32)
+        .collect();
+    let downbeats = (0..beats.len() as u32)
+        .filter(|&i| beat_in_bar[i as usize] == 0)
+        .collect();
+
+    let mut intervals: Vec<f64> = beats.windows(2).map(|w| w[1] - w[0]).collect();
+    let bpm = 60.0 / median(&mut intervals)?;
+
+    Some(BeatGrid {
+        tempo_curve: tempo_curve(&beats, duration),
+        tempo_curve_rate: TEMPO_CURVE_RATE,
+        stability: stability(&beats),
+        beats,
+        downbeats,
+        beat_in_bar,
+        beats_per_bar: BEATS_PER_BAR,
+        bpm,
+    })
+}
+
+impl BeatGrid {
+    fn beat_period(&self) -> f64 {
+        60.0 / self.bpm.max(1.0)
+    }
+
+    /// Beats at multiples of `every` within the bar (`BEATS_PER_BAR` = downbeats)
+    fn anchors(&self, every: u32) -> Vec<f64> {
+        let every = every.max(1);
+        self.beats
+            .iter()
+            .zip(&self.beat_in_bar)
+            .filter(|&(_, &pos)| pos % every == 0)
+            .map(|(&t, _)| t)
+            .collect()
+    }
+
+    /// Anchor `index`, extrapolated beyond either end of the grid with the
+    /// spacing found there
+    #[allow(clippy::cast_possible_wrap)]
+    fn anchor_at(anchors: &[f64], index: i64, step: f64) -> f64 {
+        let n = anchors.len();
+        let first_step = if n > 1 { anchors[1] - anchors[0] } else { step };
+        let last_step = if n > 1 {
+            anchors[n - 1] - anchors[n - 2]
+        } else {
+            step
+        };
+        match usize::try_from(index) {
+            Ok(i) if i < n => anchors[i],
+            Ok(i) => ((i - n + 1) as f64).mul_add(last_step, anchors[n - 1]),
+            Err(_) => (index as f64).mul_add(first_step, anchors[0]),
+        }
+    }
+
+    /// Index of the anchor nearest `time`, negative or past the end when `time`
+    /// lies outside the grid
+    #[allow(clippy::cast_possible_wrap)]
+    fn nearest_anchor(anchors: &[f64], time: f64, step: f64) -> i64 {
+        let n = anchors.len();
+        let first_step = if n > 1 { anchors[1] - anchors[0] } else { step };
+        let last_step = if n > 1 {
+            anchors[n - 1] - anchors[n - 2]
+        } else {
+            step
+        };
+        if time <= anchors[0] {
+            ((time - anchors[0]) / first_step).round() as i64
+        } else if time >= anchors[n - 1] {
+            (n - 1) as i64 + ((time - anchors[n - 1]) / last_step).round() as i64
+        } else {
+            let i = anchors.partition_point(|&a| a < time);
+            if time - anchors[i - 1] <= anchors[i] - time {
+                (i - 1) as i64
+            } else {
+                i as i64
+            }
+        }
+    }
+
+    /// Nearest beat at a multiple of `every` beats into the bar (`every` =
+    /// `beats_per_bar` snaps to downbeats)
+    pub fn snap(&self, time: f64, every: u32) -> f64 {
+        let anchors = self.anchors(every);
+        if anchors.is_empty() {
+            return time;
+        }
+        let step = self.beat_period() * f64::from(every.max(1));
+        let index = Self::nearest_anchor(&anchors, time, step);
+        Self::anchor_at(&anchors, index, step).max(0.0)
+    }
+
+    /// The downbeat `bars` bars before the downbeat nearest `time`
+    pub fn bars_before(&self, time: f64, bars: u32) -> f64 {
+        let anchors = self.anchors(self.beats_per_bar);
+        if anchors.is_empty() {
+            return time;
+        }
+        let step = self.beat_period() * f64::from(self.beats_per_bar);
+        let index = Self::nearest_anchor(&anchors, time, step) - i64::from(bars);
+        Self::anchor_at(&anchors, index, step)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::cast_sign_loss, clippy::while_float)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44_100;
+
+    /// Clicks at `times`; every `accent_every`-th one (from `accent_from`) adds a
+    /// 60 Hz kick, the rest are short high bursts
+    fn detector_for(times: &[f64], accent_from: usize, secs: f64) -> OnsetDetector {
+        let mut detector = OnsetDetector::new(RATE);
+        let rate = f64::from(RATE);
+        let mut next = 0;
+        let mut active: Option<(usize, bool)> = None;
+        let mut noise = 0x1234_5678u32;
+        for n in 0..(secs * rate) as usize {
+            if next < times.len() && n as f64 >= times[next] * rate {
+                active = Some((
+                    n,
+                    next >= accent_from && (next - accent_from).is_multiple_of(4),
+                ));
+                next += 1;
+            }
+            let mut s = 0.0f64;
+            if let Some((start, accent)) = active {
+                let t = (n - start) as f64 / rate;
+                noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let white = f64::from(noise >> 8) / f64::from(1u32 << 24) - 0.5;
+                s += 0.5 * white * (-t / 0.01).exp();
+                if accent {
+                    s += 0.8 * (2.0 * std::f64::consts::PI * 60.0 * t).sin() * (-t / 0.08).exp();
+                }
+            }
+            detector.process(s as f32);
+        }
+        detector
+    }
+
+    fn nearest_error(found: &[f64], expected: &[f64]) -> f64 {
+        found
+            .iter()
+            .map(|&t| {
+                expected
+                    .iter()
+                    .map(|&e| (t - e).abs())
+                    .fold(f64::INFINITY, f64::min)
+            })
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn tracks_a_steady_click_track() {
+        let times: Vec<f64> = (0..60).map(|i| f64::from(i).mul_add(0.5, 0.5)).collect();
+        let grid = track(&detector_for(&times, 1, 31.0), None, 31.0).unwrap();
+
+        assert!((grid.bpm - 120.0).abs() < 1.0, "{}", grid.bpm);
+        assert!(grid.beats.len() >= 55, "{}", grid.beats.len());
+        let error = nearest_error(&grid.beats, &times);
+        assert!(error < 0.02, "{error}");
+        assert!(grid.stability > 0.9, "{}", grid.stability);
+
+        // Accents fall on clicks 1, 5, 9, …
+        for &i in &grid.downbeats {
+            let t = grid.beats[i as usize];
+            let click = ((t - 0.5) / 0.5).round() as usize;
+            assert_eq!(click % 4, 1, "downbeat at {t}");
+        }
+        assert!(grid.tempo_curve.iter().all(|&b| (b - 120.0).abs() < 2.0));
+    }
+
+    #[test]
+    fn follows_a_tempo_ramp() {
+        // 110 → 130 BPM over about 40 s
+        let mut times = Vec::new();
+        let mut t = 0.5;
+        while t < 40.0 {
+            times.push(t);
+            let bpm = 110.0 + 20.0 * t / 40.0;
+            t += 60.0 / bpm;
+        }
+        let grid = track(&detector_for(&times, 0, 40.5), None, 40.5).unwrap();
+
+        let error = nearest_error(&grid.beats, &times);
+        assert!(error < 0.03, "{error}");
+        assert!(grid.beats.len() + 3 >= times.len());
+        let early = grid.tempo_curve[8];
+        let late = grid.tempo_curve[32];
+        assert!((early - 114.0).abs() < 3.0, "{early}");
+        assert!((late - 126.0).abs() < 3.0, "{late}");
+        assert!(grid.stability < 0.9, "{}", grid.stability);
+    }
+
+    #[test]
+    fn snaps_to_the_tracked_grid() {
+        let grid = BeatGrid {
+            beats: vec![1.0, 1.5, 2.0, 2.5, 3.1, 3.7, 4.3, 4.9, 5.5],
+            downbeats: vec![0, 4, 8],
+            beat_in_bar: vec![0, 1, 2, 3, 0, 1, 2, 3, 0],
+            beats_per_bar: 4,
+            bpm: 110.0,
+            tempo_curve: Vec::new(),
+            tempo_curve_rate: TEMPO_CURVE_RATE,
+            stability: 0.5,
+        };
+        assert!((grid.snap(3.3, 4) - 3.1).abs() < 1e-9);
+        assert!((grid.snap(3.3, 1) - 3.1).abs() < 1e-9);
+        assert!((grid.snap(3.5, 1) - 3.7).abs() < 1e-9);
+        // Beyond the grid the last bar's length carries on
+        assert!((grid.snap(7.9, 4) - 7.9).abs() < 1e-9);
+        assert!((grid.snap(0.2, 4) - 1.0).abs() < 1e-9);
+        assert!((grid.snap(-1.0, 4) - 0.0).abs() < 1e-9);
+        assert!((grid.bars_before(5.4, 1) - 3.1).abs() < 1e-9);
+        assert!((grid.bars_before(5.4, 3) + 1.1).abs() < 1e-9);
+    }
+}
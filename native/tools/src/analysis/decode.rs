@@ -0,0 +1,142 @@
+//! Shared symphonia plumbing: opening the default track of a file and decoding
+//! it front to back as planar `f32`. Opus goes through `opus::OpusDecoder`
+//! alongside symphonia's own codecs.
+
+use std::fs::File;
+use std::path::Path;
+use std::sync::LazyLock;
+use symphonia::core::audio::{AudioBuffer, Signal, SignalSpec};
+use symphonia::core::codecs::{CodecParameters, CodecRegistry, Decoder, DecoderOptions};
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::Time;
+
+use super::opus::OpusDecoder;
+
+static CODECS: LazyLock<CodecRegistry> = LazyLock::new(|| {
+    let mut codecs = CodecRegistry::new();
+    symphonia::default::register_enabled_codecs(&mut codecs);
+    codecs.register_all::<OpusDecoder>();
+    codecs
+});
+
+pub struct OpenTrack {
+    pub format: Box<dyn FormatReader>,
+    pub decoder: Box<dyn Decoder>,
+    pub track_id: u32,
+    pub params: CodecParameters,
+}
+
+pub fn open_track(path: &Path) -> Option<OpenTrack> {
+    let src = File::open(path).ok()?;
+    let mss = MediaSourceStream::new(Box::new(src), MediaSourceStreamOptions::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            mss,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+    let format = probed.format;
+    let track = format.default_track()?;
+    let track_id = track.id;
+    let params = track.codec_params.clone();
+    let decoder = CODECS.make(&params, &DecoderOptions::default()).ok()?;
+
+    Some(OpenTrack {
+        format,
+        decoder,
+        track_id,
+        params,
+    })
+}
+
+impl OpenTrack {
+    /// Moves to `seconds`, or the nearest earlier point the container can seek
+    /// to. Packet timestamps tell where decoding actually resumes.
+    pub fn seek(&mut self, seconds: f64) -> Option<()> {
+        self.format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: Time::from(seconds.max(0.0)),
+                    track_id: Some(self.track_id),
+                },
+            )
+            .ok()?;
+        self.decoder.reset();
+        Some(())
+    }
+
+    /// Decodes from the current position to the end of the track, handing each
+    /// packet's timestamp and samples (planar `f32`) to `on_buffer`. Undecodable
+    /// packets are skipped; returning `false` from the callback stops early.
+    ///
+    /// Returns `false` if stopped early.
+    pub fn decode(&mut self, mut on_buffer: impl FnMut(u64, &AudioBuffer<f32>) -> bool) -> bool {
+        let mut buf: Option<AudioBuffer<f32>> = None;
+
+        while let Ok(packet) = self.format.next_packet() {
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            let Ok(decoded) = self.decoder.decode(&packet) else {
+                continue;
+            };
+
+            let reusable = buf
+                .as_ref()
+                .is_some_and(|b| b.spec() == decoded.spec() && b.capacity() >= decoded.capacity());
+            if !reusable {
+                buf = Some(decoded.make_equivalent());
+            }
+            let Some(out) = buf.as_mut() else { break };
+            decoded.convert(out);
+
+            if !on_buffer(packet.ts(), out) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Decodes the whole default track of `path`, handing each packet to `on_buffer`
+/// as planar `f32`. Undecodable packets are skipped; returning `false` from the
+/// callback stops early.
+///
+/// Returns `None` if the file cannot be opened, `Some(false)` if stopped early.
+pub fn decode_file(
+    path: &Path,
+    mut on_buffer: impl FnMut(&AudioBuffer<f32>) -> bool,
+) -> Option<bool> {
+    Some(open_track(path)?.decode(|_, buf| on_buffer(buf)))
+}
+
+/// Decodes the whole default track of `path` and hands it to `on_frame` one
+/// interleaved frame (one sample per channel) at a time.
+///
+/// Returns `None` if the file cannot be opened.
+pub fn for_each_frame(path: &Path, mut on_frame: impl FnMut(SignalSpec, &[f32])) -> Option<()> {
+    let mut frame = Vec::new();
+    decode_file(path, |buf| {
+        let spec = *buf.spec();
+        frame.resize(spec.channels.count(), 0.0);
+        for i in 0..buf.frames() {
+            for (c, s) in frame.iter_mut().enumerate() {
+                *s = buf.chan(c)[i];
+            }
+            on_frame(spec, &frame);
+        }
+        true
+    })?;
+    Some(())
+}
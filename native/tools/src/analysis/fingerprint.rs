@@ -0,0 +1,526 @@
+//! Chroma-based acoustic fingerprints, in the spirit of Chromaprint.
+//!
+//! Every `HOP_SECS` a `FRAME_SECS` window is folded into a 12-bin chroma
+//! vector. The chroma sequence is smoothed over time and turned into an image
+//! of log energies, and each 32-bit sub-fingerprint holds the signs of 32
+//! Haar-like filters laid over the next `MAX_FILTER_WIDTH` frames of it.
+//! Framing is defined in seconds, so files at different sample rates produce
+//! comparable fingerprints; chroma survives lossy coding well. Two
+//! fingerprints are compared by the bit error rate at their best alignment,
+//! which also tolerates trimmed starts and ends.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use napi_derive::napi;
+use num_complex::Complex32;
+use rustfft::{Fft, FftPlanner};
+
+use super::decode;
+
+/// Bumped whenever sub-fingerprints stop being comparable with older ones
+pub const FINGERPRINT_VERSION: u32 = 1;
+
+const FRAME_SECS: f64 = 0.372;
+const HOP_SECS: f64 = FRAME_SECS / 3.0;
+const CHROMA_MIN_HZ: f32 = 55.0;
+const CHROMA_MAX_HZ: f32 = 3520.0;
+/// Temporal smoothing of the chroma sequence
+const SMOOTHING: [f32; 5] = [0.25, 0.75, 1.0, 0.75, 0.25];
+const MAX_FILTER_WIDTH: usize = 16;
+/// Alignments with less overlap than this aren't scored, seconds
+const MIN_OVERLAP_SECS: f64 = 5.0;
+/// Above this many offset × frame comparisons, only offsets suggested by
+/// exactly matching sub-fingerprints are tried
+const FULL_SEARCH_LIMIT: usize = 16_000_000;
+/// Sub-fingerprint values this common in `a` (silence, drones) cast no votes
+const MAX_VOTES_PER_VALUE: usize = 8;
+const CANDIDATE_OFFSETS: usize = 5;
+
+#[derive(Clone, Copy)]
+enum Shape {
+    /// Lower bands against upper bands
+    Bands,
+    /// Earlier frames against later frames
+    Time,
+    /// Diagonal quadrants against each other
+    Checker,
+    /// Middle third of the bands against the outer thirds
+    BandThirds,
+    /// Middle third of the frames against the outer thirds
+    TimeThirds,
+}
+
+/// A Haar-like filter over `bands` chroma rows from `band`, `frames` wide
+struct Filter {
+    shape: Shape,
+    band: usize,
+    bands: usize,
+    frames: usize,
+}
+
+const fn filter(shape: Shape, band: usize, bands: usize, frames: usize) -> Filter {
+    Filter {
+        shape,
+        band,
+        bands,
+        frames,
+    }
+}
+
+const FILTERS: [Filter; 32] = [
+    filter(Shape::Bands, 0, 4, 4),
+    filter(Shape::Bands, 4, 4, 4),
+    filter(Shape::Bands, 8, 4, 4),
+    filter(Shape::Bands, 0, 6, 8),
+    filter(Shape::Bands, 6, 6, 8),
+    filter(Shape::Bands, 2, 8, 12),
+    filter(Shape::Bands, 0, 12, 16),
+    filter(Shape::Bands, 3, 6, 3),
+    filter(Shape::Time, 0, 12, 4),
+    filter(Shape::Time, 0, 12, 8),
+    filter(Shape::Time, 0, 6, 6),
+    filter(Shape::Time, 6, 6, 6),
+    filter(Shape::Time, 0, 4, 16),
+    filter(Shape::Time, 4, 4, 16),
+    filter(Shape::Time, 8, 4, 16),
+    filter(Shape::Time, 3, 6, 10),
+    filter(Shape::Checker, 0, 4, 8),
+    filter(Shape::Checker, 4, 4, 8),
+    filter(Shape::Checker, 8, 4, 8),
+    filter(Shape::Checker, 0, 8, 12),
+    filter(Shape::Checker, 4, 8, 12),
+    filter(Shape::Checker, 2, 6, 4),
+    filter(Shape::Checker, 6, 6, 16),
+    filter(Shape::Checker, 0, 12, 6),
+    filter(Shape::BandThirds, 0, 3, 6),
+    filter(Shape::BandThirds, 3, 3, 6),
+    filter(Shape::BandThirds, 6, 3, 6),
+    filter(Shape::BandThirds, 9, 3, 6),
+    filter(Shape::BandThirds, 0, 6, 12),
+    filter(Shape::BandThirds, 6, 6, 12),
+    filter(Shape::TimeThirds, 0, 12, 6),
+    filter(Shape::TimeThirds, 0, 12, 15),
+];
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct Fingerprint {
+    /// Seconds of audio fingerprinted
+    pub duration: f64,
+    /// Sub-fingerprints per second
+    pub rate: f64,
+    /// One 32-bit sub-fingerprint per frame
+    pub data: Vec<u32>,
+    pub version: u32,
+}
+
+#[napi(object)]
+#[derive(Clone, Debug)]
+pub struct FingerprintMatch {
+    /// 0..1; unrelated audio scores near 0, the same recording well above 0.5
+    pub score: f64,
+    /// Seconds to add to a time in `b` to reach the same audio in `a`
+    pub offset: f64,
+    /// Seconds of audio the score was measured over
+    pub overlap: f64,
+}
+
+/// Chroma frames from mono samples, then sub-fingerprints from the frames
+pub struct Fingerprinter {
+    sample_rate: f64,
+    frame_len: usize,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    spectrum: Vec<Complex32>,
+    /// (bin, pitch class) for every bin in the chroma range
+    chroma_bins: Vec<(usize, usize)>,
+    /// Samples from `buffer_start` on
+    buffer: Vec<f32>,
+    buffer_start: u64,
+    samples: u64,
+    chroma: Vec<[f32; 12]>,
+}
+
+impl Fingerprinter {
+    #[allow(clippy::cast_sign_loss)]
+    pub fn new(sample_rate: u32) -> Self {
+        let sample_rate = f64::from(sample_rate.max(1));
+        let frame_len = ((FRAME_SECS * sample_rate).round() as usize).max(2);
+        let fft_len = frame_len.next_power_of_two();
+        let bin_hz = sample_rate as f32 / fft_len as f32;
+        let window = (0..frame_len)
+            .map(|i| {
+                0.5f32.mul_add(
+                    -(2.0 * std::f32::consts::PI * i as f32 / frame_len as f32).cos(),
+                    0.5,
+                )
+            })
+            .collect();
+        let chroma_bins = (1..=fft_len / 2)
+            .filter_map(|k| {
+                let hz = k as f32 * bin_hz;
+                (CHROMA_MIN_HZ..=CHROMA_MAX_HZ).contains(&hz).then(|| {
+                    let midi = 12.0f32.mul_add((hz / 440.0).log2(), 69.0);
+                    (k, midi.round() as usize % 12)
+                })
+            })
+            .collect();
+        Self {
+            sample_rate,
+            frame_len,
+            fft: FftPlanner::new().plan_fft_forward(fft_len),
+            window,
+            spectrum: vec![Complex32::new(0.0, 0.0); fft_len],
+            chroma_bins,
+            buffer: Vec::with_capacity(frame_len * 2),
+            buffer_start: 0,
+            samples: 0,
+            chroma: Vec::new(),
+        }
+    }
+
+    /// First sample of frame `index`; frames start on the nearest sample to a
+    /// multiple of `HOP_SECS` so the frame rate doesn't depend on the sample rate
+    #[allow(clippy::cast_sign_loss)]
+    fn frame_start(&self, index: usize) -> u64 {
+        (index as f64 * HOP_SECS * self.sample_rate).round() as u64
+    }
+
+    /// Feeds one mono sample
+    pub fn process(&mut self, sample: f32) {
+        self.buffer.push(sample);
+        self.samples += 1;
+        let start = self.frame_start(self.chroma.len());
+        if start + self.frame_len as u64 > self.samples {
+            return;
+        }
+        let offset = (start - self.buffer_start) as usize;
+        self.frame(offset);
+        let next = self.frame_start(self.chroma.len());
+        let done = ((next - self.buffer_start) as usize).min(self.buffer.len());
+        self.buffer.drain(..done);
+        self.buffer_start += done as u64;
+    }
+
+    fn frame(&mut self, offset: usize) {
+        let samples = &self.buffer[offset..offset + self.frame_len];
+        for (i, bin) in self.spectrum.iter_mut().enumerate() {
+            *bin = Complex32::new(
+                samples
+                    .get(i)
+                    .zip(self.window.get(i))
+                    .map_or(0.0, |(s, w)| s * w),
+                0.0,
+            );
+        }
+        self.fft.process(&mut self.spectrum);
+        let mut chroma = [0.0f32; 12];
+        for &(k, pc) in &self.chroma_bins {
+            chroma[pc] += self.spectrum[k].norm_sqr();
+        }
+        self.chroma.push(chroma);
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.samples as f64 / self.sample_rate
+    }
+
+    /// Sub-fingerprints for everything fed so far
+    pub fn finish(&self) -> Vec<u32> {
+        let n = self.chroma.len();
+        if n < MAX_FILTER_WIDTH {
+            return Vec::new();
+        }
+
+        // Smooth, normalise and take logs, then build an integral image
+        let half = SMOOTHING.len() / 2;
+        let mut integral = vec![[0.0f64; 13]; n + 1];
+        for t in 0..n {
+            let mut frame = [0.0f32; 12];
+            for (k, &w) in SMOOTHING.iter().enumerate() {
+                let Some(src) = (t + k).checked_sub(half).and_then(|i| self.chroma.get(i)) else {
+                    continue;
+                };
+                for (out, v) in frame.iter_mut().zip(src) {
+                    *out += w * v;
+                }
+            }
+            let norm = frame.iter().map(|v| v * v).sum::<f32>().sqrt();
+            let mut row = [0.0f64; 13];
+            for (b, &v) in frame.iter().enumerate() {
+                let v = if norm > f32::EPSILON { v / norm } else { 0.0 };
+                let log = (f64::from(v) + 0.01).ln();
+                row[b + 1] = row[b] + log + integral[t][b + 1] - integral[t][b];
+            }
+            integral[t + 1] = row;
+        }
+        let mean = |t0: usize, t1: usize, b0: usize, b1: usize| {
+            let area = integral[t1][b1] - integral[t0][b1] - integral[t1][b0] + integral[t0][b0];
+            area / ((t1 - t0) * (b1 - b0)) as f64
+        };
+
+        (0..=n - MAX_FILTER_WIDTH)
+            .map(|t| {
+                FILTERS.iter().enumerate().fold(0u32, |bits, (i, f)| {
+                    let (b0, b1, t1) = (f.band, f.band + f.bands, t + f.frames);
+                    let (bm, tm) = (f.band + f.bands / 2, t + f.frames / 2);
+                    let (b3, t3) = (f.bands / 3, f.frames / 3);
+                    let response = match f.shape {
+                        Shape::Bands => mean(t, t1, b0, bm) - mean(t, t1, bm, b1),
+                        Shape::Time => mean(t, tm, b0, b1) - mean(tm, t1, b0, b1),
+                        Shape::Checker => {
+                            mean(t, tm, b0, bm) + mean(tm, t1, bm, b1)
+                                - mean(t, tm, bm, b1)
+                                - mean(tm, t1, b0, bm)
+                        }
+                        Shape::BandThirds => {
+                            let middle = mean(t, t1, b0 + b3, b1 - b3);
+                            (middle - mean(t, t1, b0, b0 + b3))
+                                + (middle - mean(t, t1, b1 - b3, b1))
+                        }
+                        Shape::TimeThirds => {
+                            let middle = mean(t + t3, t1 - t3, b0, b1);
+                            (middle - mean(t, t + t3, b0, b1))
+                                + (middle - mean(t1 - t3, t1, b0, b1))
+                        }
+                    };
+                    bits | (u32::from(response > 0.0) << i)
+                })
+            })
+            .collect()
+    }
+}
+
+pub fn fingerprint_path(path: &Path) -> Option<Fingerprint> {
+    let mut fingerprinter: Option<Fingerprinter> = None;
+    decode::for_each_frame(path, |spec, frame| {
+        let fingerprinter = fingerprinter.get_or_insert_with(|| Fingerprinter::new(spec.rate));
+        fingerprinter.process(frame.iter().sum::<f32>() / frame.len().max(1) as f32);
+    })?;
+    let fingerprinter = fingerprinter?;
+    Some(Fingerprint {
+        duration: fingerprinter.duration(),
+        rate: 1.0 / HOP_SECS,
+        data: fingerprinter.finish(),
+        version: FINGERPRINT_VERSION,
+    })
+}
+
+/// Differing bits and overlap when `a[i]` lines up with `b[i - offset]`
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+fn errors_at(a: &[u32], b: &[u32], offset: i64) -> (u64, usize) {
+    let (a, b) = if offset >= 0 {
+        (a.get(offset as usize..).unwrap_or_default(), b)
+    } else {
+        (
+            a,
+            b.get(offset.unsigned_abs() as usize..).unwrap_or_default(),
+        )
+    };
+    let errors = a
+        .iter()
+        .zip(b)
+        .map(|(x, y)| u64::from((x ^ y).count_ones()))
+        .sum();
+    (errors, a.len().min(b.len()))
+}
+
+/// Offsets where exactly equal sub-fingerprints agree most often
+#[allow(clippy::cast_possible_wrap)]
+fn voted_offsets(a: &[u32], b: &[u32]) -> Vec<i64> {
+    let mut positions: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (i, &v) in a.iter().enumerate() {
+        positions.entry(v).or_default().push(i);
+    }
+    let mut votes: HashMap<i64, usize> = HashMap::new();
+    for (j, v) in b.iter().enumerate() {
+        let Some(found) = positions.get(v) else {
+            continue;
+        };
+        if found.len() > MAX_VOTES_PER_VALUE {
+            continue;
+        }
+        for &i in found {
+            *votes.entry(i as i64 - j as i64).or_default() += 1;
+        }
+    }
+    let mut ranked: Vec<(i64, usize)> = votes.into_iter().collect();
+    ranked.sort_by(|x, y| y.1.cmp(&x.1).then(x.0.cmp(&y.0)));
+    ranked
+        .into_iter()
+        .take(CANDIDATE_OFFSETS)
+        .flat_map(|(offset, _)| offset - 1..=offset + 1)
+        .collect()
+}
+
+/// Best alignment of two sub-fingerprint sequences at `rate` per second
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+pub fn compare(a: &[u32], b: &[u32], rate: f64) -> FingerprintMatch {
+    let min_overlap = ((MIN_OVERLAP_SECS * rate).ceil() as usize).max(1);
+    let none = FingerprintMatch {
+        score: 0.0,
+        offset: 0.0,
+        overlap: 0.0,
+    };
+    if a.len() < min_overlap || b.len() < min_overlap {
+        return none;
+    }
+
+    let lo = min_overlap as i64 - b.len() as i64;
+    let hi = (a.len() - min_overlap) as i64;
+    let offsets: Vec<i64> = if (hi - lo + 1) as usize * a.len().min(b.len()) <= FULL_SEARCH_LIMIT {
+        (lo..=hi).collect()
+    } else {
+        voted_offsets(a, b)
+    };
+
+    offsets
+        .into_iter()
+        .filter(|offset| (lo..=hi).contains(offset))
+        .map(|offset| (offset, errors_at(a, b, offset)))
+        .filter(|&(_, (_, overlap))| overlap >= min_overlap)
+        .map(|(offset, (errors, overlap))| {
+            let error_rate = errors as f64 / (32 * overlap) as f64;
+            FingerprintMatch {
+                score: 2.0f64.mul_add(-error_rate, 1.0).max(0.0),
+                offset: offset as f64 / rate,
+                overlap: overlap as f64 / rate,
+            }
+        })
+        .max_by(|x, y| x.score.total_cmp(&y.score))
+        .unwrap_or(none)
+}
+
+/// Fingerprints a file from its decoded audio
+#[napi]
+#[allow(clippy::missing_errors_doc, clippy::trailing_empty_array)]
+pub async fn fingerprint_file(path: String) -> napi::Result<Option<Fingerprint>> {
+    napi::tokio::task::spawn_blocking(move || fingerprint_path(Path::new(&path)))
+        .await
+        .map_err(|e| napi::Error::from_reason(format!("join error: {e}")))
+}
+
+/// Compares two fingerprints from `fingerprint_file`, finding the offset at
+/// which they agree best
+#[napi]
+#[allow(clippy::missing_errors_doc, clippy::needless_pass_by_value)]
+pub fn compare_fingerprints(a: Fingerprint, b: Fingerprint) -> napi::Result<FingerprintMatch> {
+    if a.version != b.version || (a.rate - b.rate).abs() > 1e-9 {
+        return Err(napi::Error::from_reason(format!(
+            "fingerprints are not comparable: version {} at {} Hz vs version {} at {} Hz",
+            a.version, a.rate, b.version, b.rate
+        )));
+    }
+    Ok(compare(&a.data, &b.data, a.rate))
+}
+
+#[cfg(test)]
+#[allow(clippy::cast_sign_loss, clippy::while_float)]
+mod tests {
+    use super::*;
+
+    /// A random chord every 0.4–1.0 s with a little attack, `seed` picking the
+    /// chords; sampled from `start` seconds in at `rate`, with optional noise
+    fn song(seed: u32, rate: u32, start: f64, secs: f64, noise: f64) -> Vec<f32> {
+        let mut state = seed;
+        let mut next = move || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            f64::from(state >> 8) / f64::from(1u32 << 24)
+        };
+        let mut chords = Vec::new();
+        let mut t = 0.0;
+        while t < start + secs {
+            let notes: Vec<f64> = (0..3).map(|_| (next() * 36.0).floor() + 48.0).collect();
+            let len = 0.4 + next() * 0.6;
+            chords.push((t, notes));
+            t += len;
+        }
+
+        let rate_f = f64::from(rate);
+        let mut chord = 0;
+        (0..(secs * rate_f) as usize)
+            .map(|n| {
+                let t = start + n as f64 / rate_f;
+                while chord + 1 < chords.len() && chords[chord + 1].0 <= t {
+                    chord += 1;
+                }
+                let (onset, notes) = &chords[chord];
+                let envelope = 0.3f64.mul_add((-(t - onset) / 0.8).exp(), 0.1);
+                let tone: f64 = notes
+                    .iter()
+                    .map(|&m| {
+                        let hz = 440.0 * ((m - 69.0) / 12.0).exp2();
+                        (2.0 * std::f64::consts::PI * hz * t).sin()
+                    })
+                    .sum();
+                noise.mul_add(next() - 0.5, envelope * tone / 3.0) as f32
+            })
+            .collect()
+    }
+
+    fn fingerprint(samples: &[f32], rate: u32) -> Vec<u32> {
+        let mut fingerprinter = Fingerprinter::new(rate);
+        for &s in samples {
+            fingerprinter.process(s);
+        }
+        fingerprinter.finish()
+    }
+
+    #[test]
+    fn matches_a_trimmed_resampled_copy() {
+        let original = fingerprint(&song(7, 44_100, 0.0, 40.0, 0.0), 44_100);
+        // Starts 3.3 s later, at another sample rate, with added noise
+        let copy = fingerprint(&song(7, 22_050, 3.3, 30.0, 0.05), 22_050);
+
+        let found = compare(&original, &copy, 1.0 / HOP_SECS);
+        assert!(found.score > 0.6, "{found:?}");
+        assert!((found.offset - 3.3).abs() < HOP_SECS, "{found:?}");
+        assert!(found.overlap > 25.0, "{found:?}");
+
+        let swapped = compare(&copy, &original, 1.0 / HOP_SECS);
+        assert!((swapped.offset + 3.3).abs() < HOP_SECS, "{swapped:?}");
+    }
+
+    #[test]
+    fn rejects_a_different_song() {
+        let a = fingerprint(&song(7, 44_100, 0.0, 40.0, 0.0), 44_100);
+        let b = fingerprint(&song(8, 44_100, 0.0, 40.0, 0.0), 44_100);
+
+        let found = compare(&a, &b, 1.0 / HOP_SECS);
+        assert!(found.score < 0.25, "{found:?}");
+    }
+
+    #[test]
+    fn long_inputs_align_through_voting() {
+        // About 12 minutes of sub-fingerprints, too many offsets for the full search
+        let mut state = 0x9e37_79b9u32;
+        let mut next = move || {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            state
+        };
+        let a: Vec<u32> = (0..6000).map(|_| next()).collect();
+        // A 4000-frame excerpt from frame 1500; every third frame has a flipped bit
+        let b: Vec<u32> = a[1500..5500]
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| if i % 3 == 0 { v ^ (1 << (i % 32)) } else { v })
+            .collect();
+        let searched = (a.len() + b.len()) * a.len().min(b.len());
+        assert!(searched > FULL_SEARCH_LIMIT);
+
+        assert!(voted_offsets(&a, &b).contains(&1500));
+        let rate = 1.0 / HOP_SECS;
+        let found = compare(&a, &b, rate);
+        assert!((found.offset - 1500.0 / rate).abs() < 1e-9, "{found:?}");
+        assert!(found.score > 0.95, "{found:?}");
+
+        let swapped = compare(&b, &a, rate);
+        assert!((swapped.offset + 1500.0 / rate).abs() < 1e-9, "{swapped:?}");
+
+        let other: Vec<u32> = (0..4000).map(|_| next()).collect();
+        assert!(voted_offsets(&a, &other).is_empty());
+        assert!(compare(&a, &other, rate).score < 0.25);
+    }
+}
@@ -0,0 +1,443 @@
+//! ITU-R BS.1770-4 / EBU R128 loudness: K-weighting, gated integrated loudness,
+//! loudness range (EBU Tech 3342) and momentary / short-term curves.
+//!
+//! Weighted channel power is collected in 100 ms sub-blocks; 400 ms gating blocks
+//! (75 % overlap) and 3 s short-term blocks are sliding sums over them.
+
+use napi_derive::napi;
+use std::path::Path;
+use symphonia::core::audio::Channels;
+
+use super::decode;
+
+/// Reported for silence and streams too short to measure
+pub const SILENCE_LUFS: f64 = -70.0;
+/// Rate of the momentary and short-term curves (one value per 100 ms)
+pub const CURVE_RATE: f64 = 10.0;
+
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+const LRA_RELATIVE_GATE_LU: f64 = -20.0;
+const MOMENTARY_SUB_BLOCKS: usize = 4;
+const SHORT_TERM_SUB_BLOCKS: usize = 30;
+
+#[napi(object)]
+pub struct LoudnessInfo {
+    /// Gated integrated loudness, LUFS
+    pub integrated: f64,
+    /// Loudness range, LU
+    #[napi(js_name = "loudness_range")]
+    pub loudness_range: f64,
+    #[napi(js_name = "momentary_max")]
+    pub momentary_max: f64,
+    #[napi(js_name = "short_term_max")]
+    pub short_term_max: f64,
+    /// Values per second of the curves below
+    #[napi(js_name = "curve_rate")]
+    pub curve_rate: f64,
+    /// 400 ms loudness every 100 ms, LUFS
+    pub momentary: Option<Vec<f64>>,
+    /// 3 s loudness every 100 ms, LUFS
+    #[napi(js_name = "short_term")]
+    pub short_term: Option<Vec<f64>>,
+}
+
+pub struct Biquad {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    z1: f64,
+    z2: f64,
+}
+
+impl Biquad {
+    /// Normalised coefficients `[b0, b1, b2, a1, a2]`
+    pub const fn new([b0, b1, b2, a1, a2]: [f64; 5]) -> Self {
+        Self {
+            b0,
+            b1,
+            b2,
+            a1,
+            a2,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f64) -> f64 {
+        let output = input.mul_add(self.b0, self.z1);
+        self.z1 = self.a1.mul_add(-output, input.mul_add(self.b1, self.z2));
+        self.z2 = input.mul_add(self.b2, -self.a2 * output);
+        output
+    }
+}
+
+/// K-weighting (high shelf, then RLB high pass) for `rate`, as `[b0, b1, b2, a1, a2]`.
+///
+/// BS.1770 only tabulates 48 kHz; other rates come from the analog prototypes
+/// through the bilinear transform, which reproduces the 48 kHz table.
+fn k_weighting(rate: u32) -> ([f64; 5], [f64; 5]) {
+    let rate = f64::from(rate);
+
+    let f0 = 1_681.974_450_955_533;
+    let gain_db = 3.999_843_853_973_347;
+    let q = 0.707_175_236_955_419_6;
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.499_666_774_154_541_6);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = [
+        (vh + vb * k / q + k * k) / a0,
+        2.0 * (k * k - vh) / a0,
+        (vh - vb * k / q + k * k) / a0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / q + k * k) / a0,
+    ];
+
+    let f0 = 38.135_470_876_024_44;
+    let q = 0.500_327_037_323_877_3;
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = [
+        1.0,
+        -2.0,
+        1.0,
+        2.0 * (k * k - 1.0) / a0,
+        (1.0 - k / q + k * k) / a0,
+    ];
+
+    (shelf, high_pass)
+}
+
+/// BS.1770 channel weights: surrounds count 1.41, LFE not at all.
+pub fn channel_weights(layout: Channels) -> Vec<f64> {
+    layout
+        .iter()
+        .map(|ch| {
+            if ch.intersects(Channels::LFE1 | Channels::LFE2) {
+                0.0
+            } else if ch.intersects(
+                Channels::SIDE_LEFT
+                    | Channels::SIDE_RIGHT
+                    | Channels::REAR_LEFT
+                    | Channels::REAR_RIGHT
+                    | Channels::REAR_LEFT_CENTRE
+                    | Channels::REAR_RIGHT_CENTRE,
+            ) {
+                1.41
+            } else {
+                1.0
+            }
+        })
+        .collect()
+}
+
+fn to_lufs(power: f64) -> f64 {
+    if power <= 0.0 {
+        SILENCE_LUFS
+    } else {
+        10.0f64.mul_add(power.log10(), -0.691).max(SILENCE_LUFS)
+    }
+}
+
+fn mean(values: impl Iterator<Item = f64>) -> Option<f64> {
+    let (sum, n) = values.fold((0.0, 0usize), |(s, n), v| (s + v, n + 1));
+    (n > 0).then(|| sum / n as f64)
+}
+
+/// Nearest-rank percentile of an ascending slice
+#[allow(clippy::cast_sign_loss)]
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let idx = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[idx]
+}
+
+/// Absolute then relative gating over 400 ms block powers
+fn gated_loudness(mut blocks: Vec<f64>) -> f64 {
+    blocks.retain(|&p| to_lufs(p) > ABSOLUTE_GATE_LUFS);
+    let Some(ungated) = mean(blocks.iter().copied()) else {
+        return SILENCE_LUFS;
+    };
+    let threshold = to_lufs(ungated) + RELATIVE_GATE_LU;
+    mean(blocks.into_iter().filter(|&p| to_lufs(p) > threshold)).map_or(SILENCE_LUFS, to_lufs)
+}
+
+pub struct LoudnessMeter {
+    filters: Vec<(Biquad, Biquad)>,
+    weights: Vec<f64>,
+    sub_block_frames: usize,
+    frames: usize,
+    acc: f64,
+    /// Mean weighted channel power of each complete 100 ms sub-block
+    sub_blocks: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, weights: Vec<f64>) -> Self {
+        let (shelf, high_pass) = k_weighting(sample_rate);
+        let filters = weights
+            .iter()
+            .map(|_| (Biquad::new(shelf), Biquad::new(high_pass)))
+            .collect();
+        Self {
+            filters,
+            weights,
+            sub_block_frames: (sample_rate as usize / 10).max(1),
+            frames: 0,
+            acc: 0.0,
+            sub_blocks: Vec::new(),
+        }
+    }
+
+    /// Meter for `count` channels; the layout supplies weights when known.
+    pub fn for_layout(sample_rate: u32, layout: Option<Channels>, count: usize) -> Self {
+        let weights = layout
+            .map(channel_weights)
+            .filter(|w| w.len() == count)
+            .unwrap_or_else(|| vec![1.0; count]);
+        Self::new(sample_rate, weights)
+    }
+
+    pub const fn channels(&self) -> usize {
+        self.weights.len()
+    }
+
+    /// Feeds one frame (one sample per channel). Extra channels are ignored.
+    pub fn process(&mut self, frame: &[f32]) {
+        for ((&sample, (shelf, high_pass)), &weight) in
+            frame.iter().zip(self.filters.iter_mut()).zip(&self.weights)
+        {
+            let y = high_pass.process(shelf.process(f64::from(sample)));
+            self.acc += weight * y * y;
+        }
+        self.frames += 1;
+        if self.frames == self.sub_block_frames {
+            self.sub_blocks.push(self.acc / self.frames as f64);
+            self.frames = 0;
+            self.acc = 0.0;
+        }
+    }
+
+    /// Mean power of each window of `len` sub-blocks, advancing one sub-block at a time
+    fn block_powers(&self, len: usize) -> Vec<f64> {
+        if self.sub_blocks.len() < len {
+            return Vec::new();
+        }
+        let mut sum: f64 = self.sub_blocks[..len].iter().sum();
+        let mut powers = Vec::with_capacity(self.sub_blocks.len() - len + 1);
+        powers.push(sum / len as f64);
+        for i in len..self.sub_blocks.len() {
+            sum += self.sub_blocks[i] - self.sub_blocks[i - len];
+            powers.push(sum.max(0.0) / len as f64);
+        }
+        powers
+    }
+
+    /// Gated integrated loudness, LUFS
+    pub fn integrated(&self) -> f64 {
+        gated_loudness(self.block_powers(MOMENTARY_SUB_BLOCKS))
+    }
+
+    /// Gated integrated loudness of several streams played back to back, LUFS.
+    /// Gating blocks don't straddle the joins, which is within a block of exact.
+    pub fn integrated_over(meters: &[&Self]) -> f64 {
+        gated_loudness(
+            meters
+                .iter()
+                .flat_map(|m| m.block_powers(MOMENTARY_SUB_BLOCKS))
+                .collect(),
+        )
+    }
+
+    /// Loudness range, LU: spread between the 10th and 95th percentile of the
+    /// gated short-term loudness distribution
+    pub fn loudness_range(&self) -> f64 {
+        let blocks: Vec<f64> = self
+            .block_powers(SHORT_TERM_SUB_BLOCKS)
+            .into_iter()
+            .filter(|&p| to_lufs(p) > ABSOLUTE_GATE_LUFS)
+            .collect();
+        let Some(ungated) = mean(blocks.iter().copied()) else {
+            return 0.0;
+        };
+        let threshold = to_lufs(ungated) + LRA_RELATIVE_GATE_LU;
+        let mut levels: Vec<f64> = blocks
+            .into_iter()
+            .map(to_lufs)
+            .filter(|&l| l > threshold)
+            .collect();
+        if levels.is_empty() {
+            return 0.0;
+        }
+        levels.sort_by(f64::total_cmp);
+        percentile(&levels, 0.95) - percentile(&levels, 0.10)
+    }
+
+    /// 400 ms loudness every 100 ms, LUFS
+    pub fn momentary(&self) -> Vec<f64> {
+        self.block_powers(MOMENTARY_SUB_BLOCKS)
+            .into_iter()
+            .map(to_lufs)
+            .collect()
+    }
+
+    /// 3 s loudness every 100 ms, LUFS
+    pub fn short_term(&self) -> Vec<f64> {
+        self.block_powers(SHORT_TERM_SUB_BLOCKS)
+            .into_iter()
+            .map(to_lufs)
+            .collect()
+    }
+
+    pub fn info(&self, with_curves: bool) -> LoudnessInfo {
+        let momentary = self.momentary();
+        let short_term = self.short_term();
+        let max = |curve: &[f64]| curve.iter().copied().fold(SILENCE_LUFS, f64::max);
+        LoudnessInfo {
+            integrated: self.integrated(),
+            loudness_range: self.loudness_range(),
+            momentary_max: max(&momentary),
+            short_term_max: max(&short_term),
+            curve_rate: CURVE_RATE,
+            momentary: with_curves.then_some(momentary),
+            short_term: with_curves.then_some(short_term),
+        }
+    }
+}
+
+/// Decodes the whole file and measures its loudness.
+pub fn measure_file(path: &Path, with_curves: bool) -> Option<LoudnessInfo> {
+    let mut meter: Option<LoudnessMeter> = None;
+    decode::for_each_frame(path, |spec, frame| {
+        meter
+            .get_or_insert_with(|| {
+                LoudnessMeter::for_layout(spec.rate, Some(spec.channels), frame.len())
+            })
+            .process(frame);
+    })?;
+    meter.map(|m| m.info(with_curves))
+}
+
+/// Measures gated loudness (BS.1770-4) and loudness range over the whole file.
+/// `with_curves` also returns the momentary and short-term curves.
+#[napi]
+#[allow(clippy::needless_pass_by_value)]
+pub fn measure_loudness(path: String, with_curves: Option<bool>) -> Option<LoudnessInfo> {
+    measure_file(Path::new(&path), with_curves.unwrap_or(false))
+}
+
+#[cfg(test)]
+#[allow(clippy::cast_sign_loss)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48_000;
+
+    /// Stereo 997 Hz sine segments of (dBFS per channel, seconds), as used by
+    /// EBU Tech 3341 / 3342
+    fn meter_for(segments: &[(f64, f64)]) -> LoudnessMeter {
+        let mut meter = LoudnessMeter::new(RATE, vec![1.0, 1.0]);
+        let mut n = 0u64;
+        for &(db, secs) in segments {
+            let amp = 10f64.powf(db / 20.0);
+            for _ in 0..(secs * f64::from(RATE)) as u64 {
+                let s = (amp
+                    * (2.0 * std::f64::consts::PI * 997.0 * n as f64 / f64::from(RATE)).sin())
+                    as f32;
+                meter.process(&[s, s]);
+                n += 1;
+            }
+        }
+        meter
+    }
+
+    #[test]
+    fn k_weighting_matches_the_48k_table() {
+        let (shelf, high_pass) = k_weighting(48_000);
+        let table_shelf = [
+            1.535_124_859_586_97,
+            -2.691_696_189_406_38,
+            1.198_392_810_852_85,
+            -1.690_659_293_182_41,
+            0.732_480_774_215_85,
+        ];
+        let table_high_pass = [1.0, -2.0, 1.0, -1.990_047_454_833_98, 0.990_072_250_366_21];
+        for (a, b) in shelf
+            .iter()
+            .zip(&table_shelf)
+            .chain(high_pass.iter().zip(&table_high_pass))
+        {
+            assert!((a - b).abs() < 1e-6, "{a} vs {b}");
+        }
+    }
+
+    #[test]
+    fn integrated_loudness_matches_tech_3341() {
+        // Case 1: -23 dBFS for 20 s
+        let l = meter_for(&[(-23.0, 20.0)]).integrated();
+        assert!((l + 23.0).abs() <= 0.1, "{l}");
+        // Case 2: -33 dBFS
+        let l = meter_for(&[(-33.0, 20.0)]).integrated();
+        assert!((l + 33.0).abs() <= 0.1, "{l}");
+        // Case 3: quiet sections are removed by the relative gate
+        let l = meter_for(&[(-36.0, 10.0), (-23.0, 60.0), (-36.0, 10.0)]).integrated();
+        assert!((l + 23.0).abs() <= 0.1, "{l}");
+        // Case 4: near-silence is removed by the absolute gate
+        let l = meter_for(&[
+            (-72.0, 10.0),
+            (-36.0, 10.0),
+            (-23.0, 60.0),
+            (-36.0, 10.0),
+            (-72.0, 10.0),
+        ])
+        .integrated();
+        assert!((l + 23.0).abs() <= 0.1, "{l}");
+    }
+
+    #[test]
+    fn album_loudness_gates_across_tracks() {
+        // A quiet track next to a loud one: like Tech 3341 case 3, the quiet
+        // part falls below the relative gate of the combined programme
+        let loud = meter_for(&[(-23.0, 20.0)]);
+        let quiet = meter_for(&[(-36.0, 10.0)]);
+        let l = LoudnessMeter::integrated_over(&[&quiet, &loud]);
+        assert!((l + 23.0).abs() <= 0.1, "{l}");
+        let l = LoudnessMeter::integrated_over(&[&loud, &meter_for(&[(-26.0, 20.0)])]);
+        assert!((l + 24.3).abs() <= 0.2, "{l}");
+        assert!((LoudnessMeter::integrated_over(&[]) - SILENCE_LUFS).abs() < 1e-9);
+    }
+
+    #[test]
+    fn loudness_range_matches_tech_3342() {
+        for (segments, expected) in [
+            ([(-20.0, 20.0), (-30.0, 20.0)], 10.0),
+            ([(-20.0, 20.0), (-15.0, 20.0)], 5.0),
+            ([(-40.0, 20.0), (-20.0, 20.0)], 20.0),
+        ] {
+            let lra = meter_for(&segments).loudness_range();
+            assert!((lra - expected).abs() <= 1.0, "{lra} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn surround_channels_are_weighted_and_lfe_ignored() {
+        let layout = Channels::FRONT_LEFT
+            | Channels::FRONT_RIGHT
+            | Channels::FRONT_CENTRE
+            | Channels::LFE1
+            | Channels::SIDE_LEFT
+            | Channels::SIDE_RIGHT;
+        assert_eq!(channel_weights(layout), [1.0, 1.0, 1.0, 0.0, 1.41, 1.41]);
+    }
+
+    #[test]
+    fn curves_have_one_value_per_100ms() {
+        let meter = meter_for(&[(-23.0, 5.0)]);
+        let info = meter.info(true);
+        assert_eq!(info.momentary.unwrap().len(), 50 - 3);
+        assert_eq!(info.short_term.unwrap().len(), 50 - 29);
+        assert!((info.short_term_max + 23.0).abs() < 0.2);
+    }
+}
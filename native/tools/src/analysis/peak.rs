@@ -0,0 +1,244 @@
+//! Sample peak and true peak (ITU-R BS.1770-4 Annex 2: 4× oversampling through
+//! the annex's 48-tap polyphase interpolator), per channel.
+
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+
+/// Reported for digital silence instead of -inf, which JSON can't carry
+pub const MIN_DBFS: f64 = -144.0;
+
+const TAPS: usize = 12;
+
+/// BS.1770-4 Annex 2 interpolation filter, one row per output phase
+#[rustfmt::skip]
+const INTERPOLATOR: [[f32; TAPS]; 4] = [
+    [0.001_708_984_4, 0.010_986_328, -0.019_653_32, 0.033_203_125, -0.059_448_242, 0.137_329_1,
+     0.972_167_97, -0.102_294_92, 0.047_607_42, -0.026_611_328, 0.014_892_578, -0.008_300_781],
+    [-0.029_174_805, 0.029_296_875, -0.051_757_812, 0.089_111_33, -0.166_503_9, 0.465_087_9,
+     0.779_785_16, -0.200_317_38, 0.101_562_5, -0.058_227_54, 0.033_081_055, -0.018_920_898],
+    [-0.018_920_898, 0.033_081_055, -0.058_227_54, 0.101_562_5, -0.200_317_38, 0.779_785_16,
+     0.465_087_9, -0.166_503_9, 0.089_111_33, -0.051_757_812, 0.029_296_875, -0.029_174_805],
+    [-0.008_300_781, 0.014_892_578, -0.026_611_328, 0.047_607_42, -0.102_294_92, 0.972_167_97,
+     0.137_329_1, -0.059_448_242, 0.033_203_125, -0.019_653_32, 0.010_986_328, 0.001_708_984_4],
+];
+
+/// Frames between an input sample and the centre of the interpolator
+const INTERPOLATOR_DELAY: f64 = (TAPS / 2) as f64;
+
+#[napi(object)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChannelPeak {
+    /// Largest absolute sample, linear (1.0 = full scale)
+    #[napi(js_name = "sample_peak")]
+    pub sample_peak: f64,
+    #[napi(js_name = "sample_peak_db")]
+    pub sample_peak_db: f64,
+    /// Time of the sample peak, seconds
+    #[napi(js_name = "sample_peak_pos")]
+    pub sample_peak_pos: f64,
+    /// Largest absolute value of the 4× oversampled signal, linear
+    #[napi(js_name = "true_peak")]
+    pub true_peak: f64,
+    /// dBTP
+    #[napi(js_name = "true_peak_db")]
+    pub true_peak_db: f64,
+    #[napi(js_name = "true_peak_pos")]
+    pub true_peak_pos: f64,
+}
+
+/// Per-channel peaks and the maximum over all channels
+#[napi(object)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PeakInfo {
+    #[napi(js_name = "sample_peak")]
+    pub sample_peak: f64,
+    #[napi(js_name = "sample_peak_db")]
+    pub sample_peak_db: f64,
+    #[napi(js_name = "sample_peak_pos")]
+    pub sample_peak_pos: f64,
+    #[napi(js_name = "true_peak")]
+    pub true_peak: f64,
+    #[napi(js_name = "true_peak_db")]
+    pub true_peak_db: f64,
+    #[napi(js_name = "true_peak_pos")]
+    pub true_peak_pos: f64,
+    pub channels: Vec<ChannelPeak>,
+}
+
+pub fn to_dbfs(linear: f64) -> f64 {
+    if linear > 0.0 {
+        (20.0 * linear.log10()).max(MIN_DBFS)
+    } else {
+        MIN_DBFS
+    }
+}
+
+#[derive(Default)]
+struct ChannelState {
+    /// Last `TAPS` samples, oldest first once full
+    history: [f32; TAPS],
+    next: usize,
+    sample_peak: f32,
+    sample_peak_frame: u64,
+    true_peak: f32,
+    /// Position of the true peak in input frames (quarter-frame resolution)
+    true_peak_frame: f64,
+}
+
+impl ChannelState {
+    fn process(&mut self, sample: f32, frame: u64) {
+        if sample.abs() > self.sample_peak {
+            self.sample_peak = sample.abs();
+            self.sample_peak_frame = frame;
+        }
+
+        self.history[self.next] = sample;
+        self.next = (self.next + 1) % TAPS;
+
+        for (phase, coeffs) in INTERPOLATOR.iter().enumerate() {
+            // Newest sample meets the first coefficient
+            let mut acc = 0.0f32;
+            for (k, &c) in coeffs.iter().enumerate() {
+                acc += c * self.history[(self.next + TAPS - 1 - k) % TAPS];
+            }
+            if acc.abs() > self.true_peak {
+                self.true_peak = acc.abs();
+                self.true_peak_frame = frame as f64 - INTERPOLATOR_DELAY + phase as f64 / 4.0;
+            }
+        }
+    }
+
+    fn finish(&self, sample_rate: u32) -> ChannelPeak {
+        let rate = f64::from(sample_rate.max(1));
+        let sample_peak = f64::from(self.sample_peak);
+        let sample_peak_pos = self.sample_peak_frame as f64 / rate;
+        // The interpolated signal passes through the samples, so it can't peak lower
+        let (true_peak, true_peak_pos) = if self.true_peak > self.sample_peak {
+            (
+                f64::from(self.true_peak),
+                self.true_peak_frame.max(0.0) / rate,
+            )
+        } else {
+            (sample_peak, sample_peak_pos)
+        };
+        ChannelPeak {
+            sample_peak,
+            sample_peak_db: to_dbfs(sample_peak),
+            sample_peak_pos,
+            true_peak,
+            true_peak_db: to_dbfs(true_peak),
+            true_peak_pos,
+        }
+    }
+}
+
+pub struct PeakMeter {
+    sample_rate: u32,
+    channels: Vec<ChannelState>,
+    frames: u64,
+}
+
+impl PeakMeter {
+    pub fn new(sample_rate: u32, channels: usize) -> Self {
+        Self {
+            sample_rate,
+            channels: (0..channels).map(|_| ChannelState::default()).collect(),
+            frames: 0,
+        }
+    }
+
+    /// Feeds one frame (one sample per channel). Extra channels are ignored.
+    pub fn process(&mut self, frame: &[f32]) {
+        for (state, &sample) in self.channels.iter_mut().zip(frame) {
+            state.process(sample, self.frames);
+        }
+        self.frames += 1;
+    }
+
+    pub fn info(&self) -> PeakInfo {
+        let channels: Vec<ChannelPeak> = self
+            .channels
+            .iter()
+            .map(|c| c.finish(self.sample_rate))
+            .collect();
+        let loudest = |key: fn(&ChannelPeak) -> f64| {
+            channels
+                .iter()
+                .max_by(|a, b| key(a).total_cmp(&key(b)))
+                .cloned()
+        };
+        let sample = loudest(|c| c.sample_peak);
+        let truep = loudest(|c| c.true_peak);
+        PeakInfo {
+            sample_peak: sample.as_ref().map_or(0.0, |c| c.sample_peak),
+            sample_peak_db: sample.as_ref().map_or(MIN_DBFS, |c| c.sample_peak_db),
+            sample_peak_pos: sample.as_ref().map_or(0.0, |c| c.sample_peak_pos),
+            true_peak: truep.as_ref().map_or(0.0, |c| c.true_peak),
+            true_peak_db: truep.as_ref().map_or(MIN_DBFS, |c| c.true_peak_db),
+            true_peak_pos: truep.as_ref().map_or(0.0, |c| c.true_peak_pos),
+            channels,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::cast_sign_loss)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 48_000;
+
+    fn meter_for(freq: f64, phase: f64, amp: f64, secs: f64) -> PeakMeter {
+        let mut meter = PeakMeter::new(RATE, 1);
+        for n in 0..(secs * f64::from(RATE)) as u64 {
+            let t = n as f64 / f64::from(RATE);
+            let s = amp * (2.0 * std::f64::consts::PI * freq).mul_add(t, phase).sin();
+            meter.process(&[s as f32]);
+        }
+        meter
+    }
+
+    #[test]
+    fn interpolator_phases_are_within_a_quarter_db_of_unity() {
+        for coeffs in INTERPOLATOR {
+            let dc: f32 = coeffs.iter().sum();
+            assert!((dc - 1.0).abs() < 0.03, "{dc}");
+        }
+    }
+
+    #[test]
+    fn finds_inter_sample_peaks() {
+        // fs/4 sine shifted by 45°: every sample sits at ±0.707 of the real peak
+        let meter = meter_for(12_000.0, std::f64::consts::FRAC_PI_4, 1.0, 0.1);
+        let info = meter.info();
+        assert!(
+            (info.sample_peak - 0.707).abs() < 0.01,
+            "{}",
+            info.sample_peak
+        );
+        assert!(info.sample_peak_db < -2.9);
+        // EBU Tech 3341 case 15 expects +0.0 dBTP (−0.4/+0.2)
+        assert!(
+            (-0.4..=0.2).contains(&info.true_peak_db),
+            "{}",
+            info.true_peak_db
+        );
+    }
+
+    #[test]
+    fn reports_positions_per_channel() {
+        let mut meter = PeakMeter::new(RATE, 2);
+        for n in 0..RATE {
+            let right = if n == 36_000 { -0.5 } else { 0.0 };
+            meter.process(&[0.25, right]);
+        }
+        let info = meter.info();
+        assert_eq!(info.channels.len(), 2);
+        assert!((info.channels[0].sample_peak - 0.25).abs() < 1e-6);
+        assert!((info.sample_peak - 0.5).abs() < 1e-6);
+        assert!((info.sample_peak_pos - 0.75).abs() < 1e-9);
+        assert!((info.true_peak_pos - 0.75).abs() < 0.001);
+        assert!((to_dbfs(0.5) + 6.02).abs() < 0.01);
+        assert!((to_dbfs(0.0) - MIN_DBFS).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,791 @@
+//! Song structure: section boundaries, repeated-section labels and a preview
+//! highlight.
+//!
+//! Chroma (harmony) and MFCC (timbre) frames are averaged into short blocks
+//! while decoding. Boundaries are peaks of a checkerboard-kernel novelty curve
+//! over the blocks' self-similarity (Foote, "Automatic Audio Segmentation Using
+//! a Measure of Audio Novelty", 2000). Sections are grouped by how well they
+//! repeat along a diagonal of the similarity matrix, and groups are named from
+//! their repetition, energy and position in the track.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use napi_derive::napi;
+use num_complex::Complex32;
+use rustfft::{Fft, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use super::beats::BeatGrid;
+
+/// Feature blocks per second
+pub const BLOCK_RATE: f64 = 2.0;
+/// Length of the preview window `highlight_start` is chosen for, seconds
+pub const HIGHLIGHT_SECS: f64 = 30.0;
+
+const FFT_SIZE: usize = 4096;
+const HOP: usize = FFT_SIZE / 2;
+/// Longer tracks are summarised into proportionally longer blocks
+const MAX_BLOCKS: usize = 1200;
+const CHROMA_MIN_HZ: f32 = 55.0;
+const CHROMA_MAX_HZ: f32 = 5000.0;
+const MEL_BANDS: usize = 40;
+const MEL_MAX_HZ: f32 = 8000.0;
+/// MFCCs kept, from c1 (c0 only tracks level)
+const MFCC_COEFFS: usize = 13;
+const DIM: usize = 12 + MFCC_COEFFS;
+/// Share of block similarity coming from chroma, the rest from MFCCs
+const CHROMA_WEIGHT: f32 = 0.5;
+/// Half width of the novelty kernel, seconds
+const KERNEL_SECS: f64 = 8.0;
+const MIN_SEGMENT_SECS: f64 = 8.0;
+/// Novelty peaks below this fraction of the largest are not boundaries
+const PEAK_THRESHOLD: f64 = 0.2;
+/// Mean diagonal similarity at which two sections count as the same material
+const REPEAT_THRESHOLD: f64 = 0.45;
+/// Sections more than this much quieter than the chorus, dB, may be an intro or outro
+const QUIET_DB: f64 = 3.0;
+
+#[napi(object)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StructureSegment {
+    /// Seconds
+    pub start: f64,
+    pub end: f64,
+    /// `intro`, `verse`, `chorus`, `bridge` or `outro`
+    pub label: String,
+    /// Segments in the same group repeat the same material
+    pub group: u32,
+    /// 0..1, how clearly the boundaries and the label stand out
+    pub confidence: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct Structure {
+    pub segments: Vec<StructureSegment>,
+    /// Start of a `HIGHLIGHT_SECS` preview, on a downbeat when there's a grid
+    pub highlight_start: Option<f64>,
+}
+
+/// Summed frame features of one block
+#[derive(Clone, Copy, Default)]
+struct Block {
+    chroma: [f32; 12],
+    mfcc: [f32; MFCC_COEFFS],
+    power: f32,
+    frames: u32,
+}
+
+impl Block {
+    fn add(&mut self, other: &Self) {
+        for (a, b) in self.chroma.iter_mut().zip(&other.chroma) {
+            *a += b;
+        }
+        for (a, b) in self.mfcc.iter_mut().zip(&other.mfcc) {
+            *a += b;
+        }
+        self.power += other.power;
+        self.frames += other.frames;
+    }
+}
+
+/// Chroma and MFCC frames, `HOP` samples apart, summed into `BLOCK_RATE` blocks
+pub struct FeatureExtractor {
+    sample_rate: u32,
+    fft: Arc<dyn Fft<f32>>,
+    window: Vec<f32>,
+    ring: Vec<f32>,
+    write: usize,
+    since_hop: usize,
+    frames: usize,
+    spectrum: Vec<Complex32>,
+    power: Vec<f32>,
+    /// (bin, pitch class) for every bin in the chroma range
+    chroma_bins: Vec<(usize, usize)>,
+    /// Triangular mel filters as (first bin, weights)
+    mel: Vec<(usize, Vec<f32>)>,
+    dct: Vec<[f32; MEL_BANDS]>,
+    current: Block,
+    blocks: Vec<Block>,
+}
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10.0f32.powf(mel / 2595.0) - 1.0)
+}
+
+impl FeatureExtractor {
+    #[allow(clippy::cast_sign_loss)]
+    pub fn new(sample_rate: u32) -> Self {
+        let sample_rate = sample_rate.max(1);
+        let bin_hz = sample_rate as f32 / FFT_SIZE as f32;
+        let window = (0..FFT_SIZE)
+            .map(|i| {
+                0.5f32.mul_add(
+                    -(2.0 * std::f32::consts::PI * i as f32 / FFT_SIZE as f32).cos(),
+                    0.5,
+                )
+            })
+            .collect();
+
+        let chroma_bins = (1..=FFT_SIZE / 2)
+            .filter_map(|k| {
+                let hz = k as f32 * bin_hz;
+                (CHROMA_MIN_HZ..=CHROMA_MAX_HZ).contains(&hz).then(|| {
+                    let midi = 12.0f32.mul_add((hz / 440.0).log2(), 69.0);
+                    (k, midi.round() as usize % 12)
+                })
+            })
+            .collect();
+
+        let top = hz_to_mel(MEL_MAX_HZ.min(sample_rate as f32 / 2.0));
+        let edges: Vec<f32> = (0..MEL_BANDS + 2)
+            .map(|i| mel_to_hz(top * i as f32 / (MEL_BANDS + 1) as f32) / bin_hz)
+            .collect();
+        let mel = edges
+            .windows(3)
+            .map(|w| {
+                let first = w[0].ceil().max(1.0) as usize;
+                let last = (w[2].floor() as usize).min(FFT_SIZE / 2);
+                let weights = (first..=last)
+                    .map(|k| {
+                        let k = k as f32;
+                        if k <= w[1] {
+                            (k - w[0]) / (w[1] - w[0]).max(f32::EPSILON)
+                        } else {
+                            (w[2] - k) / (w[2] - w[1]).max(f32::EPSILON)
+                        }
+                    })
+                    .collect();
+                (first, weights)
+            })
+            .collect();
+        let dct = (1..=MFCC_COEFFS)
+            .map(|c| {
+                let mut row = [0.0; MEL_BANDS];
+                for (b, v) in row.iter_mut().enumerate() {
+                    *v = (std::f32::consts::PI * c as f32 * (b as f32 + 0.5) / MEL_BANDS as f32)
+                        .cos();
+                }
+                row
+            })
+            .collect();
+
+        Self {
+            sample_rate,
+            fft: FftPlanner::new().plan_fft_forward(FFT_SIZE),
+            window,
+            ring: vec![0.0; FFT_SIZE],
+            write: 0,
+            since_hop: 0,
+            frames: 0,
+            spectrum: vec![Complex32::new(0.0, 0.0); FFT_SIZE],
+            power: vec![0.0; FFT_SIZE / 2 + 1],
+            chroma_bins,
+            mel,
+            dct,
+            current: Block::default(),
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Feeds one mono sample
+    pub fn process(&mut self, sample: f32) {
+        self.ring[self.write] = sample;
+        self.write = (self.write + 1) % FFT_SIZE;
+        self.since_hop += 1;
+        if self.since_hop == HOP {
+            self.since_hop = 0;
+            self.frame();
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn frame(&mut self) {
+        // The window centre of frame `n` is `n * HOP` samples in
+        let centre = (self.frames * HOP) as f64 / f64::from(self.sample_rate);
+        self.frames += 1;
+        let block = (centre * BLOCK_RATE) as usize;
+        while self.blocks.len() < block {
+            self.blocks.push(std::mem::take(&mut self.current));
+        }
+
+        for (i, (bin, &w)) in self.spectrum.iter_mut().zip(&self.window).enumerate() {
+            *bin = Complex32::new(self.ring[(self.write + i) % FFT_SIZE] * w, 0.0);
+        }
+        self.fft.process(&mut self.spectrum);
+        for (p, bin) in self.power.iter_mut().zip(&self.spectrum) {
+            *p = bin.norm_sqr();
+        }
+
+        let mut frame = Block {
+            frames: 1,
+            power: self.power.iter().sum::<f32>() / FFT_SIZE as f32,
+            ..Block::default()
+        };
+        for &(k, pc) in &self.chroma_bins {
+            frame.chroma[pc] += self.power[k].sqrt();
+        }
+        let mut bands = [0.0f32; MEL_BANDS];
+        for (band, (first, weights)) in bands.iter_mut().zip(&self.mel) {
+            let energy: f32 = weights
+                .iter()
+                .zip(&self.power[*first..])
+                .map(|(w, p)| w * p)
+                .sum();
+            *band = (energy + 1e-10).ln();
+        }
+        for (c, row) in frame.mfcc.iter_mut().zip(&self.dct) {
+            *c = row.iter().zip(&bands).map(|(d, b)| d * b).sum();
+        }
+        self.current.add(&frame);
+    }
+
+    /// Blocks so far, including the one in progress
+    fn blocks(&self) -> Vec<Block> {
+        let mut blocks = self.blocks.clone();
+        if self.current.frames > 0 {
+            blocks.push(self.current);
+        }
+        blocks
+    }
+}
+
+fn normalize_in_place(v: &mut [f32]) {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > f32::EPSILON {
+        for x in v {
+            *x /= norm;
+        }
+    }
+}
+
+/// Per-block feature vectors whose dot product is the block similarity
+struct Features {
+    vectors: Vec<[f32; DIM]>,
+    /// Mean frame power of each block
+    power: Vec<f32>,
+}
+
+impl Features {
+    /// Chroma is made level-independent and centred on the track's average so
+    /// the shared key doesn't make every block alike; MFCCs are standardised
+    /// per coefficient. Both halves are unit length, scaled by their weight.
+    fn new(blocks: &[Block]) -> Self {
+        let n = blocks.len().max(1) as f32;
+        let mut chroma: Vec<[f32; 12]> = blocks
+            .iter()
+            .map(|b| {
+                let mut c = b.chroma;
+                normalize_in_place(&mut c);
+                c
+            })
+            .collect();
+        let mut mean = [0.0f32; 12];
+        for c in &chroma {
+            for (m, v) in mean.iter_mut().zip(c) {
+                *m += v / n;
+            }
+        }
+        for c in &mut chroma {
+            for (v, m) in c.iter_mut().zip(&mean) {
+                *v -= m;
+            }
+            normalize_in_place(c);
+        }
+
+        let mut mfcc: Vec<[f32; MFCC_COEFFS]> = blocks
+            .iter()
+            .map(|b| {
+                let mut m = b.mfcc;
+                for v in &mut m {
+                    *v /= b.frames.max(1) as f32;
+                }
+                m
+            })
+            .collect();
+        for k in 0..MFCC_COEFFS {
+            let mean = mfcc.iter().map(|m| m[k]).sum::<f32>() / n;
+            let std = (mfcc.iter().map(|m| (m[k] - mean).powi(2)).sum::<f32>() / n).sqrt();
+            for m in &mut mfcc {
+                m[k] = if std > f32::EPSILON {
+                    (m[k] - mean) / std
+                } else {
+                    0.0
+                };
+            }
+        }
+
+        let (cw, mw) = (CHROMA_WEIGHT.sqrt(), (1.0 - CHROMA_WEIGHT).sqrt());
+        let vectors = chroma
+            .iter()
+            .zip(&mut mfcc)
+            .map(|(c, m)| {
+                normalize_in_place(m);
+                let mut v = [0.0; DIM];
+                for (out, x) in v.iter_mut().zip(c) {
+                    *out = x * cw;
+                }
+                for (out, x) in v[12..].iter_mut().zip(m.iter()) {
+                    *out = x * mw;
+                }
+                v
+            })
+            .collect();
+        let power = blocks
+            .iter()
+            .map(|b| b.power / b.frames.max(1) as f32)
+            .collect();
+        Self { vectors, power }
+    }
+
+    const fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    fn similarity(&self, i: usize, j: usize) -> f64 {
+        let dot: f32 = self.vectors[i]
+            .iter()
+            .zip(&self.vectors[j])
+            .map(|(a, b)| a * b)
+            .sum();
+        f64::from(dot)
+    }
+
+    /// Level of a range of blocks, dB
+    fn level_db(&self, range: Range<usize>) -> f64 {
+        let len = range.len().max(1) as f64;
+        let power = self.power[range].iter().map(|&p| f64::from(p)).sum::<f64>() / len;
+        10.0 * (power + 1e-12).log10()
+    }
+
+    /// Foote novelty at every block boundary, with a Gaussian-tapered
+    /// checkerboard kernel shrunk symmetrically near the ends of the track
+    fn novelty(&self, half: usize) -> Vec<f64> {
+        let n = self.len();
+        let mut novelty = vec![0.0; n];
+        for (i, out) in novelty.iter_mut().enumerate().skip(1) {
+            let h = half.min(i).min(n - i);
+            if h < 2 {
+                continue;
+            }
+            let sigma = h as f64 / 2.0;
+            let taper = |k: usize| (-0.5 * ((k as f64 + 0.5) / sigma).powi(2)).exp();
+            let (mut sum, mut weight) = (0.0, 0.0);
+            for a in 0..h {
+                for b in 0..h {
+                    let g = taper(a) * taper(b);
+                    let (before_a, before_b) = (i - 1 - a, i - 1 - b);
+                    let (after_a, after_b) = (i + a, i + b);
+                    sum += g
+                        * (self.similarity(before_a, before_b) + self.similarity(after_a, after_b)
+                            - self.similarity(before_a, after_b)
+                            - self.similarity(after_a, before_b));
+                    weight += 4.0 * g;
+                }
+            }
+            *out = (sum / weight).max(0.0);
+        }
+        novelty
+    }
+
+    /// How well one range repeats the other: the best mean similarity along a
+    /// diagonal, sliding the shorter range within the longer, scaled down when
+    /// their lengths differ
+    fn repetition(&self, a: &Range<usize>, b: &Range<usize>) -> f64 {
+        let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+        if short.is_empty() {
+            return 0.0;
+        }
+        let best = (0..=long.len() - short.len())
+            .map(|offset| {
+                short
+                    .clone()
+                    .zip(long.start + offset..)
+                    .map(|(i, j)| self.similarity(i, j))
+                    .sum::<f64>()
+                    / short.len() as f64
+            })
+            .fold(f64::NEG_INFINITY, f64::max);
+        best * (short.len() as f64 / long.len() as f64).sqrt()
+    }
+}
+
+/// Local novelty maxima, strongest first, kept at least `min_len` blocks from
+/// each other and from the ends. Returns (block, strength 0..1) in time order.
+fn pick_boundaries(novelty: &[f64], min_len: usize) -> Vec<(usize, f64)> {
+    let n = novelty.len();
+    let top = novelty.iter().copied().fold(0.0, f64::max);
+    if top <= 0.0 {
+        return Vec::new();
+    }
+    let reach = (min_len / 2).max(1);
+    let mut peaks: Vec<(usize, f64)> = (min_len..n.saturating_sub(min_len - 1))
+        .filter(|&i| {
+            let lo = i.saturating_sub(reach);
+            let hi = (i + reach + 1).min(n);
+            novelty[i] >= PEAK_THRESHOLD * top && novelty[lo..hi].iter().all(|&v| v <= novelty[i])
+        })
+        .map(|i| (i, novelty[i] / top))
+        .collect();
+    peaks.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut kept: Vec<(usize, f64)> = Vec::new();
+    for (i, strength) in peaks {
+        if kept.iter().all(|&(k, _)| k.abs_diff(i) >= min_len) {
+            kept.push((i, strength));
+        }
+    }
+    kept.sort_by_key(|&(i, _)| i);
+    kept
+}
+
+/// Groups ranges that repeat each other, in order of first appearance.
+/// Returns each range's group and its similarity to the closest other range
+/// (in its group when it has company, anywhere otherwise).
+fn group_sections(features: &Features, ranges: &[Range<usize>]) -> (Vec<u32>, Vec<f64>) {
+    let n = ranges.len();
+    let mut repeat = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let r = features.repetition(&ranges[i], &ranges[j]);
+            repeat[i][j] = r;
+            repeat[j][i] = r;
+        }
+    }
+
+    let mut groups: Vec<u32> = Vec::with_capacity(n);
+    let mut count = 0;
+    for (i, row) in repeat.iter().enumerate() {
+        let best = (0..i)
+            .filter(|&j| row[j] >= REPEAT_THRESHOLD)
+            .max_by(|&a, &b| row[a].total_cmp(&row[b]));
+        if let Some(j) = best {
+            groups.push(groups[j]);
+        } else {
+            groups.push(count);
+            count += 1;
+        }
+    }
+
+    let closest = (0..n)
+        .map(|i| {
+            let shared = (0..n).any(|j| j != i && groups[j] == groups[i]);
+            (0..n)
+                .filter(|&j| j != i && (!shared || groups[j] == groups[i]))
+                .map(|j| repeat[i][j])
+                .fold(0.0, f64::max)
+        })
+        .collect();
+    (groups, closest)
+}
+
+/// Names each section. The chorus is the loudest group that repeats (or the
+/// loudest of several sections when nothing does); quiet or one-off sections
+/// at either end are the intro and outro; other repeated material is a verse,
+/// and one-off sections are a bridge once the chorus has been heard.
+fn label_sections(
+    groups: &[u32],
+    levels: &[f64],
+    closest: &[f64],
+) -> (Vec<&'static str>, Vec<f64>) {
+    let n = groups.len();
+    let members = |g: u32| groups.iter().filter(|&&x| x == g).count();
+    let group_level = |g: u32| {
+        let (sum, count) = groups
+            .iter()
+            .zip(levels)
+            .filter(|&(&x, _)| x == g)
+            .fold((0.0, 0.0), |(s, c), (_, &l)| (s + l, c + 1.0));
+        sum / count
+    };
+
+    let repeated = (0..n)
+        .filter(|&i| members(groups[i]) > 1)
+        .map(|i| groups[i])
+        .max_by(|&a, &b| group_level(a).total_cmp(&group_level(b)));
+    let chorus = repeated.or_else(|| {
+        (0..n)
+            .filter(|_| n > 1)
+            .max_by(|&a, &b| levels[a].total_cmp(&levels[b]))
+            .map(|i| groups[i])
+    });
+    let chorus_level = chorus.map_or(f64::NEG_INFINITY, group_level);
+    let first_chorus = (0..n).find(|&i| Some(groups[i]) == chorus).unwrap_or(n);
+
+    let mut labels = Vec::with_capacity(n);
+    let mut confidence = Vec::with_capacity(n);
+    for i in 0..n {
+        let is_repeated = members(groups[i]) > 1;
+        let quiet = levels[i] < chorus_level - QUIET_DB;
+        let at_edge = n > 1 && (i == 0 || i == n - 1);
+        let label = if Some(groups[i]) == chorus && !(at_edge && quiet) {
+            "chorus"
+        } else if at_edge && (quiet || !is_repeated) {
+            if i == 0 {
+                "intro"
+            } else {
+                "outro"
+            }
+        } else if is_repeated || i < first_chorus {
+            "verse"
+        } else {
+            "bridge"
+        };
+        labels.push(label);
+
+        let certainty = if n < 2 {
+            // Nothing to tell it apart from
+            0.0
+        } else if is_repeated {
+            closest[i]
+        } else if label == "chorus" {
+            // Loudest, but never repeated
+            0.5 * (1.0 - closest[i])
+        } else {
+            1.0 - closest[i]
+        };
+        confidence.push(certainty.clamp(0.0, 1.0));
+    }
+    (labels, confidence)
+}
+
+/// Start of the loudest `HIGHLIGHT_SECS` of blocks
+#[allow(clippy::cast_sign_loss)]
+fn loudest_window(features: &Features, rate: f64) -> f64 {
+    let len = ((HIGHLIGHT_SECS * rate) as usize).clamp(1, features.len());
+    let mut sum: f64 = features.power[..len].iter().map(|&p| f64::from(p)).sum();
+    let (mut best, mut best_sum) = (0, sum);
+    for i in len..features.len() {
+        sum += f64::from(features.power[i]) - f64::from(features.power[i - len]);
+        if sum > best_sum {
+            best = i + 1 - len;
+            best_sum = sum;
+        }
+    }
+    best as f64 / rate
+}
+
+/// Moves a preview start back so the whole window fits, keeping it on a downbeat
+fn fit_highlight(start: f64, grid: Option<&BeatGrid>, duration: f64) -> f64 {
+    let limit = (duration - HIGHLIGHT_SECS).max(0.0);
+    let Some(grid) = grid else {
+        return start.min(limit);
+    };
+    let snapped = grid.snap(start.min(limit), grid.beats_per_bar);
+    if snapped > limit {
+        grid.bars_before(limit, 1).max(0.0)
+    } else {
+        snapped
+    }
+}
+
+#[allow(clippy::cast_sign_loss)]
+pub fn analyze(extractor: &FeatureExtractor, grid: Option<&BeatGrid>, duration: f64) -> Structure {
+    let mut blocks = extractor.blocks();
+    if blocks.is_empty() {
+        return Structure::default();
+    }
+    let factor = blocks.len().div_ceil(MAX_BLOCKS);
+    if factor > 1 {
+        blocks = blocks
+            .chunks(factor)
+            .map(|chunk| {
+                let mut merged = Block::default();
+                for b in chunk {
+                    merged.add(b);
+                }
+                merged
+            })
+            .collect();
+    }
+    let rate = BLOCK_RATE / factor as f64;
+    let features = Features::new(&blocks);
+    let n = features.len();
+    let min_len = ((MIN_SEGMENT_SECS * rate).round() as usize).max(2);
+    if n < 2 * min_len {
+        return Structure {
+            segments: Vec::new(),
+            highlight_start: Some(0.0),
+        };
+    }
+
+    let half = ((KERNEL_SECS * rate).round() as usize).max(2);
+    let novelty = features.novelty(half);
+
+    // Boundaries move to the nearest downbeat; any that collapse together go
+    let mut cuts: Vec<(usize, f64, f64)> = Vec::new();
+    for (i, strength) in pick_boundaries(&novelty, min_len) {
+        let t = i as f64 / rate;
+        let t = grid.map_or(t, |g| g.snap(t, g.beats_per_bar));
+        let after_previous = cuts.last().map_or(0.0, |&(_, _, p)| p) < t;
+        if after_previous && t < duration {
+            cuts.push((i, strength, t));
+        }
+    }
+
+    let mut edges = vec![(0, 1.0, 0.0)];
+    edges.extend(cuts);
+    edges.push((n, 1.0, duration));
+    let ranges: Vec<Range<usize>> = edges.windows(2).map(|w| w[0].0..w[1].0).collect();
+    let levels: Vec<f64> = ranges
+        .iter()
+        .map(|r| features.level_db(r.clone()))
+        .collect();
+    let (groups, closest) = group_sections(&features, &ranges);
+    let (labels, label_confidence) = label_sections(&groups, &levels, &closest);
+
+    let segments: Vec<StructureSegment> = edges
+        .windows(2)
+        .enumerate()
+        .map(|(i, w)| StructureSegment {
+            start: w[0].2,
+            end: w[1].2,
+            label: labels[i].to_string(),
+            group: groups[i],
+            confidence: f64::midpoint(f64::midpoint(w[0].1, w[1].1), label_confidence[i]),
+        })
+        .collect();
+
+    // The first full-strength chorus, else the loudest stretch
+    let chorus_level = segments
+        .iter()
+        .zip(&levels)
+        .filter(|(s, _)| s.label == "chorus")
+        .map(|(_, &l)| l)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let start = segments
+        .iter()
+        .zip(&levels)
+        .find(|&(s, &l)| s.label == "chorus" && l >= chorus_level - 1.0)
+        .map_or_else(|| loudest_window(&features, rate), |(s, _)| s.start);
+
+    Structure {
+        segments,
+        highlight_start: Some(fit_highlight(start, grid, duration)),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::cast_sign_loss)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 22_050;
+
+    /// One section: a chord (MIDI notes) at `level`, with noise bursts every
+    /// `hat` seconds for texture
+    struct Section {
+        secs: f64,
+        notes: &'static [f64],
+        level: f64,
+        hat: f64,
+    }
+
+    fn extractor_for(sections: &[Section]) -> (FeatureExtractor, f64) {
+        let mut extractor = FeatureExtractor::new(RATE);
+        let rate = f64::from(RATE);
+        let mut noise = 0x2468_ace0u32;
+        let mut offset = 0.0;
+        for section in sections {
+            for n in 0..(section.secs * rate) as usize {
+                let t = n as f64 / rate;
+                let mut s: f64 = section
+                    .notes
+                    .iter()
+                    .map(|&note| {
+                        let hz = 440.0 * ((note - 69.0) / 12.0).exp2();
+                        (2.0 * std::f64::consts::PI * hz * t).sin()
+                    })
+                    .sum::<f64>()
+                    / section.notes.len() as f64;
+                s *= section.level;
+                let since_hat = t % section.hat;
+                noise = noise.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let white = f64::from(noise >> 8) / f64::from(1u32 << 24) - 0.5;
+                s += section.level * 0.5 * white * (-since_hat / 0.03).exp();
+                extractor.process(s as f32);
+            }
+            offset += section.secs;
+        }
+        (extractor, offset)
+    }
+
+    const INTRO: Section = Section {
+        secs: 16.0,
+        notes: &[48.0, 55.0],
+        level: 0.05,
+        hat: 1.0,
+    };
+    const VERSE: Section = Section {
+        secs: 16.0,
+        notes: &[57.0, 60.0, 64.0],
+        level: 0.15,
+        hat: 0.5,
+    };
+    const CHORUS: Section = Section {
+        secs: 16.0,
+        notes: &[53.0, 57.0, 60.0, 65.0],
+        level: 0.4,
+        hat: 0.25,
+    };
+    const BRIDGE: Section = Section {
+        secs: 16.0,
+        notes: &[50.0, 54.0, 57.0, 62.0],
+        level: 0.2,
+        hat: 0.75,
+    };
+
+    #[test]
+    fn labels_a_verse_chorus_song() {
+        let (extractor, duration) =
+            extractor_for(&[INTRO, VERSE, CHORUS, VERSE, CHORUS, BRIDGE, CHORUS, INTRO]);
+        let structure = analyze(&extractor, None, duration);
+
+        let labels: Vec<&str> = structure
+            .segments
+            .iter()
+            .map(|s| s.label.as_str())
+            .collect();
+        assert_eq!(
+            labels,
+            ["intro", "verse", "chorus", "verse", "chorus", "bridge", "chorus", "outro"]
+        );
+        for (i, segment) in structure.segments.iter().enumerate() {
+            let expected = 16.0 * i as f64;
+            assert!((segment.start - expected).abs() < 1.0, "{segment:?}");
+            assert!(segment.confidence > 0.5, "{segment:?}");
+        }
+        let chorus = structure.segments[2].group;
+        assert_eq!(structure.segments[4].group, chorus);
+        assert_eq!(structure.segments[6].group, chorus);
+        assert_eq!(structure.segments[1].group, structure.segments[3].group);
+        assert_ne!(structure.segments[1].group, chorus);
+
+        let highlight = structure.highlight_start.unwrap();
+        assert!((highlight - 32.0).abs() < 1.0, "{highlight}");
+    }
+
+    #[test]
+    fn highlight_fits_before_the_end() {
+        let (extractor, duration) = extractor_for(&[
+            INTRO,
+            INTRO,
+            Section {
+                secs: 24.0,
+                ..CHORUS
+            },
+        ]);
+        let structure = analyze(&extractor, None, duration);
+
+        assert_eq!(structure.segments.len(), 2, "{:?}", structure.segments);
+        assert_eq!(structure.segments[0].label, "intro");
+        assert_eq!(structure.segments[1].label, "chorus");
+        // The chorus starts at 32 s but a preview must end by 56 s
+        let highlight = structure.highlight_start.unwrap();
+        assert!((highlight - 26.0).abs() < 1e-9, "{highlight}");
+    }
+}
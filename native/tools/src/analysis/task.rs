@@ -0,0 +1,446 @@
+//! Asynchronous, cancellable analysis.
+//!
+//! `AnalysisTask` runs the same analyses as the synchronous exports on the
+//! tokio blocking pool instead of the calling thread. Cancelling the task stops
+//! decoding at the next packet and rejects the pending promises; calls made
+//! after that run normally. Progress is reported as the fraction of the audio
+//! decoded so far. `analyze_batch` spreads a list of files over a dedicated
+//! rayon pool so no more than `concurrency` files are decoded at once,
+//! streaming each result as it lands.
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi::{Error, Status};
+use napi_derive::napi;
+use rayon::prelude::*;
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    analyze_controlled, propose_long_mix, propose_transition, AdvancedTransition, AudioAnalysis,
+    TransitionProposal, LONG_MIX_NEXT_WINDOW, TRANSITION_NEXT_WINDOW,
+};
+
+/// Upper bound on the default batch concurrency
+const MAX_BATCH_THREADS: usize = 4;
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct AnalysisProgress {
+    /// File currently being analyzed
+    pub path: String,
+    /// 0..1 over the whole call, including every file it analyzes
+    pub progress: f64,
+}
+
+#[napi(object)]
+pub struct BatchOptions {
+    #[napi(js_name = "max_analyze_time")]
+    pub max_analyze_time: Option<f64>,
+    /// Also analyze the end of each file, as `analyze_audio_file` does.
+    /// Defaults to `true`
+    #[napi(js_name = "include_tail")]
+    pub include_tail: Option<bool>,
+    /// Files decoded at once, defaulting to the number of cores (at most 4)
+    pub concurrency: Option<u32>,
+}
+
+#[napi(object)]
+pub struct BatchResult {
+    /// Position of `path` in the list passed in
+    pub index: u32,
+    pub path: String,
+    /// Empty when the file couldn't be decoded
+    pub analysis: Option<AudioAnalysis>,
+}
+
+type ProgressFn = dyn Fn(&Path, f64) + Send + Sync;
+
+/// Cancellation and progress for one analysis, or one stage of several
+#[derive(Clone)]
+pub struct Control {
+    token: Option<CancellationToken>,
+    progress: Option<Arc<ProgressFn>>,
+    /// Range of the overall progress this stage covers
+    span: (f64, f64),
+}
+
+impl Default for Control {
+    fn default() -> Self {
+        Self {
+            token: None,
+            progress: None,
+            span: (0.0, 1.0),
+        }
+    }
+}
+
+impl Control {
+    fn new(
+        token: &CancellationToken,
+        on_progress: Option<ThreadsafeFunction<AnalysisProgress>>,
+    ) -> Self {
+        let progress = on_progress.map(|callback| {
+            Arc::new(move |path: &Path, progress: f64| {
+                callback.call(
+                    Ok(AnalysisProgress {
+                        path: path.to_string_lossy().into_owned(),
+                        progress,
+                    }),
+                    ThreadsafeFunctionCallMode::NonBlocking,
+                );
+            }) as Arc<ProgressFn>
+        });
+        Self {
+            token: Some(token.clone()),
+            progress,
+            ..Self::default()
+        }
+    }
+
+    /// Stage `index` of `count` equal stages
+    fn stage(&self, index: u32, count: u32) -> Self {
+        let (start, end) = self.span;
+        let width = (end - start) / f64::from(count.max(1));
+        Self {
+            span: (
+                width.mul_add(f64::from(index), start),
+                width.mul_add(f64::from(index + 1), start),
+            ),
+            ..self.clone()
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
+    /// Reports `fraction` of this stage done
+    pub fn report(&self, path: &Path, fraction: f64) {
+        if let Some(progress) = &self.progress {
+            let (start, end) = self.span;
+            progress(path, (end - start).mul_add(fraction.clamp(0.0, 1.0), start));
+        }
+    }
+}
+
+fn cancelled() -> Error {
+    Error::new(Status::Cancelled, "analysis cancelled".to_string())
+}
+
+/// Runs `job` on the blocking pool, rejecting if the task is cancelled before
+/// or while it runs
+async fn run_blocking<T: Send + 'static>(
+    token: &CancellationToken,
+    job: impl FnOnce() -> T + Send + 'static,
+) -> napi::Result<T> {
+    if token.is_cancelled() {
+        return Err(cancelled());
+    }
+    let result = napi::tokio::task::spawn_blocking(job)
+        .await
+        .map_err(|e| Error::from_reason(format!("join error: {e}")))?;
+    if token.is_cancelled() {
+        return Err(cancelled());
+    }
+    Ok(result)
+}
+
+/// Analyzes `paths` on `pool`, handing each finished file to `on_result`
+/// (files cut short by cancellation are skipped). Returns the number of files
+/// analyzed successfully.
+fn run_batch(
+    pool: &rayon::ThreadPool,
+    paths: &[String],
+    control: &Control,
+    analyze: impl Fn(&str, &Control) -> Option<AudioAnalysis> + Sync,
+    on_result: impl Fn(BatchResult) + Sync,
+) -> u32 {
+    let analyzed = AtomicU32::new(0);
+    pool.install(|| {
+        paths.par_iter().enumerate().for_each(|(index, path)| {
+            if control.is_cancelled() {
+                return;
+            }
+            let analysis = analyze(path, control);
+            // A cancelled analysis is cut short, not a decoding failure
+            if control.is_cancelled() {
+                return;
+            }
+            if analysis.is_some() {
+                analyzed.fetch_add(1, Ordering::Relaxed);
+            }
+            on_result(BatchResult {
+                index: index as u32,
+                path: path.clone(),
+                analysis,
+            });
+        });
+    });
+    analyzed.into_inner()
+}
+
+/// A cancellable handle for running analyses off the calling thread.
+/// One task can run several calls; `cancel` stops the ones in flight.
+#[napi]
+pub struct AnalysisTask {
+    /// Shared by the calls in flight, replaced on `cancel`
+    token: Mutex<CancellationToken>,
+}
+
+#[napi]
+impl AnalysisTask {
+    #[napi(constructor)]
+    pub fn new() -> Self {
+        Self {
+            token: Mutex::new(CancellationToken::new()),
+        }
+    }
+
+    fn token(&self) -> CancellationToken {
+        self.token
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl Default for AnalysisTask {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[napi]
+impl AnalysisTask {
+    /// Cancels the calls in flight; later calls start afresh
+    #[napi]
+    pub fn cancel(&self) {
+        let mut token = self.token.lock().unwrap_or_else(PoisonError::into_inner);
+        token.cancel();
+        *token = CancellationToken::new();
+    }
+
+    /// `analyze_audio_file` (or `analyze_audio_file_head` with
+    /// `include_tail: false`) on the blocking pool
+    #[napi]
+    #[allow(clippy::missing_errors_doc)]
+    pub async fn analyze(
+        &self,
+        path: String,
+        max_analyze_time: Option<f64>,
+        include_tail: Option<bool>,
+        on_progress: Option<ThreadsafeFunction<AnalysisProgress>>,
+    ) -> napi::Result<Option<AudioAnalysis>> {
+        let token = self.token();
+        let control = Control::new(&token, on_progress);
+        let include_tail = include_tail.unwrap_or(true);
+        run_blocking(&token, move || {
+            analyze_controlled(&path, max_analyze_time, include_tail, &control)
+        })
+        .await
+    }
+
+    /// `suggest_transition` on the blocking pool
+    #[napi]
+    #[allow(clippy::missing_errors_doc)]
+    pub async fn suggest_transition(
+        &self,
+        current_path: String,
+        next_path: String,
+        on_progress: Option<ThreadsafeFunction<AnalysisProgress>>,
+    ) -> napi::Result<Option<TransitionProposal>> {
+        let token = self.token();
+        let control = Control::new(&token, on_progress);
+        run_blocking(&token, move || {
+            let cur = analyze_controlled(&current_path, None, true, &control.stage(0, 2))?;
+            let next = analyze_controlled(
+                &next_path,
+                Some(TRANSITION_NEXT_WINDOW),
+                false,
+                &control.stage(1, 2),
+            )?;
+            Some(propose_transition(&cur, &next))
+        })
+        .await
+    }
+
+    /// `suggest_long_mix` on the blocking pool
+    #[napi]
+    #[allow(clippy::missing_errors_doc)]
+    pub async fn suggest_long_mix(
+        &self,
+        current_path: String,
+        next_path: String,
+        on_progress: Option<ThreadsafeFunction<AnalysisProgress>>,
+    ) -> napi::Result<Option<AdvancedTransition>> {
+        let token = self.token();
+        let control = Control::new(&token, on_progress);
+        run_blocking(&token, move || {
+            let cur = analyze_controlled(&current_path, None, true, &control.stage(0, 2))?;
+            let next = analyze_controlled(
+                &next_path,
+                Some(LONG_MIX_NEXT_WINDOW),
+                false,
+                &control.stage(1, 2),
+            )?;
+            Some(propose_long_mix(&cur, &next))
+        })
+        .await
+    }
+
+    /// Analyzes every file in `paths`, calling `on_result` as each one
+    /// finishes (not necessarily in order). Resolves to the number of files
+    /// analyzed successfully; once cancelled, files not yet finished are
+    /// skipped and the promise rejects.
+    #[napi]
+    #[allow(clippy::missing_errors_doc)]
+    pub async fn analyze_batch(
+        &self,
+        paths: Vec<String>,
+        options: Option<BatchOptions>,
+        on_result: ThreadsafeFunction<BatchResult>,
+    ) -> napi::Result<u32> {
+        let max_analyze_time = options.as_ref().and_then(|o| o.max_analyze_time);
+        let include_tail = options
+            .as_ref()
+            .and_then(|o| o.include_tail)
+            .unwrap_or(true);
+        let threads = options.as_ref().and_then(|o| o.concurrency).map_or_else(
+            || rayon::current_num_threads().min(MAX_BATCH_THREADS),
+            |c| c.max(1) as usize,
+        );
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .map_err(|e| Error::from_reason(format!("failed to start analysis pool: {e}")))?;
+        let token = self.token();
+        let control = Control::new(&token, None);
+
+        run_blocking(&token, move || {
+            run_batch(
+                &pool,
+                &paths,
+                &control,
+                |path, control| analyze_controlled(path, max_analyze_time, include_tail, control),
+                |result| {
+                    on_result.call(Ok(result), ThreadsafeFunctionCallMode::NonBlocking);
+                },
+            )
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{sine, write_wav, TempDir};
+    use std::sync::Barrier;
+
+    #[test]
+    fn stages_map_onto_overall_progress() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let token = CancellationToken::new();
+        let control = Control {
+            progress: Some({
+                let seen = Arc::clone(&seen);
+                Arc::new(move |_: &Path, p: f64| seen.lock().unwrap().push(p))
+            }),
+            ..Control::new(&token, None)
+        };
+
+        let path = Path::new("a.flac");
+        control.stage(0, 2).report(path, 0.5);
+        control.stage(1, 2).report(path, 0.0);
+        control.stage(1, 2).stage(1, 2).report(path, 1.0);
+        control.stage(1, 2).report(path, 2.0);
+        assert_eq!(*seen.lock().unwrap(), [0.25, 0.5, 1.0, 1.0]);
+
+        assert!(!control.stage(1, 2).is_cancelled());
+        token.cancel();
+        assert!(control.stage(1, 2).is_cancelled());
+        assert!(!Control::default().is_cancelled());
+    }
+
+    #[test]
+    fn cancelling_mid_decode_rejects_the_call() {
+        let dir = TempDir::new("task");
+        let path = dir.join("tone.wav");
+        write_wav(&path, 8000, sine(8000, 440.0, 0.5, 60));
+
+        let token = CancellationToken::new();
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let control = Control {
+            // Cancel as soon as decoding reports any progress
+            progress: Some({
+                let token = token.clone();
+                let reported = Arc::clone(&reported);
+                Arc::new(move |_: &Path, p: f64| {
+                    reported.lock().unwrap().push(p);
+                    token.cancel();
+                })
+            }),
+            ..Control::new(&token, None)
+        };
+        let job_path = path.to_string_lossy().into_owned();
+        let runtime = napi::tokio::runtime::Runtime::new().unwrap();
+        let result = runtime.block_on(run_blocking(&token, move || {
+            analyze_controlled(&job_path, None, true, &control)
+        }));
+
+        assert_eq!(result.unwrap_err().reason, "analysis cancelled");
+        let reported = reported.lock().unwrap().clone();
+        assert_eq!(reported.len(), 1, "{reported:?}");
+        assert!(reported[0] < 0.1, "{reported:?}");
+    }
+
+    #[test]
+    fn cancel_only_stops_calls_in_flight() {
+        let task = AnalysisTask::new();
+        let in_flight = task.token();
+        task.cancel();
+        assert!(in_flight.is_cancelled());
+        assert!(!task.token().is_cancelled());
+    }
+
+    #[test]
+    fn batch_reports_every_file_once_within_the_concurrency() {
+        let paths: Vec<String> = (0..12).map(|i| format!("{i}.flac")).collect();
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap();
+        let active = AtomicU32::new(0);
+        let peak = AtomicU32::new(0);
+        // Each job waits until two others are running too, so the pool must
+        // run three at once to finish; the counter catches a fourth
+        let full = Barrier::new(3);
+        let results = Mutex::new(Vec::new());
+
+        let analyzed = run_batch(
+            &pool,
+            &paths,
+            &Control::default(),
+            |_, _| {
+                let now = active.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                full.wait();
+                active.fetch_sub(1, Ordering::SeqCst);
+                None
+            },
+            |result| results.lock().unwrap().push((result.index, result.path)),
+        );
+
+        assert_eq!(analyzed, 0);
+        let mut results = results.into_inner().unwrap();
+        results.sort_unstable();
+        let expected: Vec<(u32, String)> = (0..12).map(|i| (i, format!("{i}.flac"))).collect();
+        assert_eq!(results, expected);
+        assert_eq!(peak.into_inner(), 3);
+    }
+}
@@ -0,0 +1,351 @@
+//! Global tempo estimation from the onset envelopes.
+//!
+//! Full-band and low-band (kick) onset autocorrelations are summed, and each
+//! lag is scored by a comb over its first few multiples so a beat period is
+//! supported by the bar structure above it. The strongest comb peak and its
+//! tempo octaves (half, double, and the 2:3 / 3:2 relatives) compete on comb
+//! strength times a log-Gaussian tempo prior. The winner is refined to a
+//! fraction of a BPM from the autocorrelation peaks at its multiples, which
+//! pin the period down far more precisely than the first peak alone.
+
+use super::beats::{
+    autocorrelation, normalize, OnsetDetector, MAX_BPM, MIN_BPM, PRIOR_BPM, PRIOR_OCTAVES,
+};
+
+/// Weight of low-band evidence against the full band
+const LOW_WEIGHT: f64 = 0.5;
+/// Multiples of a lag summed into its comb score, weighted 1 / k
+const COMB_HARMONICS: usize = 4;
+/// Octave relatives of the strongest lag that may replace it
+const OCTAVE_RATIOS: [f64; 4] = [0.5, 2.0, 2.0 / 3.0, 1.5];
+/// How far (as a fraction of the lag) a relative's peak is searched for
+const RELATIVE_SEARCH: f64 = 0.05;
+/// Multiples of the chosen period used to refine it
+const REFINE_HARMONICS: usize = 8;
+/// Less onset envelope than this gives no estimate, seconds
+const MIN_SECS: f64 = 5.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Tempo {
+    pub bpm: f64,
+    /// 0..1, how far the chosen period's comb score stands above the typical one
+    pub confidence: f64,
+}
+
+/// Combined, normalised autocorrelation for lags `0..=max_lag`
+fn combined_acf(full: &[f64], low: &[f64], max_lag: usize) -> Vec<f64> {
+    let acf = |env: &[f64]| -> Vec<f64> {
+        let acf: Vec<f64> = (0..=max_lag).map(|lag| autocorrelation(env, lag)).collect();
+        let zero = acf[0];
+        if zero > 0.0 {
+            acf.into_iter().map(|v| v / zero).collect()
+        } else {
+            vec![0.0; max_lag + 1]
+        }
+    };
+    let full = acf(full);
+    let low = acf(low);
+    full.iter()
+        .zip(&low)
+        .map(|(f, l)| LOW_WEIGHT.mul_add(*l, *f) / (1.0 + LOW_WEIGHT))
+        .collect()
+}
+
+/// Comb score of `lag`: the autocorrelation at its multiples, each taken as
+/// the peak within half a frame per multiple so an integer lag still finds
+/// the peaks of a fractional period
+#[allow(clippy::cast_sign_loss)]
+fn comb(acf: &[f64], lag: f64) -> f64 {
+    (1..=COMB_HARMONICS)
+        .map(|k| {
+            let k = k as f64;
+            let lo = (lag.mul_add(k, -k / 2.0)).round().max(0.0) as usize;
+            let hi = (lag.mul_add(k, k / 2.0)).round() as usize;
+            let peak = (lo..=hi.min(acf.len() - 1))
+                .map(|i| acf[i])
+                .fold(0.0, f64::max);
+            peak / k
+        })
+        .sum()
+}
+
+/// Vertex of the parabola through `values` around `i`, or `i` itself when
+/// it isn't a peak
+fn parabolic(values: &[f64], i: usize) -> f64 {
+    if i == 0 || i + 1 >= values.len() {
+        return i as f64;
+    }
+    let (a, b, c) = (values[i - 1], values[i], values[i + 1]);
+    let denom = 2.0f64.mul_add(-b, a + c);
+    if b >= a && b >= c && denom < 0.0 {
+        i as f64 + (0.5 * (a - c) / denom).clamp(-0.5, 0.5)
+    } else {
+        i as f64
+    }
+}
+
+/// Highest point of `values` in `lo..=hi`, refined to a fraction of a lag
+fn peak_in(values: &[f64], lo: usize, hi: usize) -> Option<f64> {
+    let hi = hi.min(values.len().checked_sub(1)?);
+    let i = (lo..=hi).max_by(|&a, &b| values[a].total_cmp(&values[b]))?;
+    Some(parabolic(values, i))
+}
+
+fn prior(lag: f64, rate: f64) -> f64 {
+    let bpm = 60.0 * rate / lag;
+    (-0.5 * ((bpm / PRIOR_BPM).log2() / PRIOR_OCTAVES).powi(2)).exp()
+}
+
+/// Beat period in frames from the autocorrelation peaks near its multiples,
+/// weighting each by its multiple since a peak at `k` lags pins the period
+/// down `k` times more finely
+#[allow(clippy::cast_sign_loss)]
+fn refine(acf: &[f64], lag: f64) -> f64 {
+    let (mut sum, mut weight) = (0.0, 0.0);
+    for k in 1..=REFINE_HARMONICS {
+        let target = lag * k as f64;
+        let reach = (target * RELATIVE_SEARCH).max(1.0);
+        if target + reach + 1.0 >= acf.len() as f64 {
+            break;
+        }
+        let lo = (target - reach).floor() as usize;
+        let hi = (target + reach).ceil() as usize;
+        let Some(found) = peak_in(acf, lo, hi) else {
+            break;
+        };
+        // Only peaks that stand out count
+        if acf[found.round() as usize] <= 0.0 {
+            continue;
+        }
+        sum += found;
+        weight += k as f64;
+    }
+    if weight > 0.0 {
+        sum / weight
+    } else {
+        lag
+    }
+}
+
+/// Estimates the global tempo from everything `onsets` has seen
+#[allow(clippy::cast_sign_loss)]
+pub fn estimate(onsets: &OnsetDetector) -> Option<Tempo> {
+    let rate = onsets.frame_rate();
+    if (onsets.full().len() as f64) < MIN_SECS * rate {
+        return None;
+    }
+    let full = normalize(onsets.full());
+    let low = normalize(onsets.low());
+
+    let lo = (60.0 * rate / MAX_BPM).floor() as usize;
+    let hi = (60.0 * rate / MIN_BPM).ceil() as usize;
+    let max_lag = (hi * REFINE_HARMONICS).min(full.len() / 2);
+    if max_lag <= hi {
+        return None;
+    }
+    let acf = combined_acf(&full, &low, max_lag);
+
+    let mut scores = vec![0.0; hi + 2];
+    for (lag, score) in scores.iter_mut().enumerate().skip(lo.saturating_sub(1)) {
+        *score = comb(&acf, lag as f64);
+    }
+    let strongest = peak_in(&scores, lo, hi)?;
+
+    let candidate = |lag: f64| {
+        let reach = (lag * RELATIVE_SEARCH).max(1.0);
+        let from = ((lag - reach).floor() as usize).max(lo);
+        let to = ((lag + reach).ceil() as usize).min(hi);
+        if from > to {
+            return None;
+        }
+        let found = peak_in(&scores, from, to)?;
+        Some((found, comb(&acf, found)))
+    };
+    let (lag, strength) = std::iter::once(strongest)
+        .chain(OCTAVE_RATIOS.iter().map(|r| strongest * r))
+        .filter_map(candidate)
+        .max_by(|a, b| (a.1 * prior(a.0, rate)).total_cmp(&(b.1 * prior(b.0, rate))))?;
+    if strength <= 0.0 {
+        return None;
+    }
+
+    let mut typical = scores[lo..=hi].to_vec();
+    typical.sort_by(f64::total_cmp);
+    let median = typical[typical.len() / 2];
+    let confidence = ((strength - median.max(0.0)) / strength).clamp(0.0, 1.0);
+
+    Some(Tempo {
+        bpm: 60.0 * rate / refine(&acf, lag),
+        confidence,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::{write_wav, TempDir};
+
+    const RATE: u32 = 22_050;
+
+    /// Drum hits per sixteenth-note step of a 4/4 bar
+    struct Pattern {
+        kick: &'static [usize],
+        snare: &'static [usize],
+        hat: &'static [usize],
+        click: &'static [usize],
+    }
+
+    const CLICKS: Pattern = Pattern {
+        kick: &[],
+        snare: &[],
+        hat: &[],
+        click: &[0, 4, 8, 12],
+    };
+    const ROCK: Pattern = Pattern {
+        kick: &[0, 8],
+        snare: &[4, 12],
+        hat: &[0, 2, 4, 6, 8, 10, 12, 14],
+        click: &[],
+    };
+    const FOUR_ON_THE_FLOOR: Pattern = Pattern {
+        kick: &[0, 4, 8, 12],
+        snare: &[4, 12],
+        hat: &[2, 6, 10, 14],
+        click: &[],
+    };
+    const BREAKBEAT: Pattern = Pattern {
+        kick: &[0, 10],
+        snare: &[4, 12],
+        hat: &[0, 2, 4, 6, 8, 10, 12, 14],
+        click: &[],
+    };
+    const SIXTEENTHS: Pattern = Pattern {
+        kick: &[0, 7, 8],
+        snare: &[4, 12],
+        hat: &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+        click: &[],
+    };
+
+    struct Noise(u32);
+
+    impl Noise {
+        fn next(&mut self) -> f64 {
+            self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            f64::from(self.0 >> 8) / f64::from(1u32 << 24) - 0.5
+        }
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn render(pattern: &Pattern, bpm: f64, secs: f64) -> Vec<f32> {
+        let rate = f64::from(RATE);
+        let mut out = vec![0.0f64; (secs * rate) as usize];
+        let mut noise = Noise(0x9e37_79b9);
+        let step = 60.0 / bpm / 4.0;
+        let mut add = |start: f64, len: f64, voice: &mut dyn FnMut(f64) -> f64| {
+            let first = (start * rate) as usize;
+            for (n, sample) in out
+                .iter_mut()
+                .skip(first)
+                .take((len * rate) as usize)
+                .enumerate()
+            {
+                *sample += voice(n as f64 / rate);
+            }
+        };
+
+        let mut index = 0;
+        loop {
+            let start = (index as f64).mul_add(step, 0.25);
+            if start >= secs {
+                break;
+            }
+            let pos = index % 16;
+            if pattern.kick.contains(&pos) {
+                add(start, 0.3, &mut |t| {
+                    // 120 Hz falling to 50 Hz
+                    let sweep = 2.1 * (1.0 - (-t / 0.03).exp());
+                    let phase = std::f64::consts::TAU * 50.0f64.mul_add(t, sweep);
+                    0.8 * phase.sin() * (-t / 0.15).exp()
+                });
+            }
+            if pattern.snare.contains(&pos) {
+                add(start, 0.2, &mut |t| {
+                    let tone = (2.0 * std::f64::consts::PI * 200.0 * t).sin();
+                    0.3f64.mul_add(tone, 0.6 * noise.next()) * (-t / 0.06).exp()
+                });
+            }
+            if pattern.hat.contains(&pos) {
+                let mut last = 0.0;
+                add(start, 0.05, &mut |t| {
+                    let white = noise.next();
+                    let high = white - last;
+                    last = white;
+                    0.2 * high * (-t / 0.015).exp()
+                });
+            }
+            if pattern.click.contains(&pos) {
+                add(start, 0.03, &mut |t| {
+                    0.6 * (2.0 * std::f64::consts::PI * 1000.0 * t).sin() * (-t / 0.008).exp()
+                });
+            }
+            index += 1;
+        }
+        out.into_iter().map(|s| s.clamp(-1.0, 1.0) as f32).collect()
+    }
+
+    /// Writes `samples` to a WAV file and runs the full head analysis on it
+    fn analyze(name: &str, samples: &[f32]) -> crate::analysis::AudioAnalysis {
+        let dir = TempDir::new(&format!("tempo-{name}"));
+        let path = dir.join("track.wav");
+        write_wav(&path, RATE, samples.iter().copied());
+        crate::analysis::analyze_audio_file_head(path.to_string_lossy().into_owned(), Some(30.0))
+            .unwrap()
+    }
+
+    fn assert_tempo(name: &str, pattern: &Pattern, bpm: f64) {
+        let analysis = analyze(&format!("{name}-{bpm}"), &render(pattern, bpm, 30.0));
+        let found = analysis.bpm.unwrap();
+        let confidence = analysis.bpm_confidence.unwrap();
+        let grid = analysis.beat_grid.unwrap().bpm;
+        assert!((found - bpm).abs() < 0.1, "{name} at {bpm}: {found}");
+        assert!(confidence > 0.5, "{name} at {bpm}: confidence {confidence}");
+        assert!((grid - bpm).abs() < 0.5, "{name} at {bpm}: grid {grid}");
+    }
+
+    #[test]
+    fn click_tracks() {
+        for bpm in [72.0, 120.0, 128.0, 150.0] {
+            assert_tempo("clicks", &CLICKS, bpm);
+        }
+    }
+
+    #[test]
+    fn rock_beats() {
+        for bpm in [90.0, 104.0, 132.0] {
+            assert_tempo("rock", &ROCK, bpm);
+        }
+    }
+
+    #[test]
+    fn four_on_the_floor_to_a_fraction_of_a_bpm() {
+        for bpm in [118.0, 123.7, 126.4, 140.0] {
+            assert_tempo("house", &FOUR_ON_THE_FLOOR, bpm);
+        }
+    }
+
+    #[test]
+    fn syncopated_and_busy_patterns_keep_their_octave() {
+        assert_tempo("breakbeat", &BREAKBEAT, 96.0);
+        assert_tempo("breakbeat", &BREAKBEAT, 140.0);
+        // Sixteenth hats shouldn't pull the estimate to double time
+        assert_tempo("sixteenths", &SIXTEENTHS, 85.0);
+    }
+
+    #[test]
+    fn noise_has_low_confidence() {
+        let mut noise = Noise(7);
+        let samples: Vec<f32> = (0..RATE * 30).map(|_| 0.3 * noise.next() as f32).collect();
+        let analysis = analyze("noise", &samples);
+        let confidence = analysis.bpm_confidence.unwrap_or(0.0);
+        assert!(confidence < 0.5, "confidence {confidence}");
+    }
+}
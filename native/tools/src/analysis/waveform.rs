@@ -0,0 +1,655 @@
+//! Seek-bar waveform overview: per-bucket min / max / RMS for every channel and
+//! an optional low / mid / high energy split, from a decode of the whole file.
+//!
+//! When the container knows its length and can seek, the file is cut into
+//! ranges that are decoded in parallel, each from its own seek point; otherwise
+//! it is decoded front to back. Each range decodes a short pre-roll before its
+//! start so the band filters are settled when its first block begins, and the
+//! last range runs to the end of the file whatever the container claimed.
+//! Results are quantised to 8 bits in a compact binary form, which is also what
+//! the analysis cache stores.
+
+use std::path::Path;
+
+use napi_derive::napi;
+use rayon::prelude::*;
+use symphonia::core::audio::{Channels, Signal};
+use symphonia::core::units::TimeBase;
+
+use super::decode;
+use super::loudness::Biquad;
+use crate::analysis_cache;
+
+/// Bumped whenever the binary layout or the measurement changes
+pub const WAVEFORM_VERSION: u32 = 2;
+pub const MAX_BUCKETS: u32 = 65_536;
+
+const MAGIC: &[u8; 4] = b"SPWF";
+const HEADER_LEN: usize = 24;
+const FLAG_BANDS: u8 = 1;
+
+/// Resolution of the intermediate blocks buckets are built from
+const BLOCKS_PER_SECOND: u32 = 200;
+const LOW_CROSSOVER_HZ: f64 = 250.0;
+const HIGH_CROSSOVER_HZ: f64 = 4000.0;
+/// Decoded ahead of a range to warm up the band filters (the 250 Hz section
+/// settles within a few tens of milliseconds)
+const PREROLL_SECS: f64 = 0.1;
+/// Shorter files aren't worth the extra seeks
+const MIN_PARALLEL_SECS: f64 = 30.0;
+const MAX_THREADS: usize = 8;
+const MAX_CHANNELS: usize = 8;
+
+#[napi(object)]
+pub struct WaveformOptions {
+    /// Also measure low (< 250 Hz), mid and high (> 4 kHz) band energy
+    pub bands: Option<bool>,
+    /// Decode threads; defaults to the number of cores, at most 8
+    pub threads: Option<u32>,
+}
+
+#[napi(object)]
+pub struct WaveformChannel {
+    /// Lowest sample per bucket, -1.0..=0.0
+    pub min: Vec<f64>,
+    /// Highest sample per bucket, 0.0..=1.0
+    pub max: Vec<f64>,
+    pub rms: Vec<f64>,
+    /// RMS of each band per bucket, present when requested
+    pub low: Option<Vec<f64>>,
+    pub mid: Option<Vec<f64>>,
+    pub high: Option<Vec<f64>>,
+}
+
+#[napi(object)]
+pub struct Waveform {
+    /// Seconds
+    pub duration: f64,
+    #[napi(js_name = "sample_rate")]
+    pub sample_rate: u32,
+    pub buckets: u32,
+    pub channels: Vec<WaveformChannel>,
+}
+
+/// Running statistics of one channel over a stretch of frames
+#[derive(Clone, Copy, Default)]
+struct Stats {
+    min: f32,
+    max: f32,
+    sum_sq: f64,
+    low: f64,
+    mid: f64,
+    high: f64,
+    frames: u32,
+}
+
+impl Stats {
+    fn merge(&mut self, other: &Self) {
+        if other.frames == 0 {
+            return;
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.sum_sq += other.sum_sq;
+        self.low += other.low;
+        self.mid += other.mid;
+        self.high += other.high;
+        self.frames += other.frames;
+    }
+
+    fn rms(&self, sum_sq: f64) -> f64 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            (sum_sq / f64::from(self.frames)).sqrt()
+        }
+    }
+}
+
+/// RBJ second-order Butterworth sections
+fn crossover(sample_rate: u32, freq: f64, high_pass: bool) -> Biquad {
+    let rate = f64::from(sample_rate.max(1));
+    let w0 = 2.0 * std::f64::consts::PI * freq.min(rate * 0.45) / rate;
+    let (sin, cos) = w0.sin_cos();
+    let alpha = sin / std::f64::consts::SQRT_2;
+    let a0 = 1.0 + alpha;
+    let (b0, b1) = if high_pass {
+        (f64::midpoint(1.0, cos), -(1.0 + cos))
+    } else {
+        (f64::midpoint(1.0, -cos), 1.0 - cos)
+    };
+    Biquad::new([
+        b0 / a0,
+        b1 / a0,
+        b0 / a0,
+        -2.0 * cos / a0,
+        (1.0 - alpha) / a0,
+    ])
+}
+
+/// Fills consecutive blocks of `block_len` frames for one stretch of the file
+struct BlockWriter<'a> {
+    blocks: &'a mut Vec<Stats>,
+    channels: usize,
+    block_len: u64,
+    /// Frame the first block starts at
+    start: u64,
+    /// Blocks can't grow past this many (every parallel range but the last)
+    limit: Option<usize>,
+    bands: Option<Vec<(Biquad, Biquad)>>,
+}
+
+impl<'a> BlockWriter<'a> {
+    fn new(
+        blocks: &'a mut Vec<Stats>,
+        layout: &Layout,
+        start: u64,
+        limit: Option<usize>,
+        with_bands: bool,
+    ) -> Self {
+        let bands = with_bands.then(|| {
+            (0..layout.channels)
+                .map(|_| {
+                    (
+                        crossover(layout.sample_rate, LOW_CROSSOVER_HZ, false),
+                        crossover(layout.sample_rate, HIGH_CROSSOVER_HZ, true),
+                    )
+                })
+                .collect()
+        });
+        Self {
+            blocks,
+            channels: layout.channels,
+            block_len: layout.block_len,
+            start,
+            limit,
+            bands,
+        }
+    }
+
+    /// Adds the frame at absolute position `pos`; frames before the start only
+    /// run through the band filters. Returns `false` once past the limit.
+    fn push(&mut self, pos: u64, frame: &[f32]) -> bool {
+        let Some(offset) = pos.checked_sub(self.start) else {
+            if let Some(bands) = self.bands.as_mut() {
+                for (&sample, (low_pass, high_pass)) in frame.iter().zip(bands.iter_mut()) {
+                    low_pass.process(f64::from(sample));
+                    high_pass.process(f64::from(sample));
+                }
+            }
+            return true;
+        };
+        let block = (offset / self.block_len) as usize;
+        if self.limit.is_some_and(|limit| block >= limit) {
+            return false;
+        }
+        let first = block * self.channels;
+        if self.blocks.len() < first + self.channels {
+            self.blocks.resize(first + self.channels, Stats::default());
+        }
+
+        for (c, &sample) in frame.iter().enumerate().take(self.channels) {
+            let stats = &mut self.blocks[first + c];
+            if stats.frames == 0 {
+                stats.min = sample;
+                stats.max = sample;
+            } else {
+                stats.min = stats.min.min(sample);
+                stats.max = stats.max.max(sample);
+            }
+            let x = f64::from(sample);
+            stats.sum_sq += x * x;
+            stats.frames += 1;
+
+            if let Some(bands) = self.bands.as_mut() {
+                let (low_pass, high_pass) = &mut bands[c];
+                let low = low_pass.process(x);
+                let high = high_pass.process(x);
+                let mid = x - low - high;
+                stats.low += low * low;
+                stats.mid += mid * mid;
+                stats.high += high * high;
+            }
+        }
+        true
+    }
+}
+
+struct Layout {
+    sample_rate: u32,
+    channels: usize,
+    block_len: u64,
+}
+
+/// Frame position of a packet timestamp
+#[allow(clippy::cast_sign_loss)]
+fn frame_of(time_base: TimeBase, ts: u64, sample_rate: u32) -> u64 {
+    let t = time_base.calc_time(ts);
+    ((t.seconds as f64 + t.frac) * f64::from(sample_rate)).round() as u64
+}
+
+/// Decodes the blocks from frame `start` on, at most `limit` of them (to the
+/// end of the file without one).
+fn scan(
+    path: &Path,
+    layout: &Layout,
+    start: u64,
+    limit: Option<usize>,
+    with_bands: bool,
+) -> Option<Vec<Stats>> {
+    let mut track = decode::open_track(path)?;
+    if start > 0 {
+        let preroll = if with_bands { PREROLL_SECS } else { 0.0 };
+        track.seek(start as f64 / f64::from(layout.sample_rate) - preroll)?;
+    }
+    let time_base = track.params.time_base;
+    let mut blocks = Vec::new();
+    let mut writer = BlockWriter::new(&mut blocks, layout, start, limit, with_bands);
+    let mut frame = [0.0f32; MAX_CHANNELS];
+    // Without a time base packets are assumed to follow each other from the start
+    let mut next_pos = 0u64;
+
+    track.decode(|ts, buf| {
+        let first = time_base.map_or(next_pos, |tb| frame_of(tb, ts, layout.sample_rate));
+        let channels = buf.spec().channels.count().min(layout.channels);
+        for i in 0..buf.frames() {
+            for (c, s) in frame.iter_mut().enumerate().take(channels) {
+                *s = buf.chan(c)[i];
+            }
+            if !writer.push(first + i as u64, &frame[..channels]) {
+                return false;
+            }
+        }
+        next_pos = first + buf.frames() as u64;
+        true
+    });
+    Some(blocks)
+}
+
+/// Decodes the whole file into blocks, in parallel where possible
+fn measure_blocks(path: &Path, with_bands: bool, threads: usize) -> Option<(Layout, Vec<Stats>)> {
+    let track = decode::open_track(path)?;
+    let sample_rate = track.params.sample_rate.unwrap_or(44_100).max(1);
+    let channels = track
+        .params
+        .channels
+        .map_or(2, Channels::count)
+        .clamp(1, MAX_CHANNELS);
+    // The parallel path needs timestamps to place each range's packets
+    let total_frames = track
+        .params
+        .n_frames
+        .filter(|_| track.params.time_base.is_some());
+    drop(track);
+
+    let layout = Layout {
+        sample_rate,
+        channels,
+        block_len: u64::from((sample_rate / BLOCKS_PER_SECOND).max(1)),
+    };
+
+    let parallel = total_frames
+        .filter(|&n| threads > 1 && n as f64 / f64::from(sample_rate) >= MIN_PARALLEL_SECS);
+    if let Some(total_frames) = parallel {
+        let total_blocks = total_frames.div_ceil(layout.block_len) as usize;
+        let per_range = total_blocks.div_ceil(threads);
+        let ranges = total_blocks.div_ceil(per_range);
+        // `n_frames` is only an estimate for some containers, so the last range
+        // isn't bounded; the others stop where the next one starts
+        let scanned: Option<Vec<Vec<Stats>>> = (0..ranges)
+            .into_par_iter()
+            .map(|i| {
+                let start = (i * per_range) as u64 * layout.block_len;
+                let limit = (i + 1 < ranges).then_some(per_range);
+                let mut range = scan(path, &layout, start, limit, with_bands)?;
+                if limit.is_some() {
+                    range.resize(per_range * channels, Stats::default());
+                }
+                Some(range)
+            })
+            .collect();
+        if let Some(scanned) = scanned {
+            return Some((layout, scanned.concat()));
+        }
+    }
+
+    let blocks = scan(path, &layout, 0, None, with_bands)?;
+    Some((layout, blocks))
+}
+
+fn quantize_signed(v: f64) -> u8 {
+    ((v.clamp(-1.0, 1.0) * 127.0).round() as i8).cast_unsigned()
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn quantize_unsigned(v: f64) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Merges blocks into `buckets` buckets and encodes them.
+///
+/// Layout (little endian): magic, format version `u8`, channel count `u8`,
+/// flags `u8`, a reserved byte, bucket count `u32`, sample rate `u32`, duration
+/// `f64`; then per channel the min and max rows (`i8`, ±127 = full scale),
+/// the RMS row and, with bands, the low / mid / high rows (`u8`, 255 = full scale).
+fn encode(layout: &Layout, blocks: &[Stats], buckets: usize, with_bands: bool) -> Vec<u8> {
+    let channels = layout.channels;
+    let block_count = blocks.len() / channels;
+    let frames: u64 = (0..block_count)
+        .map(|b| u64::from(blocks[b * channels].frames))
+        .sum();
+    let duration = frames as f64 / f64::from(layout.sample_rate);
+
+    let rows = if with_bands { 6 } else { 3 };
+    let mut out = Vec::with_capacity(HEADER_LEN + channels * rows * buckets);
+    out.extend_from_slice(MAGIC);
+    out.push(WAVEFORM_VERSION as u8);
+    out.push(channels as u8);
+    out.push(if with_bands { FLAG_BANDS } else { 0 });
+    out.push(0);
+    out.extend_from_slice(&(buckets as u32).to_le_bytes());
+    out.extend_from_slice(&layout.sample_rate.to_le_bytes());
+    out.extend_from_slice(&duration.to_le_bytes());
+
+    for c in 0..channels {
+        let merged: Vec<Stats> = (0..buckets)
+            .map(|b| {
+                let from = b * block_count / buckets;
+                let to = ((b + 1) * block_count / buckets)
+                    .max(from + 1)
+                    .min(block_count);
+                let mut stats = Stats::default();
+                for block in from..to {
+                    let s = &blocks[block * channels + c];
+                    if stats.frames == 0 {
+                        stats = *s;
+                    } else {
+                        stats.merge(s);
+                    }
+                }
+                stats
+            })
+            .collect();
+
+        out.extend(
+            merged
+                .iter()
+                .map(|s| quantize_signed(f64::from(s.min.min(0.0)))),
+        );
+        out.extend(
+            merged
+                .iter()
+                .map(|s| quantize_signed(f64::from(s.max.max(0.0)))),
+        );
+        out.extend(merged.iter().map(|s| quantize_unsigned(s.rms(s.sum_sq))));
+        if with_bands {
+            out.extend(merged.iter().map(|s| quantize_unsigned(s.rms(s.low))));
+            out.extend(merged.iter().map(|s| quantize_unsigned(s.rms(s.mid))));
+            out.extend(merged.iter().map(|s| quantize_unsigned(s.rms(s.high))));
+        }
+    }
+    out
+}
+
+/// Reads back what [`encode`] wrote; `None` for anything malformed.
+pub fn decode_waveform(data: &[u8]) -> Option<Waveform> {
+    if data.len() < HEADER_LEN || &data[..4] != MAGIC || u32::from(data[4]) != WAVEFORM_VERSION {
+        return None;
+    }
+    let channels = usize::from(data[5]);
+    let with_bands = data[6] & FLAG_BANDS != 0;
+    let buckets = u32::from_le_bytes(data[8..12].try_into().ok()?);
+    let sample_rate = u32::from_le_bytes(data[12..16].try_into().ok()?);
+    let duration = f64::from_le_bytes(data[16..24].try_into().ok()?);
+
+    let n = buckets as usize;
+    let rows = if with_bands { 6 } else { 3 };
+    if data.len() != HEADER_LEN + channels * rows * n {
+        return None;
+    }
+
+    let signed = |row: &[u8]| -> Vec<f64> {
+        row.iter()
+            .map(|&b| f64::from(b.cast_signed()) / 127.0)
+            .collect()
+    };
+    let unsigned = |row: &[u8]| -> Vec<f64> { row.iter().map(|&b| f64::from(b) / 255.0).collect() };
+
+    let channels = data[HEADER_LEN..]
+        .chunks_exact(rows * n)
+        .map(|ch| {
+            let row = |i: usize| &ch[i * n..(i + 1) * n];
+            WaveformChannel {
+                min: signed(row(0)),
+                max: signed(row(1)),
+                rms: unsigned(row(2)),
+                low: with_bands.then(|| unsigned(row(3))),
+                mid: with_bands.then(|| unsigned(row(4))),
+                high: with_bands.then(|| unsigned(row(5))),
+            }
+        })
+        .collect();
+
+    Some(Waveform {
+        duration,
+        sample_rate,
+        buckets,
+        channels,
+    })
+}
+
+/// Decodes `path` and returns its encoded waveform
+pub fn waveform_data(
+    path: &Path,
+    buckets: u32,
+    with_bands: bool,
+    threads: usize,
+) -> Option<Vec<u8>> {
+    let (layout, blocks) = measure_blocks(path, with_bands, threads)?;
+    if blocks.is_empty() {
+        return None;
+    }
+    Some(encode(
+        &layout,
+        &blocks,
+        buckets.clamp(1, MAX_BUCKETS) as usize,
+        with_bands,
+    ))
+}
+
+/// Waveform overview of the whole file for the seek bar.
+///
+/// Returns `buckets` min / max / RMS values per channel, plus low / mid / high
+/// band energy with `options.bands`, quantised to 8 bits. Served from the
+/// analysis cache when it's initialised.
+#[napi]
+#[allow(clippy::missing_errors_doc, clippy::trailing_empty_array)]
+pub async fn generate_waveform(
+    path: String,
+    buckets: u32,
+    options: Option<WaveformOptions>,
+) -> napi::Result<Option<Waveform>> {
+    let with_bands = options.as_ref().and_then(|o| o.bands).unwrap_or(false);
+    let threads = options.as_ref().and_then(|o| o.threads).map_or_else(
+        || rayon::current_num_threads().min(MAX_THREADS),
+        |t| (t as usize).clamp(1, MAX_THREADS),
+    );
+    let buckets = buckets.clamp(1, MAX_BUCKETS);
+
+    napi::tokio::task::spawn_blocking(move || {
+        let data = analysis_cache::get_or_generate_waveform(&path, buckets, with_bands, || {
+            waveform_data(Path::new(&path), buckets, with_bands, threads)
+        })?;
+        decode_waveform(&data)
+    })
+    .await
+    .map_err(|e| napi::Error::from_reason(format!("join error: {e}")))
+}
+
+#[cfg(test)]
+#[allow(clippy::cast_sign_loss, clippy::suboptimal_flops)]
+mod tests {
+    use super::*;
+    use crate::test_util::{write_wav, TempDir};
+
+    const RATE: u32 = 48_000;
+
+    fn blocks_for(freq: f64, amp: f64, secs: f64) -> (Layout, Vec<Stats>) {
+        let layout = Layout {
+            sample_rate: RATE,
+            channels: 2,
+            block_len: u64::from(RATE / BLOCKS_PER_SECOND),
+        };
+        let mut blocks = Vec::new();
+        let mut writer = BlockWriter::new(&mut blocks, &layout, 0, None, true);
+        for n in 0..(secs * f64::from(RATE)) as u64 {
+            let t = n as f64 / f64::from(RATE);
+            let s = (amp * (2.0 * std::f64::consts::PI * freq * t).sin()) as f32;
+            writer.push(n, &[s, -s * 0.5]);
+        }
+        (layout, blocks)
+    }
+
+    #[test]
+    fn encodes_and_decodes_buckets_per_channel() {
+        let (layout, blocks) = blocks_for(1000.0, 0.8, 2.0);
+        let data = encode(&layout, &blocks, 100, true);
+        assert_eq!(data.len(), HEADER_LEN + 2 * 6 * 100);
+
+        let waveform = decode_waveform(&data).unwrap();
+        assert_eq!(waveform.buckets, 100);
+        assert_eq!(waveform.sample_rate, RATE);
+        assert!((waveform.duration - 2.0).abs() < 1e-9);
+        let [left, right] = &waveform.channels[..] else {
+            panic!("expected two channels");
+        };
+        for i in 0..100 {
+            assert!((left.max[i] - 0.8).abs() < 0.02, "{}", left.max[i]);
+            assert!((left.min[i] + 0.8).abs() < 0.02, "{}", left.min[i]);
+            assert!((left.rms[i] - 0.8 / 2f64.sqrt()).abs() < 0.01);
+            assert!((right.max[i] - 0.4).abs() < 0.02);
+        }
+        assert!(decode_waveform(&data[..data.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn more_buckets_than_blocks_repeat_neighbours() {
+        let (layout, blocks) = blocks_for(1000.0, 0.5, 0.05);
+        let waveform = decode_waveform(&encode(&layout, &blocks, 64, false)).unwrap();
+        let left = &waveform.channels[0];
+        assert!(left.low.is_none());
+        assert!(left.max.iter().all(|&v| (v - 0.5).abs() < 0.02));
+    }
+
+    /// 8 kHz tone whose pitch and level change every second
+    fn stepped_tone(secs: u32) -> impl Iterator<Item = f32> {
+        const WAV_RATE: u32 = 8000;
+        (0..secs * WAV_RATE).map(|n| {
+            let t = f64::from(n) / f64::from(WAV_RATE);
+            let second = f64::from(n / WAV_RATE);
+            let freq = 60.0 + 37.0 * (second % 7.0);
+            let amp = 0.2 + 0.1 * (second % 5.0);
+            (amp * (2.0 * std::f64::consts::PI * freq * t).sin()) as f32
+        })
+    }
+
+    #[test]
+    fn parallel_ranges_match_a_sequential_decode() {
+        let dir = TempDir::new("waveform");
+        let path = dir.join("tone.wav");
+        write_wav(&path, 8000, stepped_tone(40));
+        let (layout, sequential) = measure_blocks(&path, true, 1).unwrap();
+        let (_, parallel) = measure_blocks(&path, true, 4).unwrap();
+
+        assert_eq!(layout.sample_rate, 8000);
+        assert_eq!(parallel.len(), sequential.len());
+        assert_eq!(sequential.len(), 40 * BLOCKS_PER_SECOND as usize);
+        let close = |a: f64, b: f64| (a - b).abs() <= 1e-6 + 1e-3 * a.abs().max(b.abs());
+        for (i, (p, s)) in parallel.iter().zip(&sequential).enumerate() {
+            assert_eq!(p.frames, s.frames, "block {i}");
+            assert!(close(p.sum_sq, s.sum_sq), "block {i}");
+            assert!(close(p.low, s.low), "block {i}: {} vs {}", p.low, s.low);
+            assert!(close(p.mid, s.mid), "block {i}: {} vs {}", p.mid, s.mid);
+            assert!(close(p.high, s.high), "block {i}: {} vs {}", p.high, s.high);
+        }
+    }
+
+    #[test]
+    fn preroll_settles_the_band_filters() {
+        let layout = Layout {
+            sample_rate: RATE,
+            channels: 1,
+            block_len: u64::from(RATE / BLOCKS_PER_SECOND),
+        };
+        let sample = |n: u64| {
+            let t = n as f64 / f64::from(RATE);
+            (0.5 * (2.0 * std::f64::consts::PI * 80.0 * t).sin()) as f32
+        };
+        let start = 40 * layout.block_len + 37;
+        let first_block_low = |from: u64| {
+            let mut blocks = Vec::new();
+            let mut writer = BlockWriter::new(&mut blocks, &layout, start, None, true);
+            for n in from..start + layout.block_len {
+                writer.push(n, &[sample(n)]);
+            }
+            blocks[0].low
+        };
+
+        // Low band energy of the range's first block in a decode from the top
+        let mut low_pass = crossover(RATE, LOW_CROSSOVER_HZ, false);
+        let expected: f64 = (0..start + layout.block_len)
+            .map(|n| low_pass.process(f64::from(sample(n))))
+            .skip(start as usize)
+            .map(|low| low * low)
+            .sum();
+
+        let warm = first_block_low(start - (PREROLL_SECS * f64::from(RATE)) as u64);
+        let cold = first_block_low(start);
+        assert!(
+            (warm - expected).abs() < 1e-3 * expected,
+            "{warm} vs {expected}"
+        );
+        assert!(
+            (cold - expected).abs() > 1e-2 * expected,
+            "{cold} vs {expected}"
+        );
+    }
+
+    #[test]
+    fn last_range_runs_past_the_estimated_length() {
+        let layout = Layout {
+            sample_rate: RATE,
+            channels: 1,
+            block_len: 10,
+        };
+        let mut bounded = Vec::new();
+        let mut writer = BlockWriter::new(&mut bounded, &layout, 100, Some(2), false);
+        assert!(writer.push(50, &[0.5]));
+        assert!(writer.push(119, &[0.5]));
+        assert!(!writer.push(120, &[0.5]));
+        assert_eq!(bounded.len(), 2);
+
+        let mut open = Vec::new();
+        let mut writer = BlockWriter::new(&mut open, &layout, 100, None, false);
+        for pos in 100..1000 {
+            assert!(writer.push(pos, &[0.5]));
+        }
+        assert_eq!(open.len(), 90);
+    }
+
+    #[test]
+    fn bands_follow_the_tone() {
+        for (freq, band) in [(80.0, 0), (1000.0, 1), (10_000.0, 2)] {
+            let (layout, blocks) = blocks_for(freq, 0.5, 1.0);
+            let waveform = decode_waveform(&encode(&layout, &blocks, 4, true)).unwrap();
+            let left = &waveform.channels[0];
+            let energy = [
+                left.low.as_ref().unwrap()[2],
+                left.mid.as_ref().unwrap()[2],
+                left.high.as_ref().unwrap()[2],
+            ];
+            let loudest = (0..3).max_by(|&a, &b| energy[a].total_cmp(&energy[b]));
+            assert_eq!(loudest, Some(band), "{freq} Hz: {energy:?}");
+        }
+    }
+}
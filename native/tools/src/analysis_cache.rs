@@ -0,0 +1,495 @@
+//! 音频分析结果的持久化缓存
+//!
+//! 以文件内容的快速哈希、`ANALYSIS_VERSION`、分析窗口和是否分析尾部为键，
+//! 把序列化后的 `AudioAnalysis` 存进 `SQLite`。分析算法升级（版本号变化）时旧结果整体作废，
+//! 条目超过上限时按最近使用时间淘汰。
+//! 波形概览以编码后的二进制形式存在同一个数据库的另一张表里
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use napi_derive::napi;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::analysis::waveform::WAVEFORM_VERSION;
+use crate::analysis::{analysis_window, AudioAnalysis, ANALYSIS_VERSION};
+
+/// 默认最多保留的分析结果数
+const DEFAULT_MAX_ENTRIES: u32 = 5000;
+
+/// 计算内容哈希时从文件头、中、尾各读取的字节数
+const HASH_CHUNK_SIZE: u64 = 64 * 1024;
+
+static CACHE: Mutex<Option<AnalysisCache>> = Mutex::new(None);
+
+struct AnalysisCache {
+    db_path: String,
+    conn: Connection,
+    max_entries: u32,
+}
+
+struct CacheKey {
+    hash: String,
+    /// 分析窗口，毫秒，避免拿浮点数做主键
+    window_ms: i64,
+    include_tail: bool,
+}
+
+impl CacheKey {
+    fn new(path: &Path, window: f64, include_tail: bool) -> io::Result<Self> {
+        Ok(Self {
+            hash: content_hash(path)?,
+            window_ms: (window * 1000.0).round() as i64,
+            include_tail,
+        })
+    }
+}
+
+/// 文件内容的快速哈希
+///
+/// 只读取文件大小和头、中、尾三段数据，改标签或换文件都会让哈希变化，
+/// 同时不用为了查缓存把整个文件读一遍
+fn content_hash(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+
+    let mut ctx = md5::Context::new();
+    ctx.consume(len.to_le_bytes());
+
+    if len <= HASH_CHUNK_SIZE * 3 {
+        // 小文件直接整个读
+        let mut data = Vec::with_capacity(len as usize);
+        file.read_to_end(&mut data)?;
+        ctx.consume(&data);
+    } else {
+        let mut buf = vec![0u8; HASH_CHUNK_SIZE as usize];
+        for offset in [0, len / 2 - HASH_CHUNK_SIZE / 2, len - HASH_CHUNK_SIZE] {
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut buf)?;
+            ctx.consume(&buf);
+        }
+    }
+    Ok(format!("{:x}", ctx.finalize()))
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs().try_into().unwrap_or(i64::MAX))
+}
+
+impl AnalysisCache {
+    fn open(db_path: &str, max_entries: u32) -> rusqlite::Result<Self> {
+        let conn = Connection::open(db_path)?;
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS analysis_cache (
+                 hash TEXT NOT NULL,
+                 version INTEGER NOT NULL,
+                 analyze_window INTEGER NOT NULL,
+                 include_tail INTEGER NOT NULL,
+                 data TEXT NOT NULL,
+                 last_used INTEGER NOT NULL,
+                 PRIMARY KEY (hash, version, analyze_window, include_tail)
+             );
+             CREATE INDEX IF NOT EXISTS idx_analysis_cache_last_used
+                 ON analysis_cache (last_used);
+             CREATE TABLE IF NOT EXISTS waveform_cache (
+                 hash TEXT NOT NULL,
+                 version INTEGER NOT NULL,
+                 buckets INTEGER NOT NULL,
+                 bands INTEGER NOT NULL,
+                 data BLOB NOT NULL,
+                 last_used INTEGER NOT NULL,
+                 PRIMARY KEY (hash, version, buckets, bands)
+             );
+             CREATE INDEX IF NOT EXISTS idx_waveform_cache_last_used
+                 ON waveform_cache (last_used);",
+        )?;
+        // 分析算法升级后旧版本的结果不再可信
+        conn.execute(
+            "DELETE FROM analysis_cache WHERE version != ?1",
+            [ANALYSIS_VERSION],
+        )?;
+        conn.execute(
+            "DELETE FROM waveform_cache WHERE version != ?1",
+            [WAVEFORM_VERSION],
+        )?;
+        let cache = Self {
+            db_path: db_path.to_string(),
+            conn,
+            max_entries,
+        };
+        cache.evict()?;
+        Ok(cache)
+    }
+
+    fn get(&self, key: &CacheKey) -> rusqlite::Result<Option<AudioAnalysis>> {
+        let data: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT data FROM analysis_cache
+                 WHERE hash = ?1 AND version = ?2 AND analyze_window = ?3 AND include_tail = ?4",
+                params![key.hash, ANALYSIS_VERSION, key.window_ms, key.include_tail],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(data) = data else {
+            return Ok(None);
+        };
+        self.conn.execute(
+            "UPDATE analysis_cache SET last_used = ?5
+             WHERE hash = ?1 AND version = ?2 AND analyze_window = ?3 AND include_tail = ?4",
+            params![
+                key.hash,
+                ANALYSIS_VERSION,
+                key.window_ms,
+                key.include_tail,
+                now_secs()
+            ],
+        )?;
+        // 反序列化失败说明数据损坏，当作未命中重新分析
+        Ok(serde_json::from_str(&data).ok())
+    }
+
+    fn put(&self, key: &CacheKey, analysis: &AudioAnalysis) -> rusqlite::Result<()> {
+        let Ok(data) = serde_json::to_string(analysis) else {
+            return Ok(());
+        };
+        self.conn.execute(
+            "INSERT OR REPLACE INTO analysis_cache
+                 (hash, version, analyze_window, include_tail, data, last_used)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                key.hash,
+                ANALYSIS_VERSION,
+                key.window_ms,
+                key.include_tail,
+                data,
+                now_secs()
+            ],
+        )?;
+        self.evict()
+    }
+
+    fn get_waveform(
+        &self,
+        hash: &str,
+        buckets: u32,
+        bands: bool,
+    ) -> rusqlite::Result<Option<Vec<u8>>> {
+        let data: Option<Vec<u8>> = self
+            .conn
+            .query_row(
+                "SELECT data FROM waveform_cache
+                 WHERE hash = ?1 AND version = ?2 AND buckets = ?3 AND bands = ?4",
+                params![hash, WAVEFORM_VERSION, buckets, bands],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if data.is_some() {
+            self.conn.execute(
+                "UPDATE waveform_cache SET last_used = ?5
+                 WHERE hash = ?1 AND version = ?2 AND buckets = ?3 AND bands = ?4",
+                params![hash, WAVEFORM_VERSION, buckets, bands, now_secs()],
+            )?;
+        }
+        Ok(data)
+    }
+
+    fn put_waveform(
+        &self,
+        hash: &str,
+        buckets: u32,
+        bands: bool,
+        data: &[u8],
+    ) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO waveform_cache
+                 (hash, version, buckets, bands, data, last_used)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![hash, WAVEFORM_VERSION, buckets, bands, data, now_secs()],
+        )?;
+        self.evict()
+    }
+
+    /// 每张表只保留最近使用的 `max_entries` 条
+    fn evict(&self) -> rusqlite::Result<()> {
+        for table in ["analysis_cache", "waveform_cache"] {
+            self.conn.execute(
+                &format!(
+                    "DELETE FROM {table} WHERE rowid IN (
+                         SELECT rowid FROM {table}
+                         ORDER BY last_used DESC LIMIT -1 OFFSET ?1
+                     )"
+                ),
+                [self.max_entries],
+            )?;
+        }
+        Ok(())
+    }
+
+    fn clear(&self) -> rusqlite::Result<usize> {
+        let analyses = self.conn.execute("DELETE FROM analysis_cache", [])?;
+        let waveforms = self.conn.execute("DELETE FROM waveform_cache", [])?;
+        Ok(analyses + waveforms)
+    }
+}
+
+/// 先查缓存，未命中时调用 `analyze` 并把结果写回
+///
+/// 没有初始化缓存或文件读不了时直接分析
+pub fn get_or_analyze(
+    path: &str,
+    max_analyze_time: Option<f64>,
+    include_tail: bool,
+    analyze: impl FnOnce() -> Option<AudioAnalysis>,
+) -> Option<AudioAnalysis> {
+    let key = CacheKey::new(
+        Path::new(path),
+        analysis_window(max_analyze_time),
+        include_tail,
+    )
+    .ok();
+
+    if let Some(key) = &key {
+        if let Ok(guard) = CACHE.lock() {
+            if let Some(cached) = guard.as_ref().and_then(|c| c.get(key).ok().flatten()) {
+                return Some(cached);
+            }
+        }
+    }
+
+    // 分析期间不持有锁，其他线程可以同时查询
+    let analysis = analyze()?;
+
+    if let Some(key) = &key {
+        if let Ok(guard) = CACHE.lock() {
+            if let Some(cache) = guard.as_ref() {
+                let _ = cache.put(key, &analysis);
+            }
+        }
+    }
+    Some(analysis)
+}
+
+/// 先查波形缓存，未命中时调用 `generate` 生成编码后的波形并写回
+///
+/// 没有初始化缓存或文件读不了时直接生成
+pub fn get_or_generate_waveform(
+    path: &str,
+    buckets: u32,
+    bands: bool,
+    generate: impl FnOnce() -> Option<Vec<u8>>,
+) -> Option<Vec<u8>> {
+    let hash = content_hash(Path::new(path)).ok();
+
+    if let Some(hash) = &hash {
+        if let Ok(guard) = CACHE.lock() {
+            if let Some(cached) = guard
+                .as_ref()
+                .and_then(|c| c.get_waveform(hash, buckets, bands).ok().flatten())
+            {
+                return Some(cached);
+            }
+        }
+    }
+
+    let data = generate()?;
+
+    if let Some(hash) = &hash {
+        if let Ok(guard) = CACHE.lock() {
+            if let Some(cache) = guard.as_ref() {
+                let _ = cache.put_waveform(hash, buckets, bands, &data);
+            }
+        }
+    }
+    Some(data)
+}
+
+/// 打开（或创建）分析缓存数据库，之后的分析和过渡建议都会先查缓存
+///
+/// 用同一个路径重复调用不会重新打开数据库
+#[napi]
+#[allow(clippy::missing_errors_doc, clippy::needless_pass_by_value)]
+pub fn init_analysis_cache(db_path: String, max_entries: Option<u32>) -> napi::Result<()> {
+    let max_entries = max_entries.unwrap_or(DEFAULT_MAX_ENTRIES).max(1);
+    let mut guard = CACHE
+        .lock()
+        .map_err(|_| napi::Error::from_reason("分析缓存锁已损坏"))?;
+
+    match guard.as_mut() {
+        Some(cache) if cache.db_path == db_path => cache.max_entries = max_entries,
+        _ => {
+            if let Some(parent) = Path::new(&db_path).parent() {
+                let _ = std::fs::create_dir_all(parent);
+            }
+            let cache = AnalysisCache::open(&db_path, max_entries)
+                .map_err(|e| napi::Error::from_reason(format!("打开分析缓存失败: {e}")))?;
+            *guard = Some(cache);
+        }
+    }
+    drop(guard);
+    Ok(())
+}
+
+/// 只查缓存，不做分析。参数与 `analyze_audio_file` 相同，`include_tail` 为 `false`
+/// 时对应 `analyze_audio_file_head` 的结果
+#[napi]
+#[allow(clippy::needless_pass_by_value)]
+pub fn get_cached_analysis(
+    path: String,
+    max_analyze_time: Option<f64>,
+    include_tail: Option<bool>,
+) -> Option<AudioAnalysis> {
+    let key = CacheKey::new(
+        Path::new(&path),
+        analysis_window(max_analyze_time),
+        include_tail.unwrap_or(true),
+    )
+    .ok()?;
+    CACHE.lock().ok()?.as_ref()?.get(&key).ok().flatten()
+}
+
+/// 清空分析缓存（包括波形），返回删除的条目数。缓存未初始化时返回 0
+#[napi]
+#[allow(clippy::missing_errors_doc)]
+pub fn clear_analysis_cache() -> napi::Result<u32> {
+    let guard = CACHE
+        .lock()
+        .map_err(|_| napi::Error::from_reason("分析缓存锁已损坏"))?;
+    let cleared = guard.as_ref().map_or(Ok(0), AnalysisCache::clear);
+    drop(guard);
+    cleared
+        .map(|n| n as u32)
+        .map_err(|e| napi::Error::from_reason(format!("清空分析缓存失败: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn open(dir: &TempDir, max_entries: u32) -> AnalysisCache {
+        AnalysisCache::open(dir.join("cache.db").to_str().unwrap(), max_entries).unwrap()
+    }
+
+    fn analysis(duration: f64) -> AudioAnalysis {
+        serde_json::from_value(serde_json::json!({
+            "duration": duration,
+            "fade_in_pos": 0.0,
+            "fade_out_pos": duration,
+            "segments": [],
+            "version": ANALYSIS_VERSION,
+            "analyze_window": 60.0,
+            "mix_center_pos": 0.0,
+            "mix_start_pos": 0.0,
+            "mix_end_pos": 0.0,
+            "energy_profile": [],
+        }))
+        .unwrap()
+    }
+
+    fn rows(cache: &AnalysisCache) -> i64 {
+        cache
+            .conn
+            .query_row("SELECT COUNT(*) FROM analysis_cache", [], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn hit_requires_the_same_window_and_tail() {
+        let dir = TempDir::new("cache-key");
+        let path = dir.file("a.mp3", b"audio");
+        let cache = open(&dir, DEFAULT_MAX_ENTRIES);
+
+        let key = CacheKey::new(&path, 60.0, true).unwrap();
+        cache.put(&key, &analysis(180.0)).unwrap();
+        let hit = cache
+            .get(&CacheKey::new(&path, 60.0, true).unwrap())
+            .unwrap();
+        assert_eq!(hit.map(|a| a.duration), Some(180.0));
+
+        let other_window = CacheKey::new(&path, 30.0, true).unwrap();
+        assert!(cache.get(&other_window).unwrap().is_none());
+        let head_only = CacheKey::new(&path, 60.0, false).unwrap();
+        assert!(cache.get(&head_only).unwrap().is_none());
+    }
+
+    #[test]
+    fn evicts_least_recently_used_over_capacity() {
+        let dir = TempDir::new("cache-lru");
+        let cache = open(&dir, 2);
+        let keys: Vec<CacheKey> = ["a", "b", "c"]
+            .iter()
+            .map(|name| CacheKey::new(&dir.file(name, name.as_bytes()), 60.0, true).unwrap())
+            .collect();
+
+        cache.put(&keys[0], &analysis(1.0)).unwrap();
+        cache.put(&keys[1], &analysis(2.0)).unwrap();
+        // 让 a 比 b 更早被使用，再通过读取刷新 a
+        for (key, last_used) in keys[..2].iter().zip([1, 2]) {
+            cache
+                .conn
+                .execute(
+                    "UPDATE analysis_cache SET last_used = ?1 WHERE hash = ?2",
+                    params![last_used, key.hash],
+                )
+                .unwrap();
+        }
+        assert!(cache.get(&keys[0]).unwrap().is_some());
+
+        cache.put(&keys[2], &analysis(3.0)).unwrap();
+        assert_eq!(rows(&cache), 2);
+        assert!(cache.get(&keys[0]).unwrap().is_some());
+        assert!(cache.get(&keys[1]).unwrap().is_none());
+        assert!(cache.get(&keys[2]).unwrap().is_some());
+    }
+
+    #[test]
+    fn open_drops_rows_from_other_versions() {
+        let dir = TempDir::new("cache-version");
+        let path = dir.file("a.mp3", b"audio");
+        let key = CacheKey::new(&path, 60.0, true).unwrap();
+
+        let cache = open(&dir, DEFAULT_MAX_ENTRIES);
+        cache.put(&key, &analysis(1.0)).unwrap();
+        cache
+            .conn
+            .execute(
+                "UPDATE analysis_cache SET version = ?1",
+                [ANALYSIS_VERSION - 1],
+            )
+            .unwrap();
+        drop(cache);
+
+        let cache = open(&dir, DEFAULT_MAX_ENTRIES);
+        assert_eq!(rows(&cache), 0);
+        assert!(cache.get(&key).unwrap().is_none());
+    }
+
+    #[test]
+    fn hash_covers_the_middle_chunk() {
+        let dir = TempDir::new("cache-hash");
+        let mut data: Vec<u8> = (0..HASH_CHUNK_SIZE * 5).map(|i| (i % 251) as u8).collect();
+        let path = dir.file("big.flac", &data);
+        let original = content_hash(&path).unwrap();
+
+        let middle = data.len() / 2;
+        data[middle] ^= 0xff;
+        std::fs::write(&path, &data).unwrap();
+        assert_ne!(content_hash(&path).unwrap(), original);
+
+        // 三段之外的字节不参与哈希
+        data[middle] ^= 0xff;
+        data[HASH_CHUNK_SIZE as usize + 1] ^= 0xff;
+        std::fs::write(&path, &data).unwrap();
+        assert_eq!(content_hash(&path).unwrap(), original);
+    }
+}
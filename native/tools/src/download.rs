@@ -24,7 +24,7 @@ const JPEG_MAGIC: &[u8] = &[0xFF, 0xD8, 0xFF];
 const PNG_MAGIC: &[u8] = &[0x89, 0x50, 0x4E, 0x47];
 
 // Error handling helper trait
-trait Context<T> {
+pub(crate) trait Context<T> {
     fn context<C>(self, context: C) -> Result<T>
     where
         C: std::fmt::Display;
@@ -525,20 +525,19 @@ async fn fetch_cover(client: &reqwest::Client, meta: &SongMetadata) -> Option<by
     }
 }
 
-fn get_or_create_tag(tagged_file: &mut lofty::file::TaggedFile) -> Result<&mut Tag> {
-    if tagged_file.primary_tag_mut().is_some() {
-        return Ok(tagged_file.primary_tag_mut().unwrap());
-    }
-
-    if tagged_file.first_tag_mut().is_some() {
-        return Ok(tagged_file.first_tag_mut().unwrap());
-    }
-
-    let tag_type = tagged_file.primary_tag_type();
-    tagged_file.insert_tag(Tag::new(tag_type));
+pub(crate) fn get_or_create_tag(tagged_file: &mut lofty::file::TaggedFile) -> Result<&mut Tag> {
+    let existing = tagged_file
+        .primary_tag()
+        .or_else(|| tagged_file.first_tag())
+        .map(Tag::tag_type);
+    let tag_type = existing.unwrap_or_else(|| {
+        let tag_type = tagged_file.primary_tag_type();
+        tagged_file.insert_tag(Tag::new(tag_type));
+        tag_type
+    });
 
     tagged_file
-        .primary_tag_mut()
+        .tag_mut(tag_type)
         .ok_or_else(|| Error::from_reason("Create tag failed"))
 }
 
@@ -6,11 +6,17 @@
 //! 即使它在其他平台是空操作以防止 JS 端在其他平台编译时找不到对应的函数声明
 
 mod analysis;
+mod analysis_cache;
 mod download;
+mod replaygain;
 mod scanner;
+#[cfg(test)]
+mod test_util;
 
 pub use analysis::*;
+pub use analysis_cache::*;
 pub use download::*;
+pub use replaygain::*;
 use napi_derive::napi;
 pub use scanner::scan_music_library;
 #[cfg(target_os = "windows")]
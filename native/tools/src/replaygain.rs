@@ -0,0 +1,497 @@
+//! `ReplayGain` 2.0 计算与写入
+//!
+//! 每个文件完整解码一遍，得到门限积分响度（BS.1770-4）和真峰值；
+//! 专辑模式下把所有文件首尾相接当作一条流计算专辑响度，峰值取各曲目最大值。
+//! 标签通过 lofty 写入，方式与 `download.rs` 写元数据相同。
+//! Opus 按 RFC 7845 改为写入 `R128_TRACK_GAIN` / `R128_ALBUM_GAIN`
+//! （Q7.8 定点数，相对 -23 LUFS），并移除 `REPLAYGAIN_*`，以免播放器重复调整
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use lofty::config::WriteOptions;
+use lofty::file::FileType;
+use lofty::prelude::*;
+use lofty::probe::Probe;
+use lofty::tag::{ItemKey, ItemValue, TagItem};
+use napi::threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode};
+use napi_derive::napi;
+use rayon::prelude::*;
+
+use crate::analysis::decode;
+use crate::analysis::loudness::LoudnessMeter;
+use crate::analysis::peak::PeakMeter;
+use crate::download::{get_or_create_tag, Context};
+
+/// `ReplayGain` 2.0 参考响度，LUFS
+const REFERENCE_LUFS: f64 = -18.0;
+/// Opus `R128_*_GAIN` 的参考响度（EBU R128），LUFS
+const R128_REFERENCE_LUFS: f64 = -23.0;
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct ReplayGainProgress {
+    /// `measure`：测量完一个文件；`write`：写完一个文件的标签
+    pub phase: String,
+    pub current: u32,
+    pub total: u32,
+    pub path: String,
+}
+
+#[napi(object)]
+pub struct TrackReplayGain {
+    pub path: String,
+    /// 门限积分响度，LUFS。解码失败或不支持的格式时为空
+    pub loudness: Option<f64>,
+    /// 相对 -18 LUFS 的增益，dB
+    pub gain: Option<f64>,
+    /// 真峰值，线性值（1.0 为满刻度）
+    pub peak: Option<f64>,
+    /// Opus 的 `R128_TRACK_GAIN`，Q7.8 定点数，相对 -23 LUFS。其他格式为空
+    pub r128_gain: Option<i32>,
+    /// 是否已写入标签，dry run 时始终为 `false`
+    pub written: bool,
+    pub error: Option<String>,
+}
+
+#[napi(object)]
+pub struct AlbumReplayGain {
+    pub loudness: f64,
+    pub gain: f64,
+    pub peak: f64,
+    /// Opus 的 `R128_ALBUM_GAIN`
+    pub r128_gain: i32,
+}
+
+#[napi(object)]
+pub struct ReplayGainResult {
+    pub tracks: Vec<TrackReplayGain>,
+    /// 仅专辑模式，且至少有一个文件测量成功时存在
+    pub album: Option<AlbumReplayGain>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Track,
+    Album,
+}
+
+impl Mode {
+    fn parse(mode: &str) -> napi::Result<Self> {
+        match mode {
+            "track" => Ok(Self::Track),
+            "album" => Ok(Self::Album),
+            _ => Err(napi::Error::from_reason(format!(
+                "未知的 ReplayGain 模式: {mode}，应为 track 或 album"
+            ))),
+        }
+    }
+}
+
+struct Measurement {
+    loudness: LoudnessMeter,
+    /// 各声道真峰值的最大值，线性
+    peak: f64,
+}
+
+#[derive(Clone, Copy)]
+struct Gain {
+    loudness: f64,
+    peak: f64,
+}
+
+impl Gain {
+    fn gain(self) -> f64 {
+        REFERENCE_LUFS - self.loudness
+    }
+
+    /// Q7.8 定点数，超出范围时饱和
+    fn r128_gain(self) -> i32 {
+        ((R128_REFERENCE_LUFS - self.loudness) * 256.0)
+            .round()
+            .clamp(f64::from(i16::MIN), f64::from(i16::MAX)) as i32
+    }
+}
+
+fn measure(path: &Path) -> Result<Measurement, String> {
+    decode_and_measure(path).ok_or_else(|| "无法解码音频".to_string())
+}
+
+fn decode_and_measure(path: &Path) -> Option<Measurement> {
+    let mut meters: Option<(LoudnessMeter, PeakMeter)> = None;
+    decode::for_each_frame(path, |spec, frame| {
+        let (loudness, peaks) = meters.get_or_insert_with(|| {
+            (
+                LoudnessMeter::for_layout(spec.rate, Some(spec.channels), frame.len()),
+                PeakMeter::new(spec.rate, frame.len()),
+            )
+        });
+        loudness.process(frame);
+        peaks.process(frame);
+    })?;
+    let (loudness, peaks) = meters?;
+    Some(Measurement {
+        loudness,
+        peak: peaks.info().true_peak,
+    })
+}
+
+fn format_gain(gain: f64) -> String {
+    format!("{gain:.2} dB")
+}
+
+fn format_peak(peak: f64) -> String {
+    format!("{peak:.6}")
+}
+
+const REPLAYGAIN_KEYS: [ItemKey; 4] = [
+    ItemKey::ReplayGainTrackGain,
+    ItemKey::ReplayGainTrackPeak,
+    ItemKey::ReplayGainAlbumGain,
+    ItemKey::ReplayGainAlbumPeak,
+];
+
+fn r128_item(key: &str, gain: Gain) -> TagItem {
+    TagItem::new(
+        ItemKey::Unknown(key.to_string()),
+        ItemValue::Text(gain.r128_gain().to_string()),
+    )
+}
+
+/// 写入曲目（以及专辑）增益标签，返回文件是否为 Opus
+fn write_tags(path: &Path, track: Gain, album: Option<Gain>) -> napi::Result<bool> {
+    let mut tagged_file = Probe::open(path)
+        .context("Open file failed")?
+        .read()
+        .context("Read tag failed")?;
+    let is_opus = tagged_file.file_type() == FileType::Opus;
+
+    let tag = get_or_create_tag(&mut tagged_file)?;
+
+    if is_opus {
+        for key in &REPLAYGAIN_KEYS {
+            tag.remove_key(key);
+        }
+        // 通用 Tag 没有 R128 的键，`insert_text` 会丢弃未映射的键
+        tag.insert_unchecked(r128_item("R128_TRACK_GAIN", track));
+        if let Some(album) = album {
+            tag.insert_unchecked(r128_item("R128_ALBUM_GAIN", album));
+        }
+    } else {
+        tag.insert_text(ItemKey::ReplayGainTrackGain, format_gain(track.gain()));
+        tag.insert_text(ItemKey::ReplayGainTrackPeak, format_peak(track.peak));
+        if let Some(album) = album {
+            tag.insert_text(ItemKey::ReplayGainAlbumGain, format_gain(album.gain()));
+            tag.insert_text(ItemKey::ReplayGainAlbumPeak, format_peak(album.peak));
+        }
+    }
+
+    tagged_file
+        .save_to_path(path, WriteOptions::default())
+        .context("Save tag failed")?;
+
+    Ok(is_opus)
+}
+
+fn is_opus(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("opus"))
+}
+
+fn report(
+    on_progress: Option<&ThreadsafeFunction<ReplayGainProgress>>,
+    phase: &str,
+    current: u32,
+    total: u32,
+    path: &str,
+) {
+    if let Some(callback) = on_progress {
+        callback.call(
+            Ok(ReplayGainProgress {
+                phase: phase.to_string(),
+                current,
+                total,
+                path: path.to_string(),
+            }),
+            ThreadsafeFunctionCallMode::NonBlocking,
+        );
+    }
+}
+
+/// 把所有测量成功的文件当作一条流：响度合并门限计算，峰值取最大值。
+/// 没有任何成功的测量时为空
+fn album_gain<'a>(measurements: impl Iterator<Item = &'a Measurement> + Clone) -> Option<Gain> {
+    let meters: Vec<&LoudnessMeter> = measurements.clone().map(|m| &m.loudness).collect();
+    (!meters.is_empty()).then(|| Gain {
+        loudness: LoudnessMeter::integrated_over(&meters),
+        peak: measurements.map(|m| m.peak).fold(0.0, f64::max),
+    })
+}
+
+fn run(
+    paths: &[String],
+    mode: Mode,
+    dry_run: bool,
+    on_progress: Option<&ThreadsafeFunction<ReplayGainProgress>>,
+) -> ReplayGainResult {
+    let total = paths.len() as u32;
+    let measured = AtomicU32::new(0);
+
+    let measurements: Vec<Result<Measurement, String>> = paths
+        .par_iter()
+        .map(|path| {
+            let measurement = measure(Path::new(path));
+            let current = measured.fetch_add(1, Ordering::Relaxed) + 1;
+            report(on_progress, "measure", current, total, path);
+            measurement
+        })
+        .collect();
+
+    let album = if mode == Mode::Album {
+        album_gain(measurements.iter().flatten())
+    } else {
+        None
+    };
+
+    let mut tracks = Vec::with_capacity(paths.len());
+    for (i, (path, measurement)) in paths.iter().zip(&measurements).enumerate() {
+        let measurement = match measurement {
+            Ok(measurement) => measurement,
+            Err(error) => {
+                tracks.push(TrackReplayGain {
+                    path: path.clone(),
+                    loudness: None,
+                    gain: None,
+                    peak: None,
+                    r128_gain: None,
+                    written: false,
+                    error: Some(error.clone()),
+                });
+                continue;
+            }
+        };
+        let gain = Gain {
+            loudness: measurement.loudness.integrated(),
+            peak: measurement.peak,
+        };
+
+        let (opus, written, error) = if dry_run {
+            (is_opus(Path::new(path)), false, None)
+        } else {
+            let result = write_tags(Path::new(path), gain, album);
+            report(on_progress, "write", i as u32 + 1, total, path);
+            match result {
+                Ok(opus) => (opus, true, None),
+                Err(e) => (is_opus(Path::new(path)), false, Some(e.reason.clone())),
+            }
+        };
+
+        tracks.push(TrackReplayGain {
+            path: path.clone(),
+            loudness: Some(gain.loudness),
+            gain: Some(gain.gain()),
+            peak: Some(gain.peak),
+            r128_gain: opus.then(|| gain.r128_gain()),
+            written,
+            error,
+        });
+    }
+
+    ReplayGainResult {
+        tracks,
+        album: album.map(|a| AlbumReplayGain {
+            loudness: a.loudness,
+            gain: a.gain(),
+            peak: a.peak,
+            r128_gain: a.r128_gain(),
+        }),
+    }
+}
+
+/// 计算 `ReplayGain` 2.0 增益并写入标签
+///
+/// `mode` 为 `track` 时只写曲目增益；为 `album` 时把所有文件当作一张专辑，
+/// 同时写入专辑增益。`dry_run` 为 `true` 时只返回计算结果，不修改文件。
+/// 单个文件解码或写入失败不会中断整体，错误记录在对应曲目的 `error` 中
+#[napi]
+#[allow(
+    clippy::missing_errors_doc,
+    clippy::needless_pass_by_value,
+    clippy::trailing_empty_array
+)]
+pub async fn compute_replaygain(
+    paths: Vec<String>,
+    mode: String,
+    dry_run: Option<bool>,
+    on_progress: Option<ThreadsafeFunction<ReplayGainProgress>>,
+) -> napi::Result<ReplayGainResult> {
+    let mode = Mode::parse(&mode)?;
+    let dry_run = dry_run.unwrap_or(false);
+
+    napi::tokio::task::spawn_blocking(move || run(&paths, mode, dry_run, on_progress.as_ref()))
+        .await
+        .map_err(|e| napi::Error::from_reason(format!("join 错误: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{sine, write_opus, write_wav, TempDir};
+
+    const RATE: u32 = 48_000;
+
+    fn tone(amp: f64, secs: u32) -> impl Iterator<Item = f32> {
+        sine(RATE, 1000.0, amp, secs)
+    }
+
+    fn measurement(amp: f64, secs: u32) -> Measurement {
+        let mut loudness = LoudnessMeter::new(RATE, vec![1.0]);
+        for s in tone(amp, secs) {
+            loudness.process(&[s]);
+        }
+        Measurement {
+            loudness,
+            peak: amp,
+        }
+    }
+
+    fn strings(paths: &[std::path::PathBuf]) -> Vec<String> {
+        paths
+            .iter()
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn gain_is_relative_to_minus_18_lufs() {
+        let gain = Gain {
+            loudness: -23.0,
+            peak: 0.5,
+        };
+        assert!((gain.gain() - 5.0).abs() < 1e-9);
+        assert_eq!(format_gain(gain.gain()), "5.00 dB");
+        assert_eq!(format_gain(-7.456), "-7.46 dB");
+        assert_eq!(format_peak(0.5), "0.500000");
+    }
+
+    #[test]
+    fn r128_gain_is_q7_8_relative_to_minus_23_lufs() {
+        let gain = |loudness| {
+            Gain {
+                loudness,
+                peak: 1.0,
+            }
+            .r128_gain()
+        };
+        assert_eq!(gain(-23.0), 0);
+        assert_eq!(gain(-9.03), -3576);
+        assert_eq!(gain(-33.5), 2688);
+        assert_eq!(gain(-300.0), i32::from(i16::MAX));
+        assert_eq!(gain(200.0), i32::from(i16::MIN));
+    }
+
+    #[test]
+    fn parses_modes() {
+        assert!(Mode::parse("track").is_ok_and(|m| m == Mode::Track));
+        assert!(Mode::parse("album").is_ok_and(|m| m == Mode::Album));
+        assert!(Mode::parse("Album").is_err());
+        assert!(Mode::parse("").is_err());
+    }
+
+    #[test]
+    fn album_gain_measures_all_tracks_as_one_stream() {
+        let loud = measurement(0.5, 10);
+        let quiet = measurement(0.25, 10);
+        let album = album_gain([&loud, &quiet].into_iter()).unwrap();
+
+        let mut joined = LoudnessMeter::new(RATE, vec![1.0]);
+        for s in tone(0.5, 10).chain(tone(0.25, 10)) {
+            joined.process(&[s]);
+        }
+        assert!((album.loudness - joined.integrated()).abs() < 0.01);
+        assert!(album.loudness < loud.loudness.integrated());
+        assert!(album.loudness > quiet.loudness.integrated());
+        assert!((album.peak - 0.5).abs() < 1e-9);
+
+        assert!(album_gain(std::iter::empty()).is_none());
+    }
+
+    #[test]
+    fn dry_run_measures_without_touching_files() {
+        let dir = TempDir::new("replaygain-dry-run");
+        let wav = dir.join("tone.wav");
+        write_wav(&wav, RATE, tone(0.5, 5));
+        let before = std::fs::read(&wav).unwrap();
+
+        let paths = strings(&[wav.clone(), dir.join("missing.flac")]);
+        let result = run(&paths, Mode::Album, true, None);
+
+        let tone = &result.tracks[0];
+        assert!(!tone.written);
+        assert!(tone.error.is_none());
+        assert!(tone.r128_gain.is_none());
+        // 1 kHz 正弦，振幅 0.5：约 -9.03 LUFS
+        let loudness = tone.loudness.unwrap();
+        assert!((loudness + 9.03).abs() < 0.1, "{loudness}");
+        assert!((tone.gain.unwrap() - (REFERENCE_LUFS - loudness)).abs() < 1e-9);
+        assert_eq!(std::fs::read(&wav).unwrap(), before);
+
+        assert_eq!(result.tracks[1].error.as_deref(), Some("无法解码音频"));
+        assert!(result.tracks[1].loudness.is_none());
+
+        let album = result.album.unwrap();
+        assert!((album.loudness - loudness).abs() < 0.01);
+    }
+
+    #[test]
+    fn opus_gets_r128_tags_instead_of_replaygain() {
+        let dir = TempDir::new("replaygain-opus");
+        let opus = dir.join("tone.opus");
+        let samples: Vec<f32> = tone(0.5, 5).collect();
+        write_opus(
+            &opus,
+            &samples,
+            &["TITLE=Tone", "REPLAYGAIN_TRACK_GAIN=-1.00 dB"],
+        );
+        let wav = dir.join("tone.wav");
+        write_wav(&wav, RATE, samples);
+
+        let result = run(
+            &strings(&[opus.clone(), wav.clone()]),
+            Mode::Album,
+            false,
+            None,
+        );
+        let track = &result.tracks[0];
+        assert!(track.written, "{:?}", track.error);
+        // 与 WAV 相同的 -9.03 LUFS 附近：R128 增益约 (-23 + 9.03) × 256
+        let loudness = track.loudness.unwrap();
+        assert!((loudness + 9.03).abs() < 0.2, "{loudness}");
+        let r128 = track.r128_gain.unwrap();
+        assert_eq!(
+            r128,
+            ((R128_REFERENCE_LUFS - loudness) * 256.0).round() as i32
+        );
+        assert!((r128 + 3576).abs() < 52, "{r128}");
+
+        let tagged = lofty::read_from_path(&opus).unwrap();
+        let tag = tagged.primary_tag().unwrap();
+        let text = |key: &str| tag.get_string(&ItemKey::Unknown(key.to_string()));
+        assert_eq!(text("R128_TRACK_GAIN"), Some(r128.to_string().as_str()));
+        let album = result.album.unwrap();
+        assert_eq!(
+            text("R128_ALBUM_GAIN"),
+            Some(album.r128_gain.to_string().as_str())
+        );
+        assert!(tag.get(&ItemKey::ReplayGainTrackGain).is_none());
+        assert_eq!(tag.title().as_deref(), Some("Tone"));
+
+        // 其他格式照常写 REPLAYGAIN_*
+        assert!(result.tracks[1].written);
+        assert!(result.tracks[1].r128_gain.is_none());
+        let tagged = lofty::read_from_path(&wav).unwrap();
+        let tag = tagged.primary_tag().unwrap();
+        assert!(tag.get(&ItemKey::ReplayGainTrackGain).is_some());
+    }
+}
@@ -0,0 +1,145 @@
+//! 单元测试共用的临时目录与测试音频（WAV、Ogg Opus）生成
+
+use std::path::{Path, PathBuf};
+
+use audiopus::coder::Encoder;
+use audiopus::{Application, Channels, SampleRate};
+
+/// 每个测试独占的临时目录，结束时（包括测试失败时）删除
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// `name` 在同一测试进程内需唯一
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("splayer-tools-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn join(&self, name: &str) -> PathBuf {
+        self.0.join(name)
+    }
+
+    /// 写入 `data` 并返回文件路径
+    pub fn file(&self, name: &str, data: &[u8]) -> PathBuf {
+        let path = self.join(name);
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// 写入 16 位单声道 PCM WAV，样本超出 [-1, 1] 时截断
+pub fn write_wav(path: &Path, rate: u32, samples: impl IntoIterator<Item = f32>) {
+    let data: Vec<u8> = samples
+        .into_iter()
+        .flat_map(|s| ((s.clamp(-1.0, 1.0) * 32767.0) as i16).to_le_bytes())
+        .collect();
+    let mut out = Vec::with_capacity(44 + data.len());
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // 单声道
+    out.extend_from_slice(&rate.to_le_bytes());
+    out.extend_from_slice(&(rate * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(&data);
+    std::fs::write(path, out).unwrap();
+}
+
+/// Ogg 页校验和：多项式 0x04C11DB7，不反射，初值与结果异或均为 0
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter().fold(0, |crc, &b| {
+        (0..8).fold(crc ^ (u32::from(b) << 24), |crc, _| {
+            if crc & 0x8000_0000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x04C1_1DB7
+            }
+        })
+    })
+}
+
+/// 一页只放一个包
+fn ogg_page(out: &mut Vec<u8>, packet: &[u8], granule: u64, sequence: u32, flags: u8) {
+    let start = out.len();
+    out.extend_from_slice(b"OggS");
+    out.extend_from_slice(&[0, flags]);
+    out.extend_from_slice(&granule.to_le_bytes());
+    out.extend_from_slice(&1u32.to_le_bytes()); // 流序列号
+    out.extend_from_slice(&sequence.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes()); // 校验和，最后填入
+    let full = packet.len() / 255;
+    out.push(full as u8 + 1);
+    out.extend(std::iter::repeat_n(255, full));
+    out.push((packet.len() % 255) as u8);
+    out.extend_from_slice(packet);
+    let crc = ogg_crc(&out[start..]);
+    out[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+}
+
+/// 把 48 kHz 单声道样本编码为 Ogg Opus（每包 20 ms），`comments` 写入 `OpusTags`
+pub fn write_opus(path: &Path, samples: &[f32], comments: &[&str]) {
+    const FRAME: usize = 960;
+    let encoder = Encoder::new(SampleRate::Hz48000, Channels::Mono, Application::Audio).unwrap();
+    let pre_skip = encoder.lookahead().unwrap() as u16;
+
+    let mut head = b"OpusHead".to_vec();
+    head.extend_from_slice(&[1, 1]); // 版本、声道数
+    head.extend_from_slice(&pre_skip.to_le_bytes());
+    head.extend_from_slice(&48_000u32.to_le_bytes());
+    head.extend_from_slice(&0i16.to_le_bytes()); // 输出增益
+    head.push(0); // 声道映射族
+
+    let vendor = b"splayer-test";
+    let mut tags = b"OpusTags".to_vec();
+    tags.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    tags.extend_from_slice(vendor);
+    tags.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+    for comment in comments {
+        tags.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        tags.extend_from_slice(comment.as_bytes());
+    }
+
+    let mut out = Vec::new();
+    ogg_page(&mut out, &head, 0, 0, 0x02);
+    ogg_page(&mut out, &tags, 0, 1, 0);
+    let frames: Vec<&[f32]> = samples.chunks(FRAME).collect();
+    let mut packet = [0u8; 4000];
+    for (i, frame) in frames.iter().enumerate() {
+        let mut input = [0.0; FRAME];
+        input[..frame.len()].copy_from_slice(frame);
+        let len = encoder.encode_float(&input, &mut packet).unwrap();
+        let last = i + 1 == frames.len();
+        // 粒度位置含 pre-skip；最后一页按实际样本数截断
+        let end = if last { samples.len() } else { (i + 1) * FRAME };
+        let granule = u64::from(pre_skip) + end as u64;
+        ogg_page(
+            &mut out,
+            &packet[..len],
+            granule,
+            i as u32 + 2,
+            if last { 0x04 } else { 0 },
+        );
+    }
+    std::fs::write(path, out).unwrap();
+}
+
+/// `secs` 秒、采样率 `rate` 的 `freq` Hz 正弦波
+pub fn sine(rate: u32, freq: f64, amp: f64, secs: u32) -> impl Iterator<Item = f32> {
+    (0..secs * rate).map(move |n| {
+        let t = f64::from(n) / f64::from(rate);
+        (amp * (2.0 * std::f64::consts::PI * freq * t).sin()) as f32
+    })
+}
@@ -0,0 +1,342 @@
+// src-tauri/src/audio_diagnostics.rs
+
+//! Playback counters shared by the Oboe callback, the decode thread and the HTTP
+//! downloader. Hot paths only touch atomics, so collecting them is close to free.
+
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Decode time histogram: 100 µs buckets up to 12.8 ms, plus one overflow bucket.
+const DECODE_BUCKET_US: u64 = 100;
+const DECODE_BUCKETS: usize = 128;
+
+/// Byte counter for one progressive HTTP download.
+pub struct DownloadStats {
+    started: Instant,
+    bytes: AtomicU64,
+    /// Read position of the decoder's stream, in bytes
+    consumed: AtomicU64,
+    /// Milliseconds from `started` until EOF/error, 0 while still downloading
+    finished_ms: AtomicU64,
+}
+
+impl DownloadStats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            bytes: AtomicU64::new(0),
+            consumed: AtomicU64::new(0),
+            finished_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn add_bytes(&self, n: usize) {
+        self.bytes.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    /// Called by the reader after every read/seek with its new position.
+    pub fn set_consumed(&self, pos: u64) {
+        self.consumed.store(pos, Ordering::Relaxed);
+    }
+
+    /// Downloaded bytes the decoder hasn't read yet. 0 after seeking past the download.
+    fn bytes_ahead(&self) -> u64 {
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        bytes.saturating_sub(self.consumed.load(Ordering::Relaxed))
+    }
+
+    pub fn finish(&self) {
+        let ms = (self.started.elapsed().as_millis() as u64).max(1);
+        self.finished_ms.store(ms, Ordering::Relaxed);
+    }
+
+    fn bytes_per_sec(&self) -> f64 {
+        let bytes = self.bytes.load(Ordering::Relaxed);
+        let ms = match self.finished_ms.load(Ordering::Relaxed) {
+            0 => self.started.elapsed().as_millis() as u64,
+            ms => ms,
+        };
+        if ms == 0 {
+            return 0.0;
+        }
+        bytes as f64 * 1000.0 / ms as f64
+    }
+}
+
+impl Default for DownloadStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parameters the Oboe stream actually opened with (may differ from what was requested).
+#[derive(Clone, serde::Serialize)]
+pub struct OutputInfo {
+    pub sample_rate: i32,
+    pub buffer_size_frames: i32,
+    pub frames_per_burst: i32,
+    pub performance_mode: String,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct AudioDiagnosticsSnapshot {
+    pub ring_fill: usize,
+    pub ring_capacity: usize,
+    pub underruns: u64,
+    pub overruns: u64,
+    pub decoded_packets: u64,
+    pub decode_avg_ms: f64,
+    pub decode_p99_ms: f64,
+    pub skipped_decode_errors: u64,
+    pub http_bytes_downloaded: Option<u64>,
+    /// Downloaded bytes ahead of the decoder's read position
+    pub http_bytes_buffered: Option<u64>,
+    pub http_download_rate: Option<f64>,
+    pub output: Option<OutputInfo>,
+}
+
+pub struct AudioDiagnostics {
+    ring_capacity: AtomicUsize,
+    ring_fill: AtomicUsize,
+    underruns: AtomicU64,
+    overruns: AtomicU64,
+    skipped_decode_errors: AtomicU64,
+    decoded_packets: AtomicU64,
+    decode_total_us: AtomicU64,
+    decode_histogram: [AtomicU32; DECODE_BUCKETS + 1],
+    http: Mutex<Option<Arc<DownloadStats>>>,
+    output: Mutex<Option<OutputInfo>>,
+    /// Periodic log interval in seconds, 0 = disabled
+    log_interval_secs: AtomicU32,
+    /// Set once the decoder hits EOF, so the final drain isn't counted as underruns
+    draining: AtomicBool,
+    /// Set after the first fully served callback since start or a flush; the
+    /// initial fill isn't counted as underruns
+    primed: AtomicBool,
+}
+
+impl Default for AudioDiagnostics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AudioDiagnostics {
+    pub fn new() -> Self {
+        Self {
+            ring_capacity: AtomicUsize::new(0),
+            ring_fill: AtomicUsize::new(0),
+            underruns: AtomicU64::new(0),
+            overruns: AtomicU64::new(0),
+            skipped_decode_errors: AtomicU64::new(0),
+            decoded_packets: AtomicU64::new(0),
+            decode_total_us: AtomicU64::new(0),
+            decode_histogram: std::array::from_fn(|_| AtomicU32::new(0)),
+            http: Mutex::new(None),
+            output: Mutex::new(None),
+            log_interval_secs: AtomicU32::new(0),
+            draining: AtomicBool::new(false),
+            primed: AtomicBool::new(false),
+        }
+    }
+
+    /// Clears per-track counters. Called when a new track starts.
+    pub fn reset(&self, ring_capacity: usize) {
+        self.ring_capacity.store(ring_capacity, Ordering::Relaxed);
+        self.ring_fill.store(0, Ordering::Relaxed);
+        self.underruns.store(0, Ordering::Relaxed);
+        self.overruns.store(0, Ordering::Relaxed);
+        self.skipped_decode_errors.store(0, Ordering::Relaxed);
+        self.decoded_packets.store(0, Ordering::Relaxed);
+        self.decode_total_us.store(0, Ordering::Relaxed);
+        for bucket in &self.decode_histogram {
+            bucket.store(0, Ordering::Relaxed);
+        }
+        self.draining.store(false, Ordering::Relaxed);
+        self.primed.store(false, Ordering::Relaxed);
+        *self.http.lock().unwrap() = None;
+        *self.output.lock().unwrap() = None;
+    }
+
+    // ---- Oboe callback side ----
+
+    pub fn record_callback(&self, ring_fill: usize, starved: bool) {
+        self.ring_fill.store(ring_fill, Ordering::Relaxed);
+        if !starved {
+            self.primed.store(true, Ordering::Relaxed);
+        } else if self.primed.load(Ordering::Relaxed) && !self.draining.load(Ordering::Relaxed) {
+            self.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The ring buffer was emptied for a seek; it refills before underruns count again.
+    pub fn record_flush(&self) {
+        self.ring_fill.store(0, Ordering::Relaxed);
+        self.primed.store(false, Ordering::Relaxed);
+    }
+
+    // ---- Decode thread side ----
+
+    pub fn record_decode(&self, elapsed: Duration) {
+        let us = elapsed.as_micros() as u64;
+        let bucket = ((us / DECODE_BUCKET_US) as usize).min(DECODE_BUCKETS);
+        self.decode_histogram[bucket].fetch_add(1, Ordering::Relaxed);
+        self.decode_total_us.fetch_add(us, Ordering::Relaxed);
+        self.decoded_packets.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// `dropped` decoded samples didn't fit in the ring buffer and were discarded.
+    /// Waiting for the callback to make room is normal backpressure, not an overrun.
+    pub fn record_overrun(&self, dropped: usize) {
+        if dropped > 0 {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_skipped_decode_error(&self) {
+        self.skipped_decode_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn set_draining(&self) {
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    pub fn set_http_stats(&self, stats: Option<Arc<DownloadStats>>) {
+        *self.http.lock().unwrap() = stats;
+    }
+
+    pub fn set_output(&self, info: OutputInfo) {
+        *self.output.lock().unwrap() = Some(info);
+    }
+
+    // ---- Logging ----
+
+    pub fn log_interval(&self) -> Option<Duration> {
+        match self.log_interval_secs.load(Ordering::Relaxed) {
+            0 => None,
+            secs => Some(Duration::from_secs(secs as u64)),
+        }
+    }
+
+    pub fn set_log_interval(&self, secs: u32) {
+        self.log_interval_secs.store(secs, Ordering::Relaxed);
+    }
+
+    pub fn log_snapshot(&self) {
+        match serde_json::to_string(&self.snapshot()) {
+            Ok(json) => log::info!("[AudioDiagnostics] {}", json),
+            Err(e) => log::warn!("[AudioDiagnostics] Failed to serialize snapshot: {}", e),
+        }
+    }
+
+    pub fn snapshot(&self) -> AudioDiagnosticsSnapshot {
+        let decoded_packets = self.decoded_packets.load(Ordering::Relaxed);
+        let decode_avg_ms = if decoded_packets == 0 {
+            0.0
+        } else {
+            self.decode_total_us.load(Ordering::Relaxed) as f64 / decoded_packets as f64 / 1000.0
+        };
+
+        let http = self.http.lock().unwrap().clone();
+
+        AudioDiagnosticsSnapshot {
+            ring_fill: self.ring_fill.load(Ordering::Relaxed),
+            ring_capacity: self.ring_capacity.load(Ordering::Relaxed),
+            underruns: self.underruns.load(Ordering::Relaxed),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            decoded_packets,
+            decode_avg_ms,
+            decode_p99_ms: self.decode_percentile_ms(0.99),
+            skipped_decode_errors: self.skipped_decode_errors.load(Ordering::Relaxed),
+            http_bytes_downloaded: http.as_ref().map(|h| h.bytes.load(Ordering::Relaxed)),
+            http_bytes_buffered: http.as_ref().map(|h| h.bytes_ahead()),
+            http_download_rate: http.as_ref().map(|h| h.bytes_per_sec()),
+            output: self.output.lock().unwrap().clone(),
+        }
+    }
+
+    /// Upper edge of the histogram bucket containing the given percentile.
+    fn decode_percentile_ms(&self, p: f64) -> f64 {
+        let counts: Vec<u64> = self
+            .decode_histogram
+            .iter()
+            .map(|b| b.load(Ordering::Relaxed) as u64)
+            .collect();
+        let total: u64 = counts.iter().sum();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let target = (total as f64 * p).ceil() as u64;
+        let mut seen = 0;
+        for (i, count) in counts.iter().enumerate() {
+            seen += count;
+            if seen >= target {
+                return ((i as u64 + 1) * DECODE_BUCKET_US) as f64 / 1000.0;
+            }
+        }
+        ((DECODE_BUCKETS as u64 + 1) * DECODE_BUCKET_US) as f64 / 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_reports_counters_and_http_buffer() {
+        let diagnostics = AudioDiagnostics::new();
+        diagnostics.reset(1024);
+        // The initial fill isn't an underrun, nor is waiting for the ring to drain
+        diagnostics.record_callback(0, true);
+        diagnostics.record_overrun(0);
+        diagnostics.record_callback(512, false);
+        diagnostics.record_callback(0, true);
+        diagnostics.record_overrun(256);
+        diagnostics.record_skipped_decode_error();
+        for us in [150, 250, 250, 20_000] {
+            diagnostics.record_decode(Duration::from_micros(us));
+        }
+
+        let http = Arc::new(DownloadStats::new());
+        http.add_bytes(10_000);
+        http.set_consumed(4_000);
+        diagnostics.set_http_stats(Some(http.clone()));
+
+        let snap = diagnostics.snapshot();
+        assert_eq!(snap.ring_capacity, 1024);
+        assert_eq!(snap.ring_fill, 0);
+        assert_eq!(snap.underruns, 1);
+        assert_eq!(snap.overruns, 1);
+        assert_eq!(snap.skipped_decode_errors, 1);
+        assert_eq!(snap.decoded_packets, 4);
+        assert!((snap.decode_avg_ms - 5.1625).abs() < 1e-9);
+        // The 20 ms packet lands in the overflow bucket
+        assert!((snap.decode_p99_ms - 12.9).abs() < 1e-9);
+        assert_eq!(snap.http_bytes_downloaded, Some(10_000));
+        assert_eq!(snap.http_bytes_buffered, Some(6_000));
+
+        // Seeking past the downloaded range leaves nothing buffered
+        http.set_consumed(50_000);
+        assert_eq!(diagnostics.snapshot().http_bytes_buffered, Some(0));
+
+        // Refilling after a seek flush isn't an underrun until the ring is primed again
+        diagnostics.record_flush();
+        diagnostics.record_callback(0, true);
+        assert_eq!(diagnostics.snapshot().underruns, 1);
+        diagnostics.record_callback(512, false);
+        diagnostics.record_callback(0, true);
+        assert_eq!(diagnostics.snapshot().underruns, 2);
+
+        // Draining at EOF isn't an underrun; a new track clears everything
+        diagnostics.set_draining();
+        diagnostics.record_callback(0, true);
+        assert_eq!(diagnostics.snapshot().underruns, 2);
+        diagnostics.reset(2048);
+        let snap = diagnostics.snapshot();
+        assert_eq!(snap.underruns, 0);
+        assert_eq!(snap.decode_p99_ms, 0.0);
+        assert!(snap.http_bytes_buffered.is_none());
+    }
+}
@@ -0,0 +1,76 @@
+// src-tauri/src/audio_dsp.rs
+
+//! Sample-level stages shared by live playback (`audio_player`) and offline export
+//! (`audio_export`): decoded buffer → interleaved stereo → gain.
+
+use symphonia::core::audio::{AudioBufferRef, SampleBuffer};
+
+/// Converts a decoded packet to interleaved f32 and appends it to `out` as stereo.
+pub fn append_stereo(decoded: AudioBufferRef<'_>, out: &mut Vec<f32>) {
+    let spec = *decoded.spec();
+    let num_frames = decoded.frames();
+    let num_channels = spec.channels.count();
+
+    let mut sample_buf = SampleBuffer::<f32>::new(num_frames as u64, spec);
+    sample_buf.copy_interleaved_ref(decoded);
+    downmix_to_stereo(sample_buf.samples(), num_channels, out);
+}
+
+/// Interleaved N-channel → interleaved stereo.
+/// Mono is duplicated to both sides, stereo passes through, and for more channels
+/// the first two (front L/R) are taken.
+pub fn downmix_to_stereo(samples: &[f32], num_channels: usize, out: &mut Vec<f32>) {
+    if num_channels == 0 {
+        return;
+    }
+    out.reserve(samples.len() / num_channels * 2);
+
+    let mut i = 0;
+    while i < samples.len() {
+        if num_channels == 1 {
+            let s = samples[i];
+            out.push(s);
+            out.push(s);
+            i += 1;
+        } else {
+            let l = samples[i];
+            let r = if i + 1 < samples.len() {
+                samples[i + 1]
+            } else {
+                l
+            };
+            out.push(l);
+            out.push(r);
+            i += num_channels;
+        }
+    }
+}
+
+pub fn apply_gain(samples: &mut [f32], gain: f32) {
+    if gain == 1.0 {
+        return;
+    }
+    for s in samples.iter_mut() {
+        *s *= gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn downmix_handles_mono_stereo_and_surround() {
+        let mut out = Vec::new();
+        downmix_to_stereo(&[0.1, 0.2], 1, &mut out);
+        assert_eq!(out, [0.1, 0.1, 0.2, 0.2]);
+
+        out.clear();
+        downmix_to_stereo(&[0.1, 0.2, 0.3, 0.4], 2, &mut out);
+        assert_eq!(out, [0.1, 0.2, 0.3, 0.4]);
+
+        out.clear();
+        downmix_to_stereo(&[0.1, 0.2, 0.9, 0.9, 0.9, 0.9], 6, &mut out);
+        assert_eq!(out, [0.1, 0.2]);
+    }
+}
@@ -8,17 +8,31 @@ use std::thread;
 use std::time::Duration;
 
 use oboe::{
-    AudioOutputCallback, AudioStream, AudioStreamBuilder, DataCallbackResult, Output,
-    PerformanceMode, SharingMode, Stereo,
+    AudioOutputCallback, AudioStream, AudioStreamBase, AudioStreamBuilder, AudioStreamSafe,
+    DataCallbackResult, Output, PerformanceMode, SharingMode, Stereo,
 };
 use ringbuf::traits::*;
-use symphonia::core::audio::SampleBuffer;
 use symphonia::core::codecs::DecoderOptions;
 use symphonia::core::formats::FormatOptions;
 use symphonia::core::io::{MediaSource, MediaSourceStream};
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
-use tauri::{Emitter, State};
+use tauri::{Emitter, Manager, State};
+
+use crate::audio_diagnostics::{
+    AudioDiagnostics, AudioDiagnosticsSnapshot, DownloadStats, OutputInfo,
+};
+use crate::audio_dsp;
+use crate::audio_session::{SessionSnapshot, SessionStore};
+
+/// 2 channels * 192000 samples/sec * 2 seconds = maximum needed
+const RING_BUFFER_SAMPLES: usize = 192000 * 4;
+/// Producer back-off while the ring buffer is full
+const RING_WAIT: Duration = Duration::from_micros(500);
+
+/// A probed-but-not-yet-decoding source, plus download stats when it comes from HTTP.
+type PreparedStream = (MediaSourceStream, Hint, Option<Arc<DownloadStats>>);
+type PreloadedStream = (String, MediaSourceStream, Hint, Option<Arc<DownloadStats>>);
 
 #[derive(Clone, serde::Serialize)]
 struct MetadataPayload {
@@ -49,6 +63,7 @@ struct ProgressiveStream {
     shared: Arc<(Mutex<SharedStreamData>, Condvar)>,
     pos: u64,
     content_length: Option<u64>,
+    stats: Arc<DownloadStats>,
 }
 
 impl Read for ProgressiveStream {
@@ -63,6 +78,7 @@ impl Read for ProgressiveStream {
                 buf[0..to_read]
                     .copy_from_slice(&state.buffer[self.pos as usize..self.pos as usize + to_read]);
                 self.pos += to_read as u64;
+                self.stats.set_consumed(self.pos);
                 return Ok(to_read);
             }
 
@@ -112,6 +128,7 @@ impl Seek for ProgressiveStream {
         }
 
         self.pos = new_pos as u64;
+        self.stats.set_consumed(self.pos);
         Ok(self.pos)
     }
 }
@@ -129,7 +146,8 @@ impl MediaSource for ProgressiveStream {
 // Commands sent from the frontend via Tauri IPC
 // ============================================================
 pub enum AudioCommand {
-    Play(String, bool),
+    /// url, start paused, start position in seconds
+    Play(String, bool, Option<f32>),
     Preload(String),
     Pause,
     Resume,
@@ -192,6 +210,7 @@ struct PlayerCallback {
     playing: Arc<AtomicBool>,
     /// Communication context with decoding thread
     stream_ctx: Arc<StreamContext>,
+    diagnostics: Arc<AudioDiagnostics>,
 }
 
 impl AudioOutputCallback for PlayerCallback {
@@ -229,20 +248,29 @@ impl AudioOutputCallback for PlayerCallback {
             self.stream_ctx
                 .flush_requested
                 .store(false, Ordering::Release);
+            self.diagnostics.record_flush();
             return DataCallbackResult::Continue;
         }
 
         let mut samples_read: u64 = 0;
+        let mut starved = false;
         let mut cons = self.consumer.lock().unwrap();
 
         for frame in frames.iter_mut() {
             // Each frame = 2 f32 samples (L, R)
-            let l = cons.try_pop().unwrap_or(0.0);
-            let r = cons.try_pop().unwrap_or(0.0);
+            let (l, r) = match (cons.try_pop(), cons.try_pop()) {
+                (Some(l), Some(r)) => (l, r),
+                (l, _) => {
+                    starved = true;
+                    (l.unwrap_or(0.0), 0.0)
+                }
+            };
             frame.0 = l * vol;
             frame.1 = r * vol;
             samples_read += 1;
         }
+        self.diagnostics
+            .record_callback(cons.occupied_len(), starved);
         drop(cons);
 
         // Update position based on exactly how many samples were output
@@ -264,7 +292,9 @@ pub struct AudioState {
     command_tx: Mutex<Sender<AudioCommand>>,
     status: Arc<Mutex<PlaybackStatus>>,
     _app_handle: tauri::AppHandle,
-    preloaded: Arc<Mutex<Option<(String, MediaSourceStream, Hint)>>>,
+    preloaded: Arc<Mutex<Option<PreloadedStream>>>,
+    diagnostics: Arc<AudioDiagnostics>,
+    session: Arc<SessionStore>,
 }
 
 impl AudioState {
@@ -285,10 +315,21 @@ impl AudioState {
         let preloaded_clone = preloaded.clone();
         let status_clone = status.clone();
         let app_handle_clone = app_handle.clone();
+        let diagnostics = Arc::new(AudioDiagnostics::new());
+        let diagnostics_clone = diagnostics.clone();
+        let session = Arc::new(SessionStore::new(app_handle.path().app_data_dir().ok()));
+        let session_clone = session.clone();
 
         // Main audio management thread
         thread::spawn(move || {
-            Self::audio_thread(rx, status_clone, app_handle_clone, preloaded_clone);
+            Self::audio_thread(
+                rx,
+                status_clone,
+                app_handle_clone,
+                preloaded_clone,
+                diagnostics_clone,
+                session_clone,
+            );
         });
 
         Self {
@@ -296,6 +337,8 @@ impl AudioState {
             status,
             _app_handle: app_handle,
             preloaded,
+            diagnostics,
+            session,
         }
     }
 
@@ -304,9 +347,21 @@ impl AudioState {
         rx: std::sync::mpsc::Receiver<AudioCommand>,
         status: Arc<Mutex<PlaybackStatus>>,
         app_handle: tauri::AppHandle,
-        preloaded: Arc<Mutex<Option<(String, MediaSourceStream, Hint)>>>,
+        preloaded: Arc<Mutex<Option<PreloadedStream>>>,
+        diagnostics: Arc<AudioDiagnostics>,
+        session: Arc<SessionStore>,
     ) {
         let volume = Arc::new(Mutex::new(1.0f32));
+
+        // Persist the current position (pause / stop / periodic)
+        let save_position = |status: &Mutex<PlaybackStatus>| {
+            if let Ok(st) = status.lock() {
+                // A seek the decode thread hasn't applied yet is where playback will resume
+                let pos = st.seek_to.unwrap_or_else(|| st.position_secs());
+                session.update(|s| s.position_secs = pos);
+            }
+            session.save();
+        };
         let playing = Arc::new(AtomicBool::new(false));
 
         // Current stream handle (if any)
@@ -324,7 +379,7 @@ impl AudioState {
             };
 
             match cmd {
-                AudioCommand::Play(url, start_paused) => {
+                AudioCommand::Play(url, start_paused, start_at) => {
                     // ---- Stop existing playback ----
                     playing.store(false, Ordering::SeqCst);
                     decode_stop.store(true, Ordering::SeqCst);
@@ -336,8 +391,7 @@ impl AudioState {
                     decode_stop.store(false, Ordering::SeqCst);
 
                     // ---- Create ring buffer ----
-                    // 2 channels * 192000 samples/sec * 2 seconds = maximum needed
-                    let rb = ringbuf::HeapRb::<f32>::new(192000 * 4);
+                    let rb = ringbuf::HeapRb::<f32>::new(RING_BUFFER_SAMPLES);
                     let (producer, consumer) = rb.split();
                     let arc_consumer = Arc::new(Mutex::new(consumer));
 
@@ -352,9 +406,18 @@ impl AudioState {
                         st.is_transitioning = true; // block false ENDED detection
                         st.duration_secs = 0.0;
                         st.position_samples = 0;
-                        st.seek_to = None;
+                        // Picked up by the decode thread before its first packet
+                        st.seek_to = start_at;
                         st.metadata = None;
                     }
+                    diagnostics.reset(RING_BUFFER_SAMPLES);
+                    // Saved once the queued commands below are applied, so a restored
+                    // position isn't overwritten with 0 on disk first
+                    session.update(|s| {
+                        s.url = Some(url.clone());
+                        s.position_secs = start_at.unwrap_or(0.0);
+                        s.duration_secs = 0.0;
+                    });
 
                     // ---- Start decode thread ----
                     let decode_stop_clone = decode_stop.clone();
@@ -364,6 +427,8 @@ impl AudioState {
                     let stream_ctx_for_decode = stream_ctx.clone();
                     let app_handle_clone = app_handle.clone();
                     let preloaded_for_decode = preloaded.clone();
+                    let diagnostics_for_decode = diagnostics.clone();
+                    let session_for_decode = session.clone();
 
                     _decode_handle = Some(thread::spawn(move || {
                         Self::decode_thread(
@@ -378,6 +443,8 @@ impl AudioState {
                             stream_ctx_for_decode,
                             app_handle_clone,
                             preloaded_for_decode,
+                            diagnostics_for_decode,
+                            session_for_decode,
                         );
                     }));
 
@@ -388,15 +455,22 @@ impl AudioState {
                     loop {
                         match rx.try_recv() {
                             Ok(queued) => {
-                                // Process immediately (only volume/pause make sense here)
+                                // Process immediately (only volume/pause/seek make sense here)
                                 match queued {
                                     AudioCommand::SetVolume(v) => {
                                         *volume.lock().unwrap() = v;
+                                        session.update(|s| s.volume = v);
                                     }
                                     AudioCommand::Pause => {
                                         playing.store(false, Ordering::SeqCst);
                                         status.lock().unwrap().is_playing = false;
                                     }
+                                    // A seek right after Play is picked up by the decode
+                                    // thread before its first packet
+                                    AudioCommand::Seek(time) => {
+                                        status.lock().unwrap().seek_to = Some(time);
+                                        session.update(|s| s.position_secs = time);
+                                    }
                                     _ => {}
                                 }
                             }
@@ -404,13 +478,14 @@ impl AudioState {
                             Err(TryRecvError::Disconnected) => return,
                         }
                     }
+                    session.save();
                 }
                 AudioCommand::Preload(url) => {
                     let preloaded_clone = preloaded.clone();
                     thread::spawn(move || {
-                        if let Some((mss, hint)) = Self::prepare_stream(&url) {
+                        if let Some((mss, hint, http_stats)) = Self::prepare_stream(&url) {
                             let mut p = preloaded_clone.lock().unwrap();
-                            *p = Some((url, mss, hint));
+                            *p = Some((url, mss, hint, http_stats));
                         }
                     });
                 }
@@ -419,6 +494,7 @@ impl AudioState {
                     if let Ok(mut st) = status.lock() {
                         st.is_playing = false;
                     }
+                    save_position(&status);
                 }
                 AudioCommand::Resume => {
                     playing.store(true, Ordering::SeqCst);
@@ -429,6 +505,7 @@ impl AudioState {
                 AudioCommand::Stop => {
                     playing.store(false, Ordering::SeqCst);
                     decode_stop.store(true, Ordering::SeqCst);
+                    save_position(&status);
                     if let Ok(mut st) = status.lock() {
                         st.is_playing = false;
                         st.position_samples = 0;
@@ -436,12 +513,18 @@ impl AudioState {
                 }
                 AudioCommand::SetVolume(v) => {
                     *volume.lock().unwrap() = v;
+                    session.update(|s| s.volume = v);
                 }
                 AudioCommand::Seek(time) => {
                     if let Ok(mut st) = status.lock() {
                         st.seek_to = Some(time);
                         st.position_samples = (time * st.sample_rate as f32) as u64;
                     }
+                    session.update(|s| s.position_secs = time);
+                    // The decode thread only saves periodically while playing
+                    if !playing.load(Ordering::SeqCst) {
+                        session.save();
+                    }
                 }
             }
         }
@@ -459,11 +542,13 @@ impl AudioState {
         volume: Arc<Mutex<f32>>,
         stream_ctx: Arc<StreamContext>,
         app_handle: tauri::AppHandle,
-        preloaded: Arc<Mutex<Option<(String, MediaSourceStream, Hint)>>>,
+        preloaded: Arc<Mutex<Option<PreloadedStream>>>,
+        diagnostics: Arc<AudioDiagnostics>,
+        session: Arc<SessionStore>,
     ) {
         let preloaded_data = {
             let mut p = preloaded.lock().unwrap();
-            if let Some((p_url, _, _)) = &*p {
+            if let Some((p_url, _, _, _)) = &*p {
                 if p_url == &url {
                     p.take()
                 } else {
@@ -474,14 +559,15 @@ impl AudioState {
             }
         };
 
-        let (mss, hint) = if let Some((_, mss, hint)) = preloaded_data {
-            (mss, hint)
+        let (mss, hint, http_stats) = if let Some((_, mss, hint, http_stats)) = preloaded_data {
+            (mss, hint, http_stats)
         } else {
             match Self::prepare_stream(&url) {
                 Some(s) => s,
                 None => return,
             }
         };
+        diagnostics.set_http_stats(http_stats);
 
         let mut probed = match symphonia::default::get_probe().format(
             &hint,
@@ -537,6 +623,7 @@ impl AudioState {
         if let (Some(tb), Some(n_frames)) = (tb, n_frames) {
             let duration_secs = tb.calc_time(n_frames);
             let dur = duration_secs.seconds as f32 + duration_secs.frac as f32;
+            session.update(|s| s.duration_secs = dur);
             if let Ok(mut st) = status.lock() {
                 st.duration_secs = dur;
                 st.metadata = Some(AudioMetadata {
@@ -585,6 +672,7 @@ impl AudioState {
                 volume,
                 playing: playing.clone(),
                 stream_ctx: stream_ctx.clone(),
+                diagnostics: diagnostics.clone(),
             })
             .open_stream()
         {
@@ -593,6 +681,13 @@ impl AudioState {
                     eprintln!("[AudioPlayer] Failed to start Oboe stream: {}", e);
                 }
 
+                diagnostics.set_output(OutputInfo {
+                    sample_rate: s.get_sample_rate(),
+                    buffer_size_frames: s.get_buffer_size_in_frames(),
+                    frames_per_burst: s.get_frames_per_burst(),
+                    performance_mode: format!("{:?}", s.get_performance_mode()),
+                });
+
                 let mut st = status.lock().unwrap();
                 st.sample_rate = sample_rate_file;
 
@@ -618,6 +713,11 @@ impl AudioState {
 
         // ---- Decode loop ----
         let mut last_progress_emit = std::time::Instant::now();
+        let mut last_diagnostics_log = std::time::Instant::now();
+        let mut stereo_buf: Vec<f32> = Vec::new();
+        // Twice the time the callback takes to play a full ring
+        let stall_timeout =
+            Duration::from_secs_f64(RING_BUFFER_SAMPLES as f64 / sample_rate_file.max(1) as f64);
 
         loop {
             if stop_flag.load(Ordering::Relaxed) {
@@ -635,10 +735,24 @@ impl AudioState {
                             duration: st.duration_secs,
                         },
                     );
+                    if st.is_playing {
+                        session.update(|s| s.position_secs = pos);
+                    }
+                }
+                if playing.load(Ordering::Relaxed) {
+                    session.save_throttled();
                 }
                 last_progress_emit = std::time::Instant::now();
             }
 
+            // Periodic diagnostics dump, only when enabled via set_audio_diagnostics_logging
+            if let Some(interval) = diagnostics.log_interval() {
+                if last_diagnostics_log.elapsed() >= interval {
+                    diagnostics.log_snapshot();
+                    last_diagnostics_log = std::time::Instant::now();
+                }
+            }
+
             // Check for seek request
             let seek_target = {
                 let mut st = status.lock().unwrap();
@@ -693,10 +807,12 @@ impl AudioState {
             }
 
             // Decode packet
+            let decode_start = std::time::Instant::now();
             let decoded = match decoder.decode(&packet) {
                 Ok(d) => d,
                 Err(symphonia::core::errors::Error::DecodeError(msg)) => {
                     eprintln!("[Decode] Decode error (skipping): {}", msg);
+                    diagnostics.record_skipped_decode_error();
                     continue;
                 }
                 Err(e) => {
@@ -704,65 +820,44 @@ impl AudioState {
                     break;
                 }
             };
-
-            // Convert to interleaved f32
-            let spec = *decoded.spec();
-            let num_frames = decoded.frames();
-            let num_channels = spec.channels.count();
-
-            let mut sample_buf = SampleBuffer::<f32>::new(num_frames as u64, spec);
-            sample_buf.copy_interleaved_ref(decoded);
-            let samples = sample_buf.samples();
-
-            // Write to ring buffer (stereo interleaved)
-            // If source is mono, duplicate to stereo
-            // If source is stereo, write as-is
-            // If source has more channels, take first two
-            let mut i = 0;
-            while i < samples.len() {
-                if stop_flag.load(Ordering::Relaxed) {
-                    return;
-                }
-
-                let (l, r) = if num_channels == 1 {
-                    let s = samples[i];
-                    i += 1;
-                    (s, s)
-                } else {
-                    let l = samples[i];
-                    let r = if i + 1 < samples.len() {
-                        samples[i + 1]
-                    } else {
-                        l
-                    };
-                    i += num_channels;
-                    (l, r)
-                };
-
+            diagnostics.record_decode(decode_start.elapsed());
+
+            // Convert to interleaved stereo f32 (see audio_dsp::downmix_to_stereo)
+            stereo_buf.clear();
+            audio_dsp::append_stereo(decoded, &mut stereo_buf);
+
+            // Write to ring buffer (stereo interleaved). A full ring is normal backpressure;
+            // only if the callback stops draining it while playing (e.g. the output device
+            // went away) is the rest of the packet dropped and counted as an overrun.
+            let mut dropped = 0;
+            let mut waited = Duration::ZERO;
+            'write: for (i, &sample) in stereo_buf.iter().enumerate() {
                 // Try to push to ring buffer; if full, spin-wait briefly
                 loop {
                     if stop_flag.load(Ordering::Relaxed) {
                         return;
                     }
-                    if producer.try_push(l).is_ok() {
+                    if producer.try_push(sample).is_ok() {
+                        waited = Duration::ZERO;
                         break;
                     }
-                    thread::sleep(Duration::from_micros(500));
-                }
-                loop {
-                    if stop_flag.load(Ordering::Relaxed) {
-                        return;
-                    }
-                    if producer.try_push(r).is_ok() {
-                        break;
+                    if !playing.load(Ordering::Relaxed) {
+                        // Paused: the callback isn't supposed to consume
+                        waited = Duration::ZERO;
+                    } else if waited >= stall_timeout {
+                        dropped = stereo_buf.len() - i;
+                        break 'write;
                     }
-                    thread::sleep(Duration::from_micros(500));
+                    thread::sleep(RING_WAIT);
+                    waited += RING_WAIT;
                 }
             }
+            diagnostics.record_overrun(dropped);
         }
 
         // Playback finished naturally — wait for ring buffer to drain
         // then signal end
+        diagnostics.set_draining();
         let mut drain_wait = 0;
         while !producer.is_empty() && drain_wait < 200 {
             if stop_flag.load(Ordering::Relaxed) {
@@ -788,7 +883,7 @@ impl AudioState {
     }
 
     /// Prepare a stream (starts download if HTTP) without starting decoding.
-    fn prepare_stream(url: &str) -> Option<(MediaSourceStream, Hint)> {
+    pub(crate) fn prepare_stream(url: &str) -> Option<PreparedStream> {
         let mut hint = Hint::new();
         let ext = url.rsplit('.').next().unwrap_or("").to_lowercase();
         let ext_clean = ext.split('?').next().unwrap_or(&ext);
@@ -807,6 +902,8 @@ impl AudioState {
             let shared_clone = shared.clone();
             let url_string = url.to_string();
             let mut content_length = None;
+            let http_stats = Arc::new(DownloadStats::new());
+            let http_stats_clone = http_stats.clone();
 
             if let Ok(resp) = reqwest::blocking::Client::builder()
                 .timeout(Duration::from_secs(30))
@@ -825,6 +922,7 @@ impl AudioState {
                     loop {
                         match r.read(&mut chunk) {
                             Ok(0) => {
+                                http_stats_clone.finish();
                                 let (lock, cvar) = &*shared_clone;
                                 let mut state = lock.lock().unwrap();
                                 state.is_eof = true;
@@ -832,12 +930,14 @@ impl AudioState {
                                 break;
                             }
                             Ok(n) => {
+                                http_stats_clone.add_bytes(n);
                                 let (lock, cvar) = &*shared_clone;
                                 let mut state = lock.lock().unwrap();
                                 state.buffer.extend_from_slice(&chunk[0..n]);
                                 cvar.notify_all();
                             }
                             Err(_) => {
+                                http_stats_clone.finish();
                                 let (lock, cvar) = &*shared_clone;
                                 let mut state = lock.lock().unwrap();
                                 state.has_error = true;
@@ -856,11 +956,13 @@ impl AudioState {
                 shared,
                 pos: 0,
                 content_length,
+                stats: http_stats.clone(),
             };
 
             Some((
                 MediaSourceStream::new(Box::new(stream), Default::default()),
                 hint,
+                Some(http_stats),
             ))
         } else {
             // Local file
@@ -870,6 +972,7 @@ impl AudioState {
                     Some((
                         MediaSourceStream::new(Box::new(cursor), Default::default()),
                         hint,
+                        None,
                     ))
                 }
                 Err(e) => {
@@ -886,12 +989,14 @@ pub fn play_audio(
     state: State<AudioState>,
     url: String,
     paused: Option<bool>,
+    seek: Option<f32>,
 ) -> Result<(), String> {
+    let start_at = seek.filter(|&t| t > 0.0);
     state
         .command_tx
         .lock()
         .unwrap()
-        .send(AudioCommand::Play(url, paused.unwrap_or(false)))
+        .send(AudioCommand::Play(url, paused.unwrap_or(false), start_at))
         .map_err(|e| e.to_string())
 }
 
@@ -979,3 +1084,40 @@ pub fn get_metadata(state: State<AudioState>) -> Result<Option<AudioMetadata>, S
     let st = state.status.lock().map_err(|e| e.to_string())?;
     Ok(st.metadata.clone())
 }
+
+#[tauri::command]
+pub fn get_audio_diagnostics(state: State<AudioState>) -> AudioDiagnosticsSnapshot {
+    state.diagnostics.snapshot()
+}
+
+/// Enables periodic diagnostics logging every `interval_secs` seconds; 0 disables it.
+#[tauri::command]
+pub fn set_audio_diagnostics_logging(state: State<AudioState>, interval_secs: u32) {
+    state.diagnostics.set_log_interval(interval_secs);
+}
+
+/// Stores frontend-owned queue data alongside the session so it can be restored.
+#[tauri::command]
+pub fn set_session_queue(state: State<AudioState>, queue: serde_json::Value) {
+    state.session.update(|s| s.queue = Some(queue));
+    state.session.save();
+}
+
+/// Reloads the last persisted track, paused at its saved position, and returns the
+/// snapshot (including the queue). The usual `audioplayer://metadata` event follows.
+#[tauri::command]
+pub fn restore_session(state: State<AudioState>) -> Result<Option<SessionSnapshot>, String> {
+    let snapshot = state.session.snapshot();
+    let Some(url) = snapshot.url.clone() else {
+        return Ok(None);
+    };
+
+    let tx = state.command_tx.lock().unwrap();
+    tx.send(AudioCommand::SetVolume(snapshot.volume.clamp(0.0, 1.0)))
+        .map_err(|e| e.to_string())?;
+    // The seek travels with Play, so the saved position is never replaced by 0 on disk
+    let start_at = (snapshot.position_secs > 0.0).then_some(snapshot.position_secs);
+    tx.send(AudioCommand::Play(url, true, start_at))
+        .map_err(|e| e.to_string())?;
+    Ok(Some(snapshot))
+}
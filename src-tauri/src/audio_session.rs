@@ -0,0 +1,175 @@
+// src-tauri/src/audio_session.rs
+
+//! Persists the player session (track, position, volume, queue) to the app data dir,
+//! so it survives Android killing the process. `PlaybackStatus` stays the in-memory
+//! source of truth; this is only a small JSON snapshot written at safe points.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+const SESSION_FILE: &str = "audio_session.json";
+
+/// How often the decode thread re-saves the position while playing
+pub const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionSnapshot {
+    pub url: Option<String>,
+    pub position_secs: f32,
+    pub duration_secs: f32,
+    pub volume: f32,
+    /// Opaque queue data owned by the frontend, stored as-is
+    pub queue: Option<serde_json::Value>,
+    /// Unix time in milliseconds
+    pub saved_at: u64,
+}
+
+impl Default for SessionSnapshot {
+    fn default() -> Self {
+        Self {
+            url: None,
+            position_secs: 0.0,
+            duration_secs: 0.0,
+            volume: 1.0,
+            queue: None,
+            saved_at: 0,
+        }
+    }
+}
+
+pub struct SessionStore {
+    /// `None` when the app data dir is unavailable; persistence is then a no-op
+    path: Option<PathBuf>,
+    current: Mutex<SessionSnapshot>,
+    last_save: Mutex<Option<Instant>>,
+}
+
+impl SessionStore {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        let path = dir.map(|d| d.join(SESSION_FILE));
+        let current = path
+            .as_ref()
+            .and_then(|p| Self::read(p))
+            .unwrap_or_default();
+        Self {
+            path,
+            current: Mutex::new(current),
+            last_save: Mutex::new(None),
+        }
+    }
+
+    fn read(path: &Path) -> Option<SessionSnapshot> {
+        let data = fs::read_to_string(path).ok()?;
+        match serde_json::from_str(&data) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                eprintln!("[AudioSession] Ignoring corrupt session file: {}", e);
+                None
+            }
+        }
+    }
+
+    /// The snapshot as loaded from disk at startup plus any later updates.
+    pub fn snapshot(&self) -> SessionSnapshot {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// Updates the in-memory snapshot without writing it.
+    pub fn update(&self, f: impl FnOnce(&mut SessionSnapshot)) {
+        f(&mut self.current.lock().unwrap());
+    }
+
+    /// Writes the snapshot to disk (write to a temp file, then rename).
+    pub fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+
+        let json = {
+            let mut current = self.current.lock().unwrap();
+            current.saved_at = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+            match serde_json::to_string(&*current) {
+                Ok(j) => j,
+                Err(e) => {
+                    eprintln!("[AudioSession] Failed to serialize session: {}", e);
+                    return;
+                }
+            }
+        };
+
+        let result = (|| -> std::io::Result<()> {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let tmp = path.with_extension("json.tmp");
+            fs::write(&tmp, json)?;
+            fs::rename(&tmp, path)
+        })();
+        if let Err(e) = result {
+            eprintln!("[AudioSession] Failed to save session: {}", e);
+        }
+        *self.last_save.lock().unwrap() = Some(Instant::now());
+    }
+
+    /// Saves only if `SESSION_SAVE_INTERVAL` has passed since the last write.
+    pub fn save_throttled(&self) {
+        let due = match *self.last_save.lock().unwrap() {
+            Some(t) => t.elapsed() >= SESSION_SAVE_INTERVAL,
+            None => true,
+        };
+        if due {
+            self.save();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_round_trips_through_disk() {
+        let dir = std::env::temp_dir().join(format!("splayer-session-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let store = SessionStore::new(Some(dir.clone()));
+        assert!(store.snapshot().url.is_none());
+        store.update(|s| {
+            s.url = Some("/music/a.flac".to_string());
+            s.position_secs = 42.5;
+            s.volume = 0.3;
+            s.queue = Some(serde_json::json!([1, 2, 3]));
+        });
+        store.save();
+
+        let restored = SessionStore::new(Some(dir.clone())).snapshot();
+        assert_eq!(restored.url.as_deref(), Some("/music/a.flac"));
+        assert_eq!(restored.position_secs, 42.5);
+        assert_eq!(restored.volume, 0.3);
+        assert_eq!(restored.queue, Some(serde_json::json!([1, 2, 3])));
+        assert!(restored.saved_at > 0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupt_file_falls_back_to_default() {
+        let dir = std::env::temp_dir().join(format!("splayer-session-bad-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(SESSION_FILE), "{ not json").unwrap();
+
+        let store = SessionStore::new(Some(dir.clone()));
+        assert!(store.snapshot().url.is_none());
+        assert_eq!(store.snapshot().volume, 1.0);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -2,7 +2,12 @@
 
 // Declare modules
 mod android_fs;
+mod audio_diagnostics;
+mod audio_dsp;
+mod audio_export;
 mod audio_player;
+mod audio_session;
+mod lyric;
 
 use audio_player::AudioState;
 
@@ -40,11 +45,24 @@ fn update_playback_state(
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
+        .plugin(tauri_plugin_log::Builder::new().build())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_dialog::init())
         .invoke_handler(tauri::generate_handler![
             android_fs::read_lyric_dir_android,
             android_fs::read_lyric_file_android,
+            lyric::commands::parse_lyric_file,
+            lyric::commands::read_lyric_text,
+            lyric::commands::convert_lyric,
+            lyric::commands::merge_lyric_tracks,
+            lyric::commands::convert_lyric_tracks,
+            lyric::commands::retime_lyric,
+            lyric::commands::retime_lyric_file,
+            lyric::commands::read_embedded_lyrics,
+            lyric::commands::resolve_lyrics,
+            lyric::commands::build_lyric_index,
+            lyric::commands::match_lyric,
+            lyric::commands::search_lyrics,
             // Audio playback commands
             audio_player::play_audio,
             audio_player::preload_audio,
@@ -57,6 +75,11 @@ pub fn run() {
             audio_player::get_duration,
             audio_player::get_playback_state,
             audio_player::get_metadata,
+            audio_player::get_audio_diagnostics,
+            audio_player::set_audio_diagnostics_logging,
+            audio_player::set_session_queue,
+            audio_player::restore_session,
+            audio_export::export_processed_audio,
             // Native media commands
             update_metadata,
             update_playback_state,
@@ -64,6 +87,8 @@ pub fn run() {
         .setup(|app| {
             use tauri::Manager;
             app.manage(AudioState::new(app.handle().clone()));
+            app.manage(lyric::commands::LyricIndexState::default());
+            app.manage(lyric::commands::LyricSearchState::default());
             Ok(())
         })
         .run(tauri::generate_context!())
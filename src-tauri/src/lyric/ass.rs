@@ -0,0 +1,311 @@
+// src-tauri/src/lyric/ass.rs
+
+//! Advanced SubStation Alpha karaoke (`.ass`) with `{\k}` word timing.
+//!
+//! Word durations are in centiseconds and run back to back from the dialogue
+//! start; an empty `{\kN}` segment encodes a gap. Translation and romanization
+//! are separate dialogues in the `Translation` / `Roman` styles. The `Name`
+//! field carries `bg` / `duet` flags.
+
+use super::convert::ConvertOptions;
+use super::{Lyric, LyricLine, LyricWord};
+
+const TRANSLATION_STYLE: &str = "Translation";
+const ROMAN_STYLE: &str = "Roman";
+
+/// Parses `h:mm:ss.cc`.
+fn parse_ass_time(s: &str) -> Option<u64> {
+    let mut parts = s.trim().split(':');
+    let h: u64 = parts.next()?.parse().ok()?;
+    let m: u64 = parts.next()?.parse().ok()?;
+    let (sec, cs) = parts.next()?.split_once('.')?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let sec: u64 = sec.parse().ok()?;
+    let frac: String = cs.chars().chain("00".chars()).take(2).collect();
+    let cs: u64 = frac.parse().ok()?;
+    h.checked_mul(3_600_000)?
+        .checked_add(m.checked_mul(60_000)?)?
+        .checked_add(sec.checked_mul(1000)?)?
+        .checked_add(cs * 10)
+}
+
+fn format_ass_time(cs: u64) -> String {
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        cs / 360_000,
+        cs / 6000 % 60,
+        cs / 100 % 60,
+        cs % 100
+    )
+}
+
+/// ms → centiseconds, rounded to nearest.
+fn to_cs(ms: u64) -> u64 {
+    ms.saturating_add(5) / 10
+}
+
+/// Splits dialogue text into (duration in cs, text) karaoke segments.
+/// Returns `None` when there is no `\k` tag; other override tags are dropped.
+fn parse_karaoke(text: &str) -> Option<Vec<(u64, String)>> {
+    let mut segments: Vec<(u64, String)> = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+    let mut found = false;
+
+    while !rest.is_empty() {
+        if let Some(block) = rest.strip_prefix('{') {
+            let Some(close) = block.find('}') else {
+                plain.push_str(rest);
+                break;
+            };
+            for tag in block[..close].split('\\').skip(1) {
+                let digits = tag
+                    .strip_prefix("kf")
+                    .or_else(|| tag.strip_prefix("ko"))
+                    .or_else(|| tag.strip_prefix('K'))
+                    .or_else(|| tag.strip_prefix('k'));
+                if let Some(cs) = digits.and_then(|d| d.trim().parse::<u64>().ok()) {
+                    found = true;
+                    segments.push((cs, String::new()));
+                }
+            }
+            rest = &block[close + 1..];
+            continue;
+        }
+
+        let next = rest.find('{').unwrap_or(rest.len());
+        let chunk = rest[..next]
+            .replace("\\N", " ")
+            .replace("\\n", " ")
+            .replace("\\h", " ");
+        match segments.last_mut() {
+            Some((_, text)) => text.push_str(&chunk),
+            None => plain.push_str(&chunk),
+        }
+        rest = &rest[next..];
+    }
+
+    if !found {
+        return None;
+    }
+    if !plain.trim().is_empty() {
+        // Text before the first `\k` belongs to the first timed segment
+        segments[0].1.insert_str(0, &plain);
+    }
+    Some(segments)
+}
+
+pub fn parse_ass(content: &str) -> Lyric {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut in_events = false;
+    let mut fields: Vec<String> = Vec::new();
+    let mut lines: Vec<LyricLine> = Vec::new();
+    let mut extras: Vec<(u64, bool, String)> = Vec::new();
+
+    for row in content.lines() {
+        let row = row.trim();
+        if row.starts_with('[') {
+            in_events = row.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+        if let Some(format) = row.strip_prefix("Format:") {
+            fields = format
+                .split(',')
+                .map(|f| f.trim().to_ascii_lowercase())
+                .collect();
+            continue;
+        }
+        let Some(values) = row.strip_prefix("Dialogue:") else {
+            continue;
+        };
+        if fields.is_empty() {
+            continue;
+        }
+
+        // Text is the last field and may itself contain commas
+        let values: Vec<&str> = values.trim_start().splitn(fields.len(), ',').collect();
+        let get = |name: &str| {
+            fields
+                .iter()
+                .position(|f| f == name)
+                .and_then(|i| values.get(i).copied())
+                .unwrap_or("")
+        };
+        let (Some(start), Some(end)) = (parse_ass_time(get("start")), parse_ass_time(get("end")))
+        else {
+            continue;
+        };
+        let style = get("style").trim();
+        let name = get("name").trim();
+        let text = get("text");
+
+        if style.eq_ignore_ascii_case(TRANSLATION_STYLE) || style.eq_ignore_ascii_case(ROMAN_STYLE)
+        {
+            let plain = parse_karaoke(text)
+                .map(|segs| segs.into_iter().map(|(_, t)| t).collect())
+                .unwrap_or_else(|| strip_overrides(text));
+            extras.push((
+                start,
+                style.eq_ignore_ascii_case(TRANSLATION_STYLE),
+                plain.trim().to_string(),
+            ));
+            continue;
+        }
+
+        let mut line = match parse_karaoke(text) {
+            Some(segments) => {
+                let mut words = Vec::new();
+                let mut pos = to_cs(start);
+                for (cs, word) in segments {
+                    if !word.is_empty() {
+                        words.push(LyricWord {
+                            start_time: pos * 10,
+                            end_time: (pos + cs) * 10,
+                            word,
+                            roman_word: String::new(),
+                        });
+                    }
+                    pos += cs;
+                }
+                if words.is_empty() {
+                    continue;
+                }
+                LyricLine {
+                    words,
+                    start_time: start,
+                    end_time: end.max(start),
+                    ..Default::default()
+                }
+            }
+            None => {
+                let text = strip_overrides(text);
+                if text.trim().is_empty() {
+                    continue;
+                }
+                LyricLine::plain(start, end.max(start), text.trim())
+            }
+        };
+        line.is_bg = name.contains("bg");
+        line.is_duet = name.contains("duet");
+        lines.push(line);
+    }
+
+    for (start, is_translation, text) in extras {
+        if let Some(line) = lines.iter_mut().find(|l| l.start_time == start) {
+            if is_translation {
+                line.translated_lyric = text;
+            } else {
+                line.roman_lyric = text;
+            }
+        }
+    }
+
+    lines.sort_by_key(|l| l.start_time);
+    Lyric {
+        word_timed: !lines.is_empty() && lines.iter().any(|l| l.words.len() > 1),
+        lines,
+        ..Default::default()
+    }
+}
+
+fn strip_overrides(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        match rest[open..].find('}') {
+            Some(close) => rest = &rest[open + close + 1..],
+            None => {
+                rest = &rest[open..];
+                break;
+            }
+        }
+    }
+    out.push_str(rest);
+    out.replace("\\N", " ")
+        .replace("\\n", " ")
+        .replace("\\h", " ")
+}
+
+/// Override blocks can't be escaped in ASS, so braces become full-width.
+fn escape(text: &str) -> String {
+    text.replace('{', "｛")
+        .replace('}', "｝")
+        .replace('\n', "\\N")
+}
+
+pub fn write_ass(lyric: &Lyric, options: &ConvertOptions) -> String {
+    let title = lyric.metadata.title.as_deref().unwrap_or("");
+    let mut out = format!(
+        "[Script Info]\n\
+         ScriptType: v4.00+\n\
+         Title: {}\n\
+         PlayResX: 1920\n\
+         PlayResY: 1080\n\
+         \n\
+         [V4+ Styles]\n\
+         Format: Name, Fontname, Fontsize, PrimaryColour, SecondaryColour, OutlineColour, BackColour, Bold, Italic, Underline, StrikeOut, ScaleX, ScaleY, Spacing, Angle, BorderStyle, Outline, Shadow, Alignment, MarginL, MarginR, MarginV, Encoding\n\
+         Style: Default,Arial,64,&H00FFFFFF,&H00808080,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,40,40,120,1\n\
+         Style: {},Arial,44,&H00FFFFFF,&H00FFFFFF,&H00000000,&H00000000,0,0,0,0,100,100,0,0,1,2,0,2,40,40,60,1\n\
+         Style: {},Arial,40,&H00D0D0D0,&H00D0D0D0,&H00000000,&H00000000,0,1,0,0,100,100,0,0,1,2,0,2,40,40,20,1\n\
+         \n\
+         [Events]\n\
+         Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n",
+        title, TRANSLATION_STYLE, ROMAN_STYLE
+    );
+
+    for line in &lyric.lines {
+        let start = to_cs(line.start_time);
+        let end = to_cs(line.end_time).max(start);
+        let name = match (line.is_bg, line.is_duet) {
+            (true, true) => "bg-duet",
+            (true, false) => "bg",
+            (false, true) => "duet",
+            (false, false) => "",
+        };
+
+        let mut text = String::new();
+        let mut pos = start;
+        for w in &line.words {
+            let ws = to_cs(w.start_time).max(pos);
+            if ws > pos {
+                text.push_str(&format!("{{\\k{}}}", ws - pos));
+            }
+            let we = to_cs(w.end_time).max(ws);
+            text.push_str(&format!("{{\\k{}}}{}", we - ws, escape(&w.word)));
+            pos = we;
+        }
+
+        out.push_str(&format!(
+            "Dialogue: 0,{},{},Default,{},0,0,0,karaoke,{}\n",
+            format_ass_time(start),
+            format_ass_time(end),
+            name,
+            text
+        ));
+        for (enabled, style, extra) in [
+            (
+                options.include_translation,
+                TRANSLATION_STYLE,
+                &line.translated_lyric,
+            ),
+            (options.include_roman, ROMAN_STYLE, &line.roman_lyric),
+        ] {
+            if enabled && !extra.is_empty() {
+                out.push_str(&format!(
+                    "Dialogue: 0,{},{},{},,0,0,0,,{}\n",
+                    format_ass_time(start),
+                    format_ass_time(end),
+                    style,
+                    escape(extra)
+                ));
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,195 @@
+// src-tauri/src/lyric/commands.rs
+
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use tauri::{command, AppHandle, Manager, Runtime, State};
+
+use super::convert::{self, ConvertOptions, ConvertedLyric, LyricFormat, SecondaryTracks};
+use super::embedded::{self, EmbeddedLyrics, LyricOrigin, ResolvedLyrics};
+use super::encoding::{self, DecodedText};
+use super::matcher::{LyricIndex, LyricMatch, TrackQuery};
+use super::merge::MergeResult;
+use super::retime::{self, Retime};
+use super::scanner::{self, ScanCache, ScanResult};
+use super::search::{self, LazySearchIndex, SearchHit};
+use super::uri;
+use super::Lyric;
+
+/// Lyric folder index used by `match_lyric`, rebuilt by `build_lyric_index`
+#[derive(Default)]
+pub struct LyricIndexState(pub Mutex<LyricIndex>);
+
+/// Full-text index used by `search_lyrics`; `build_lyric_index` replaces its
+/// files, which are read and indexed on the next search
+#[derive(Default)]
+pub struct LyricSearchState(pub Mutex<LazySearchIndex>);
+
+/// Serializes scans so concurrent callers don't race on the cache file
+static SCAN_LOCK: Mutex<()> = Mutex::new(());
+
+const SCAN_CACHE_FILE: &str = "lyric_scan_cache.json";
+
+/// Scans each of `dirs` through the on-disk metadata cache in the app data dir.
+/// Unchanged files are taken from the cache instead of being parsed again.
+pub fn scan_dirs_cached<R: Runtime>(app: &AppHandle<R>, dirs: &[PathBuf]) -> ScanResult {
+    let _guard = SCAN_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let cache_path = app
+        .path()
+        .app_data_dir()
+        .ok()
+        .map(|dir| dir.join(SCAN_CACHE_FILE));
+    let mut cache = cache_path
+        .as_deref()
+        .map(ScanCache::load)
+        .unwrap_or_default();
+
+    let mut result = ScanResult::default();
+    for dir in dirs {
+        let scanned = scanner::scan_dir(dir, &mut cache, scanner::DEFAULT_MAX_DEPTH);
+        result.files.extend(scanned.files);
+        result.errors.extend(scanned.errors);
+        result.reused += scanned.reused;
+        result.parsed += scanned.parsed;
+    }
+
+    if let Some(path) = cache_path {
+        if let Err(e) = cache.save(&path) {
+            log::warn!("Failed to save lyric scan cache: {}", e);
+        }
+    }
+    result
+}
+
+/// Reads a lyric file and returns it parsed into structured lines.
+/// The format is chosen by extension (`.ttml`, `.krc`, `.qrc`, `.yrc`, otherwise LRC).
+#[command]
+pub fn parse_lyric_file(uri: String) -> Result<Lyric, String> {
+    super::read_lyric(&uri::to_path(&uri)?)
+}
+
+/// Reads a lyric file in whatever encoding it was saved in and returns the text
+/// with the detected encoding. With `rewrite_utf8`, non-UTF-8 files (or UTF-8 with
+/// a BOM) are rewritten in place as plain UTF-8.
+#[command]
+pub fn read_lyric_text(uri: String, rewrite_utf8: Option<bool>) -> Result<DecodedText, String> {
+    let path = uri::to_path(&uri)?;
+    let decoded = encoding::read_text(&path).map_err(|e| e.to_string())?;
+    if rewrite_utf8.unwrap_or(false) && !decoded.is_utf8() && !decoded.lossy {
+        encoding::rewrite_utf8(&path, &decoded.text).map_err(|e| e.to_string())?;
+    }
+    Ok(decoded)
+}
+
+/// Converts lyric text between formats (`lrc`, `enhanced_lrc`, `ttml`, `srt`, `ass`;
+/// `krc`, `qrc` and `yrc` as input only).
+#[command]
+pub fn convert_lyric(
+    input: String,
+    from: LyricFormat,
+    to: LyricFormat,
+    options: Option<ConvertOptions>,
+) -> Result<String, String> {
+    convert::convert_lyric(&input, from, to, &options.unwrap_or_default())
+}
+
+/// Merges separately delivered translation / romanization tracks into the main
+/// lyric by timestamp. All inputs share `format` (LRC by default).
+#[command]
+pub fn merge_lyric_tracks(
+    input: String,
+    format: Option<LyricFormat>,
+    tracks: SecondaryTracks,
+) -> Result<MergeResult, String> {
+    convert::parse_with_tracks(&input, format.unwrap_or(LyricFormat::Lrc), &tracks)
+}
+
+/// `convert_lyric` with translation / romanization tracks merged in first;
+/// lines that couldn't be placed are returned alongside the output.
+#[command]
+pub fn convert_lyric_tracks(
+    input: String,
+    from: LyricFormat,
+    tracks: SecondaryTracks,
+    to: LyricFormat,
+    options: Option<ConvertOptions>,
+) -> Result<ConvertedLyric, String> {
+    convert::convert_with_tracks(&input, from, &tracks, to, &options.unwrap_or_default())
+}
+
+/// Retimes lyric text (shift, stretch or two-point re-anchor) and returns it in
+/// the same format.
+#[command]
+pub fn retime_lyric(input: String, format: LyricFormat, op: Retime) -> Result<String, String> {
+    retime::retime_text(&input, format, &op)
+}
+
+/// Retimes an LRC or TTML file in place and returns the retimed lines.
+#[command]
+pub fn retime_lyric_file(uri: String, op: Retime) -> Result<Lyric, String> {
+    retime::retime_file(&uri::to_path(&uri)?, &op)
+}
+
+/// Lyrics embedded in an audio file's tags (`USLT`/`SYLT`, Vorbis `LYRICS`, MP4 `©lyr`).
+#[command(async)]
+pub fn read_embedded_lyrics(uri: String) -> Result<EmbeddedLyrics, String> {
+    embedded::read_embedded_lyrics(&uri::to_path(&uri)?)
+}
+
+/// Picks between sidecar and embedded lyrics for an audio file. Timed lyrics win
+/// over plain text; otherwise `preference` (default: sidecar, then embedded) decides.
+#[command(async)]
+pub fn resolve_lyrics(
+    uri: String,
+    preference: Option<Vec<LyricOrigin>>,
+) -> Result<Option<ResolvedLyrics>, String> {
+    let preference = preference.unwrap_or_else(|| embedded::DEFAULT_PREFERENCE.to_vec());
+    Ok(embedded::resolve_lyrics(&uri::to_path(&uri)?, &preference))
+}
+
+/// Indexes every lyric file under `dirs` for `match_lyric` and `search_lyrics`,
+/// replacing the previous indexes. Returns the number of indexed files.
+#[command(async)]
+pub fn build_lyric_index<R: Runtime>(
+    app: AppHandle<R>,
+    dirs: Vec<String>,
+    state: State<'_, LyricIndexState>,
+    search_state: State<'_, LyricSearchState>,
+) -> Result<usize, String> {
+    let dirs = dirs
+        .iter()
+        .map(|d| uri::to_path(d))
+        .collect::<Result<Vec<_>, _>>()?;
+    let scanned = scan_dirs_cached(&app, &dirs);
+    let index = LyricIndex::from_files(&scanned.files);
+    let count = index.entries.len();
+    *search_state.0.lock().unwrap() = LazySearchIndex::new(scanned.files);
+    *state.0.lock().unwrap() = index;
+    Ok(count)
+}
+
+/// Ranked lyric candidates for a track from the current index, best first.
+#[command]
+pub fn match_lyric(
+    track: TrackQuery,
+    state: State<'_, LyricIndexState>,
+) -> Result<Vec<LyricMatch>, String> {
+    Ok(state.0.lock().unwrap().find(&track))
+}
+
+/// Local lyric files containing `query` (simplified and traditional Chinese are
+/// treated alike), with the matched line and its start time, best matches first.
+/// The first search after `build_lyric_index` reads the indexed files.
+#[command(async)]
+pub fn search_lyrics(
+    query: String,
+    limit: Option<usize>,
+    state: State<'_, LyricSearchState>,
+) -> Result<Vec<SearchHit>, String> {
+    Ok(state
+        .0
+        .lock()
+        .unwrap()
+        .get()
+        .search(&query, limit.unwrap_or(search::DEFAULT_LIMIT)))
+}
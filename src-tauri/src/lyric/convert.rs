@@ -0,0 +1,396 @@
+// src-tauri/src/lyric/convert.rs
+
+//! Conversion between lyric formats through the shared `Lyric` model.
+
+use serde::{Deserialize, Serialize};
+
+use super::merge::{self, MergeResult, UnmatchedLine};
+use super::{ass, krc, lrc, qrc, srt, ttml, yrc, Lyric};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LyricFormat {
+    Lrc,
+    /// LRC with `<mm:ss.xx>` word timing
+    EnhancedLrc,
+    Ttml,
+    Srt,
+    /// ASS karaoke with `{\k}` word timing
+    Ass,
+    /// Decrypted KRC text (input only)
+    Krc,
+    /// QRC XML or bare text (input only)
+    Qrc,
+    /// NetEase YRC (input only)
+    Yrc,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConvertOptions {
+    pub include_translation: bool,
+    pub include_roman: bool,
+    /// Round every timestamp to a multiple of this many ms (0 = keep as is)
+    pub round_ms: u64,
+    /// `xml:lang` of TTML translations; defaults to the lyric's own
+    /// `translation_lang`, and is left out when neither is known
+    pub translation_lang: Option<String>,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            include_translation: true,
+            include_roman: false,
+            round_ms: 0,
+            translation_lang: None,
+        }
+    }
+}
+
+/// Translation / romanization delivered as separate documents (NetEase `tlyric`,
+/// `romalrc`), in the same format as the main input.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SecondaryTracks {
+    pub translation: Option<String>,
+    pub romanization: Option<String>,
+    /// Defaults to [`merge::DEFAULT_TOLERANCE_MS`]
+    pub tolerance_ms: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConvertedLyric {
+    pub output: String,
+    /// Secondary lines that couldn't be placed on a line of the main input
+    pub unmatched: Vec<UnmatchedLine>,
+}
+
+pub fn parse(input: &str, from: LyricFormat) -> Result<Lyric, String> {
+    Ok(match from {
+        LyricFormat::Lrc | LyricFormat::EnhancedLrc => lrc::parse_lrc(input),
+        LyricFormat::Ttml => ttml::parse_ttml(input)?,
+        LyricFormat::Srt => srt::parse_srt(input),
+        LyricFormat::Ass => ass::parse_ass(input),
+        LyricFormat::Krc => krc::parse_krc_text(input),
+        LyricFormat::Qrc => qrc::parse_qrc(input),
+        LyricFormat::Yrc => yrc::parse_yrc(input),
+    })
+}
+
+pub fn export(lyric: &Lyric, to: LyricFormat, options: &ConvertOptions) -> Result<String, String> {
+    let rounded;
+    let lyric = if options.round_ms > 1 {
+        rounded = round_times(lyric, options.round_ms);
+        &rounded
+    } else {
+        lyric
+    };
+
+    Ok(match to {
+        LyricFormat::Lrc => lrc::write_lrc(lyric, options, false),
+        LyricFormat::EnhancedLrc => lrc::write_lrc(lyric, options, true),
+        LyricFormat::Ttml => ttml::write_ttml(lyric, options),
+        LyricFormat::Srt => srt::write_srt(lyric, options),
+        LyricFormat::Ass => ass::write_ass(lyric, options),
+        LyricFormat::Krc | LyricFormat::Qrc | LyricFormat::Yrc => {
+            return Err(format!("Export to {:?} is not supported", to));
+        }
+    })
+}
+
+pub fn convert_lyric(
+    input: &str,
+    from: LyricFormat,
+    to: LyricFormat,
+    options: &ConvertOptions,
+) -> Result<String, String> {
+    export(&parse(input, from)?, to, options)
+}
+
+/// Parses `input` and merges its secondary tracks into it.
+pub fn parse_with_tracks(
+    input: &str,
+    from: LyricFormat,
+    tracks: &SecondaryTracks,
+) -> Result<MergeResult, String> {
+    let parse_track = |track: &Option<String>| {
+        track
+            .as_deref()
+            .filter(|t| !t.trim().is_empty())
+            .map(|t| parse(t, from))
+            .transpose()
+    };
+    let translation = parse_track(&tracks.translation)?;
+    let romanization = parse_track(&tracks.romanization)?;
+    Ok(merge::merge_tracks(
+        parse(input, from)?,
+        translation.as_ref(),
+        romanization.as_ref(),
+        tracks.tolerance_ms.unwrap_or(merge::DEFAULT_TOLERANCE_MS),
+    ))
+}
+
+/// Like [`convert_lyric`], with translation / romanization merged in first.
+pub fn convert_with_tracks(
+    input: &str,
+    from: LyricFormat,
+    tracks: &SecondaryTracks,
+    to: LyricFormat,
+    options: &ConvertOptions,
+) -> Result<ConvertedLyric, String> {
+    let merged = parse_with_tracks(input, from, tracks)?;
+    Ok(ConvertedLyric {
+        output: export(&merged.lyric, to, options)?,
+        unmatched: merged.unmatched,
+    })
+}
+
+fn round_times(lyric: &Lyric, step: u64) -> Lyric {
+    let round = |t: u64| t.saturating_add(step / 2) / step * step;
+    let mut lyric = lyric.clone();
+    for line in &mut lyric.lines {
+        line.start_time = round(line.start_time);
+        line.end_time = round(line.end_time);
+        for w in &mut line.words {
+            w.start_time = round(w.start_time);
+            w.end_time = round(w.end_time);
+        }
+    }
+    lyric
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lyric::{LyricLine, LyricWord};
+
+    const TOLERANCE_MS: u64 = 10;
+
+    fn word(start: u64, end: u64, text: &str) -> LyricWord {
+        LyricWord {
+            start_time: start,
+            end_time: end,
+            word: text.to_string(),
+            roman_word: String::new(),
+        }
+    }
+
+    /// Word-timed lines with odd millisecond values, a gap inside a line, gaps
+    /// between lines and two back-to-back lines.
+    fn sample() -> Lyric {
+        let line = |words: Vec<LyricWord>, translation: &str| LyricLine {
+            start_time: words[0].start_time,
+            end_time: words.last().unwrap().end_time,
+            words,
+            translated_lyric: translation.to_string(),
+            ..Default::default()
+        };
+        let mut lyric = Lyric {
+            lines: vec![
+                line(
+                    vec![
+                        word(1_234, 1_687, "Hello "),
+                        word(1_687, 2_301, "bright "),
+                        word(2_501, 3_333, "world"),
+                    ],
+                    "你好 明亮的世界",
+                ),
+                line(
+                    vec![word(4_005, 4_999, "晴"), word(4_999, 6_127, "天")],
+                    "Sunny day",
+                ),
+                line(
+                    vec![word(6_127, 7_003, "Back "), word(7_003, 8_888, "to back")],
+                    "",
+                ),
+                line(vec![word(65_432, 67_891, "Later")], "稍后"),
+            ],
+            word_timed: true,
+            ..Default::default()
+        };
+        lyric.metadata.title = Some("Round Trip".to_string());
+        lyric
+    }
+
+    fn assert_close(a: u64, b: u64, what: &str) {
+        assert!(a.abs_diff(b) <= TOLERANCE_MS, "{}: {} vs {}", what, a, b);
+    }
+
+    fn assert_round_trip(to: LyricFormat, word_level: bool, options: &ConvertOptions) -> Lyric {
+        let original = sample();
+        let exported = export(&original, to, options).unwrap();
+        let reparsed = parse(&exported, to).unwrap();
+
+        assert_eq!(
+            reparsed.lines.len(),
+            original.lines.len(),
+            "{:?}:\n{}",
+            to,
+            exported
+        );
+        for (a, b) in original.lines.iter().zip(&reparsed.lines) {
+            assert_eq!(a.text().trim(), b.text().trim(), "{:?}", to);
+            assert_close(a.start_time, b.start_time, "line start");
+            assert_close(a.end_time, b.end_time, "line end");
+            if word_level {
+                assert_eq!(a.words.len(), b.words.len(), "{:?}: {}", to, a.text());
+                for (wa, wb) in a.words.iter().zip(&b.words) {
+                    assert_close(wa.start_time, wb.start_time, "word start");
+                    assert_close(wa.end_time, wb.end_time, "word end");
+                }
+            }
+        }
+        reparsed
+    }
+
+    fn without_extras() -> ConvertOptions {
+        ConvertOptions {
+            include_translation: false,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lrc_round_trip() {
+        assert_round_trip(LyricFormat::Lrc, false, &without_extras());
+    }
+
+    #[test]
+    fn enhanced_lrc_round_trip() {
+        assert_round_trip(LyricFormat::EnhancedLrc, true, &without_extras());
+    }
+
+    #[test]
+    fn ttml_round_trip() {
+        let reparsed = assert_round_trip(LyricFormat::Ttml, true, &ConvertOptions::default());
+        assert_eq!(reparsed.lines[0].translated_lyric, "你好 明亮的世界");
+        assert_eq!(reparsed.metadata.title.as_deref(), Some("Round Trip"));
+    }
+
+    #[test]
+    fn srt_round_trip() {
+        let reparsed = assert_round_trip(LyricFormat::Srt, false, &ConvertOptions::default());
+        assert_eq!(reparsed.lines[1].translated_lyric, "Sunny day");
+        assert_eq!(reparsed.lines[2].translated_lyric, "");
+    }
+
+    #[test]
+    fn ass_round_trip() {
+        let reparsed = assert_round_trip(LyricFormat::Ass, true, &ConvertOptions::default());
+        assert_eq!(reparsed.lines[3].translated_lyric, "稍后");
+    }
+
+    #[test]
+    fn huge_timestamps_neither_overflow_nor_panic() {
+        // Out-of-range cue times are rejected rather than wrapped
+        let srt = "1\n99999999999999999:00:00,000 --> 99999999999999999:00:01,000\nx\n";
+        assert!(parse(srt, LyricFormat::Srt).unwrap().lines.is_empty());
+        let ass = "[Events]\nFormat: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+                   Dialogue: 0,99999999999999999:00:00.00,99999999999999999:00:01.00,Default,,0,0,0,,x\n";
+        assert!(parse(ass, LyricFormat::Ass).unwrap().lines.is_empty());
+
+        let mut lyric = sample();
+        lyric
+            .lines
+            .push(LyricLine::plain(u64::MAX - 1, u64::MAX, "end"));
+        let options = ConvertOptions {
+            round_ms: 10,
+            ..Default::default()
+        };
+        for to in [
+            LyricFormat::Lrc,
+            LyricFormat::EnhancedLrc,
+            LyricFormat::Ttml,
+            LyricFormat::Srt,
+            LyricFormat::Ass,
+        ] {
+            assert!(export(&lyric, to, &options).is_ok());
+        }
+    }
+
+    #[test]
+    fn lrc_writes_translations_as_duplicate_timestamps() {
+        let out = export(&sample(), LyricFormat::Lrc, &ConvertOptions::default()).unwrap();
+        assert!(out.contains("[00:01.23]Hello bright world\n[00:01.23]你好 明亮的世界\n"));
+    }
+
+    #[test]
+    fn background_and_duet_lines_survive_ttml_and_ass() {
+        let mut lyric = sample();
+        lyric.lines[1].is_duet = true;
+        let mut bg = LyricLine::plain(4_500, 5_500, "(ooh)");
+        // Background vocals belong to the singer of the line they accompany
+        bg.is_bg = true;
+        bg.is_duet = true;
+        lyric.lines.insert(2, bg);
+
+        for to in [LyricFormat::Ttml, LyricFormat::Ass] {
+            let out = export(&lyric, to, &ConvertOptions::default()).unwrap();
+            let reparsed = parse(&out, to).unwrap();
+            let flags: Vec<(bool, bool)> = reparsed
+                .lines
+                .iter()
+                .map(|l| (l.is_bg, l.is_duet))
+                .collect();
+            assert_eq!(
+                flags,
+                [
+                    (false, false),
+                    (false, true),
+                    (true, true),
+                    (false, false),
+                    (false, false)
+                ],
+                "{:?}",
+                to
+            );
+        }
+    }
+
+    #[test]
+    fn merges_secondary_tracks_before_export() {
+        let tracks = SecondaryTracks {
+            translation: Some("[00:01.10]一\n[00:09.00]孤立\n".to_string()),
+            romanization: Some("[00:02.00]ni\n".to_string()),
+            tolerance_ms: None,
+        };
+        let options = ConvertOptions {
+            include_roman: true,
+            ..Default::default()
+        };
+        let converted = convert_with_tracks(
+            "[00:01.00]one\n[00:02.00]two\n",
+            LyricFormat::Lrc,
+            &tracks,
+            LyricFormat::Srt,
+            &options,
+        )
+        .unwrap();
+        assert!(converted.output.contains("one\n一\n\n"));
+        assert!(converted.output.contains("two\n　\nni\n"));
+        assert_eq!(converted.unmatched.len(), 1);
+        assert_eq!(converted.unmatched[0].text, "孤立");
+    }
+
+    #[test]
+    fn rounding_and_unsupported_targets() {
+        let options = ConvertOptions {
+            round_ms: 100,
+            ..Default::default()
+        };
+        let out = export(&sample(), LyricFormat::Srt, &options).unwrap();
+        assert!(out.contains("00:00:01,200 --> 00:00:03,300"));
+        assert!(export(&sample(), LyricFormat::Krc, &options).is_err());
+
+        let converted = convert_lyric(
+            "[00:01.00]a\n[00:02.00]b\n",
+            LyricFormat::Lrc,
+            LyricFormat::Srt,
+            &options,
+        )
+        .unwrap();
+        assert!(converted.starts_with("1\n00:00:01,000 --> 00:00:02,000\na\n"));
+    }
+}
@@ -0,0 +1,726 @@
+// src-tauri/src/lyric/embedded.rs
+
+//! Lyrics embedded in audio file tags.
+//!
+//! ID3v2 `USLT` / `SYLT` and MP4 `©lyr` are read straight from the tag bytes:
+//! symphonia skips `SYLT`, and its MP4 demuxer isn't enabled in this build.
+//! Vorbis `LYRICS` / `UNSYNCEDLYRICS` comments (FLAC, Ogg) come through symphonia.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+
+use super::lrc::{self, LAST_LINE_FALLBACK_MS};
+use super::{Lyric, LyricLine, LyricWord};
+
+/// Upper bound for an MP4 `moov` box read into memory
+const MAX_MOOV_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Sidecar extensions tried next to an audio file, richest timing first
+const SIDECAR_ORDER: [&str; 5] = ["ttml", "yrc", "qrc", "krc", "lrc"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EmbeddedSource {
+    /// ID3v2 unsynchronized lyrics
+    Uslt,
+    /// ID3v2 synchronized lyrics
+    Sylt,
+    /// Vorbis comment (FLAC, Ogg) or another tag symphonia maps to lyrics
+    Vorbis,
+    /// MP4 `©lyr` atom
+    Mp4,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmbeddedLyrics {
+    /// Unsynchronized lyrics text
+    pub text: Option<String>,
+    /// Timed lines, from `SYLT` or from unsynchronized text that is itself LRC
+    pub lyric: Option<Lyric>,
+    /// Tags lyrics were found in
+    pub sources: Vec<EmbeddedSource>,
+}
+
+impl EmbeddedLyrics {
+    pub fn is_empty(&self) -> bool {
+        self.text.is_none() && self.lyric.is_none()
+    }
+
+    fn set_text(&mut self, text: String, source: EmbeddedSource) {
+        let text = text.trim();
+        if self.text.is_none() && !text.is_empty() {
+            self.text = Some(text.to_string());
+            self.sources.push(source);
+        }
+    }
+}
+
+/// Reads the lyrics embedded in an audio file's tags.
+pub fn read_embedded_lyrics(path: &Path) -> Result<EmbeddedLyrics, String> {
+    let mut file = File::open(path).map_err(|e| e.to_string())?;
+    let mut magic = [0u8; 8];
+    let n = file.read(&mut magic).map_err(|e| e.to_string())?;
+
+    let mut found = EmbeddedLyrics::default();
+    if magic[..n].starts_with(b"ID3") {
+        let tag = read_id3_tag(&mut file).map_err(|e| e.to_string())?;
+        parse_id3(&tag, &mut found);
+    } else if n == 8 && &magic[4..] == b"ftyp" {
+        if let Some(text) = read_mp4_lyrics(&mut file).map_err(|e| e.to_string())? {
+            found.set_text(text, EmbeddedSource::Mp4);
+        }
+    }
+    if found.is_empty() {
+        read_symphonia_tags(path, &mut found);
+    }
+
+    // Taggers often store LRC in the unsynchronized field
+    if found.lyric.is_none() {
+        if let Some(text) = &found.text {
+            let lyric = lrc::parse_lrc(text);
+            if !lyric.lines.is_empty() {
+                found.lyric = Some(lyric);
+            }
+        }
+    }
+    Ok(found)
+}
+
+fn read_id3_tag(file: &mut File) -> io::Result<Vec<u8>> {
+    let mut header = [0u8; 10];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    let mut tag = vec![0u8; 10 + synchsafe(&header[6..10]) as usize];
+    tag[..10].copy_from_slice(&header);
+    // A truncated tag is parsed as far as it goes
+    let mut filled = 10;
+    while filled < tag.len() {
+        match file.read(&mut tag[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    tag.truncate(filled);
+    Ok(tag)
+}
+
+fn synchsafe(b: &[u8]) -> u32 {
+    b.iter().fold(0, |acc, &x| (acc << 7) | u32::from(x & 0x7f))
+}
+
+fn be_u32(b: &[u8]) -> u32 {
+    u32::from_be_bytes([b[0], b[1], b[2], b[3]])
+}
+
+/// Reverses ID3 unsynchronisation (`FF 00` → `FF`).
+fn unsynchronise(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    for (i, &b) in data.iter().enumerate() {
+        if b == 0 && i > 0 && data[i - 1] == 0xff {
+            continue;
+        }
+        out.push(b);
+    }
+    out
+}
+
+/// Parses an ID3v2.3/2.4 tag (header included), filling `USLT` text and `SYLT` lines.
+fn parse_id3(data: &[u8], found: &mut EmbeddedLyrics) {
+    if data.len() < 10 || &data[..3] != b"ID3" {
+        return;
+    }
+    // v2.2 uses three-character frame ids and predates most lyric taggers
+    let version = data[3];
+    if !(3..=4).contains(&version) {
+        return;
+    }
+    let flags = data[5];
+    let end = (10 + synchsafe(&data[6..10]) as usize).min(data.len());
+    let mut body = Cow::Borrowed(&data[10..end]);
+    if version == 3 && flags & 0x80 != 0 {
+        body = Cow::Owned(unsynchronise(&body));
+    }
+
+    let mut pos = 0;
+    if flags & 0x40 != 0 && body.len() >= 4 {
+        pos = match version {
+            3 => 4 + be_u32(&body[..4]) as usize,
+            _ => synchsafe(&body[..4]) as usize,
+        };
+    }
+
+    while pos + 10 <= body.len() {
+        let id = &body[pos..pos + 4];
+        if id[0] == 0 {
+            break; // padding
+        }
+        let size = match version {
+            3 => be_u32(&body[pos + 4..pos + 8]),
+            _ => synchsafe(&body[pos + 4..pos + 8]),
+        } as usize;
+        let frame_flags = u16::from_be_bytes([body[pos + 8], body[pos + 9]]);
+        pos += 10;
+        let Some(frame) = pos.checked_add(size).and_then(|end| body.get(pos..end)) else {
+            break;
+        };
+        pos += size;
+
+        let frame = match version {
+            // Compressed or encrypted
+            3 if frame_flags & 0x00c0 != 0 => continue,
+            3 => Cow::Borrowed(frame),
+            _ if frame_flags & 0x000c != 0 => continue,
+            _ => {
+                let frame = if frame_flags & 0x0001 != 0 {
+                    frame.get(4..).unwrap_or_default()
+                } else {
+                    frame
+                };
+                if frame_flags & 0x0002 != 0 || flags & 0x80 != 0 {
+                    Cow::Owned(unsynchronise(frame))
+                } else {
+                    Cow::Borrowed(frame)
+                }
+            }
+        };
+
+        match id {
+            b"USLT" => {
+                if let Some(text) = parse_uslt(&frame) {
+                    found.set_text(text, EmbeddedSource::Uslt);
+                }
+            }
+            b"SYLT" if found.lyric.is_none() => {
+                if let Some(lyric) = parse_sylt(&frame) {
+                    found.lyric = Some(lyric);
+                    found.sources.push(EmbeddedSource::Sylt);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Splits off a string terminated per the ID3 text encoding (`00`, or `00 00` for UTF-16).
+fn split_terminated(data: &[u8], encoding: u8) -> (&[u8], &[u8]) {
+    if matches!(encoding, 1 | 2) {
+        let mut i = 0;
+        while i + 1 < data.len() {
+            if data[i] == 0 && data[i + 1] == 0 {
+                return (&data[..i], &data[i + 2..]);
+            }
+            i += 2;
+        }
+        (data, &[])
+    } else {
+        match data.iter().position(|&b| b == 0) {
+            Some(i) => (&data[..i], &data[i + 1..]),
+            None => (data, &[]),
+        }
+    }
+}
+
+fn decode_text(data: &[u8], encoding: u8) -> String {
+    let text = match encoding {
+        // Latin-1 maps byte for byte onto the first 256 code points
+        0 => data.iter().map(|&b| char::from(b)).collect(),
+        // UTF-16 with BOM (little endian if it's missing)
+        1 => encoding_rs::UTF_16LE.decode(data).0.into_owned(),
+        2 => encoding_rs::UTF_16BE
+            .decode_without_bom_handling(data)
+            .0
+            .into_owned(),
+        _ => String::from_utf8_lossy(data).into_owned(),
+    };
+    text.trim_end_matches('\0').to_string()
+}
+
+/// `USLT`: encoding, language, descriptor, text.
+fn parse_uslt(frame: &[u8]) -> Option<String> {
+    let (&encoding, rest) = frame.split_first()?;
+    let (_, text) = split_terminated(rest.get(3..)?, encoding);
+    Some(decode_text(text, encoding))
+}
+
+/// `SYLT`: encoding, language, timestamp format, content type, descriptor, then
+/// (text, 32-bit time) pairs.
+fn parse_sylt(frame: &[u8]) -> Option<Lyric> {
+    let (&encoding, rest) = frame.split_first()?;
+    let (&format, rest) = rest.get(3..)?.split_first()?;
+    // Format 1 counts MPEG frames, which needs the stream's frame rate
+    if format != 2 {
+        return None;
+    }
+    let (_, mut rest) = split_terminated(rest.get(1..)?, encoding);
+
+    let mut entries = Vec::new();
+    while !rest.is_empty() {
+        let (text, after) = split_terminated(rest, encoding);
+        let Some(time) = after.get(..4) else {
+            break;
+        };
+        entries.push((u64::from(be_u32(time)), decode_text(text, encoding)));
+        rest = &after[4..];
+    }
+    sylt_to_lyric(entries)
+}
+
+/// Builds lines from `SYLT` entries. Entries are whole lines, unless some of them
+/// start with a newline: then they are syllables and the newline starts a line.
+fn sylt_to_lyric(mut entries: Vec<(u64, String)>) -> Option<Lyric> {
+    entries.sort_by_key(|(time, _)| *time);
+    let is_break = |t: &str| t.starts_with(['\n', '\r']);
+    let syllables = entries.iter().skip(1).any(|(_, t)| is_break(t));
+
+    let mut groups: Vec<Vec<(u64, String)>> = Vec::new();
+    for (time, text) in entries {
+        if !syllables || is_break(&text) || groups.is_empty() {
+            groups.push(Vec::new());
+        }
+        let text = text.trim_start_matches(['\n', '\r']).to_string();
+        if let Some(group) = groups.last_mut() {
+            group.push((time, text));
+        }
+    }
+
+    let starts: Vec<u64> = groups.iter().map(|g| g[0].0).collect();
+    let mut lines = Vec::new();
+    for (i, group) in groups.iter().enumerate() {
+        let text: String = group.iter().map(|(_, t)| t.as_str()).collect();
+        // Empty entries only mark where the previous line ends
+        if text.trim().is_empty() {
+            continue;
+        }
+        let start = starts[i];
+        let end = starts
+            .get(i + 1)
+            .copied()
+            .unwrap_or_else(|| group[group.len() - 1].0 + LAST_LINE_FALLBACK_MS);
+
+        if !syllables {
+            lines.push(LyricLine::plain(start, end, text.trim()));
+            continue;
+        }
+        let words = group
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, t))| !t.is_empty())
+            .map(|(j, (time, word))| LyricWord {
+                start_time: *time,
+                end_time: group.get(j + 1).map_or(end, |(next, _)| *next),
+                word: word.trim_end_matches(['\n', '\r']).to_string(),
+                roman_word: String::new(),
+            })
+            .collect();
+        lines.push(LyricLine {
+            words,
+            start_time: start,
+            end_time: end,
+            ..Default::default()
+        });
+    }
+
+    if lines.is_empty() {
+        return None;
+    }
+    Some(Lyric {
+        lines,
+        word_timed: syllables,
+        ..Default::default()
+    })
+}
+
+/// Walks the top-level MP4 boxes to `moov` and returns its `©lyr` text.
+fn read_mp4_lyrics(file: &mut File) -> io::Result<Option<String>> {
+    let len = file.metadata()?.len();
+    let mut pos = 0;
+    while pos + 8 <= len {
+        file.seek(SeekFrom::Start(pos))?;
+        let mut header = [0u8; 8];
+        file.read_exact(&mut header)?;
+        let (size, header_len) = match be_u32(&header[..4]) {
+            0 => (len - pos, 8),
+            1 => {
+                let mut large = [0u8; 8];
+                file.read_exact(&mut large)?;
+                (u64::from_be_bytes(large), 16)
+            }
+            size => (u64::from(size), 8),
+        };
+        if size < header_len {
+            break;
+        }
+        if &header[4..] == b"moov" {
+            if size > MAX_MOOV_SIZE {
+                break;
+            }
+            let mut moov = vec![0u8; (size - header_len) as usize];
+            file.read_exact(&mut moov)?;
+            return Ok(find_mp4_lyrics(&moov));
+        }
+        match pos.checked_add(size) {
+            Some(next) if next <= len => pos = next,
+            _ => break,
+        }
+    }
+    Ok(None)
+}
+
+/// Body of the first child box of type `kind`.
+fn mp4_child<'a>(data: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let (size, header_len) = match be_u32(&data[pos..]) {
+            0 => (data.len() - pos, 8),
+            1 => {
+                let large = data.get(pos + 8..pos + 16)?;
+                (
+                    usize::try_from(u64::from_be_bytes(large.try_into().ok()?)).ok()?,
+                    16,
+                )
+            }
+            size => (size as usize, 8),
+        };
+        if size < header_len {
+            return None;
+        }
+        let end = pos.checked_add(size).filter(|&end| end <= data.len())?;
+        if &data[pos + 4..pos + 8] == kind {
+            return Some(&data[pos + header_len..end]);
+        }
+        pos = end;
+    }
+    None
+}
+
+/// `moov/udta/meta/ilst/©lyr/data`
+fn find_mp4_lyrics(moov: &[u8]) -> Option<String> {
+    let meta = mp4_child(mp4_child(moov, b"udta")?, b"meta")?;
+    // `meta` is a full box (version + flags) in MP4, but not in some QuickTime files
+    let meta = if meta.get(4..8) == Some(b"hdlr") {
+        meta
+    } else {
+        meta.get(4..)?
+    };
+    let lyrics = mp4_child(mp4_child(meta, b"ilst")?, b"\xa9lyr")?;
+    // `data`: type indicator and locale, then UTF-8 text
+    let data = mp4_child(lyrics, b"data")?.get(8..)?;
+    Some(String::from_utf8_lossy(data).into_owned())
+}
+
+fn read_symphonia_tags(path: &Path, found: &mut EmbeddedLyrics) {
+    let Ok(file) = File::open(path) else {
+        return;
+    };
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+    let Ok(mut probed) = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    ) else {
+        return;
+    };
+
+    let mut take = |revision: &MetadataRevision| {
+        for tag in revision.tags() {
+            if tag.std_key == Some(StandardTagKey::Lyrics) {
+                found.set_text(tag.value.to_string(), EmbeddedSource::Vorbis);
+            }
+        }
+    };
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|m| m.current()) {
+        take(revision);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        take(revision);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LyricOrigin {
+    /// Lyric file next to the audio file with the same stem
+    Sidecar,
+    /// Tags inside the audio file
+    Embedded,
+}
+
+pub const DEFAULT_PREFERENCE: [LyricOrigin; 2] = [LyricOrigin::Sidecar, LyricOrigin::Embedded];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedLyrics {
+    pub origin: LyricOrigin,
+    /// Sidecar file, or the audio file for embedded lyrics
+    pub path: String,
+    pub lyric: Option<Lyric>,
+    pub text: Option<String>,
+}
+
+/// First `<stem>.<ext>` lyric file next to `audio`, in [`SIDECAR_ORDER`].
+pub fn find_sidecar(audio: &Path) -> Option<PathBuf> {
+    SIDECAR_ORDER.iter().find_map(|ext| {
+        [ext.to_string(), ext.to_ascii_uppercase()]
+            .into_iter()
+            .map(|e| audio.with_extension(e))
+            .find(|p| p.is_file())
+    })
+}
+
+/// Picks the lyrics for `audio` from its sidecar file and embedded tags.
+/// Timed lyrics beat plain text whatever their origin; between two of the same
+/// kind, the origin listed first in `preference` wins.
+pub fn resolve_lyrics(audio: &Path, preference: &[LyricOrigin]) -> Option<ResolvedLyrics> {
+    let mut candidates = Vec::new();
+    for &origin in preference {
+        let candidate = match origin {
+            LyricOrigin::Sidecar => find_sidecar(audio).and_then(|path| {
+                let lyric = super::read_lyric(&path).ok()?;
+                Some(ResolvedLyrics {
+                    origin,
+                    path: path.to_string_lossy().into_owned(),
+                    lyric: Some(lyric),
+                    text: None,
+                })
+            }),
+            LyricOrigin::Embedded => read_embedded_lyrics(audio)
+                .ok()
+                .filter(|e| !e.is_empty())
+                .map(|e| ResolvedLyrics {
+                    origin,
+                    path: audio.to_string_lossy().into_owned(),
+                    lyric: e.lyric,
+                    text: e.text,
+                }),
+        };
+        candidates.extend(candidate);
+    }
+
+    let is_timed = |c: &ResolvedLyrics| c.lyric.as_ref().is_some_and(|l| !l.lines.is_empty());
+    match candidates.iter().position(is_timed) {
+        Some(i) => Some(candidates.swap_remove(i)),
+        None => candidates.into_iter().find(|c| c.text.is_some()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn frame(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = id.to_vec();
+        out.extend((body.len() as u32).to_be_bytes());
+        out.extend([0, 0]);
+        out.extend(body);
+        out
+    }
+
+    /// ID3v2.3 tag around `frames`, followed by some fake audio.
+    fn id3v23(frames: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = frames.concat();
+        let size = body.len() as u32;
+        let mut out = b"ID3\x03\x00\x00".to_vec();
+        out.extend((0..4).rev().map(|i| ((size >> (7 * i)) & 0x7f) as u8));
+        out.extend(body);
+        out.extend([0xff, 0xfb, 0x90, 0x00]);
+        out
+    }
+
+    fn utf16(text: &str) -> Vec<u8> {
+        let mut out = vec![0xff, 0xfe];
+        out.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        out
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("splayer-embedded-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn reads_uslt_and_syllable_sylt() {
+        let mut uslt = b"\x00eng\x00".to_vec();
+        uslt.extend(b"Caf\xe9 line one\nline two");
+
+        let mut sylt = b"\x01eng\x02\x01".to_vec();
+        sylt.extend(utf16("desc"));
+        sylt.extend([0, 0]);
+        for (text, time) in [
+            ("Hel", 1000u32),
+            ("lo ", 1400),
+            ("\nwor", 3000),
+            ("ld", 3500),
+        ] {
+            sylt.extend(utf16(text));
+            sylt.extend([0, 0]);
+            sylt.extend(time.to_be_bytes());
+        }
+
+        let mut found = EmbeddedLyrics::default();
+        parse_id3(
+            &id3v23(&[frame(b"USLT", &uslt), frame(b"SYLT", &sylt)]),
+            &mut found,
+        );
+        assert_eq!(found.text.as_deref(), Some("Café line one\nline two"));
+        assert_eq!(found.sources, [EmbeddedSource::Uslt, EmbeddedSource::Sylt]);
+
+        let lyric = found.lyric.unwrap();
+        assert!(lyric.word_timed);
+        assert_eq!(lyric.lines.len(), 2);
+        assert_eq!(lyric.lines[0].text(), "Hello ");
+        assert_eq!(
+            (lyric.lines[0].start_time, lyric.lines[0].end_time),
+            (1000, 3000)
+        );
+        assert_eq!(
+            (
+                lyric.lines[0].words[1].start_time,
+                lyric.lines[0].words[1].end_time
+            ),
+            (1400, 3000)
+        );
+        assert_eq!(lyric.lines[1].text(), "world");
+        assert_eq!(lyric.lines[1].end_time, 3500 + LAST_LINE_FALLBACK_MS);
+    }
+
+    #[test]
+    fn line_sylt_uses_empty_entries_as_line_ends() {
+        let entries = vec![
+            (2000, "second".to_string()),
+            (500, "first".to_string()),
+            (1500, String::new()),
+        ];
+        let lyric = sylt_to_lyric(entries).unwrap();
+        assert!(!lyric.word_timed);
+        let spans: Vec<(u64, u64, String)> = lyric
+            .lines
+            .iter()
+            .map(|l| (l.start_time, l.end_time, l.text()))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                (500, 1500, "first".to_string()),
+                (2000, 2000 + LAST_LINE_FALLBACK_MS, "second".to_string())
+            ]
+        );
+    }
+
+    fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend(kind);
+        out.extend(body);
+        out
+    }
+
+    #[test]
+    fn reads_mp4_lyrics_and_parses_lrc_text() {
+        let mut data = vec![0, 0, 0, 1, 0, 0, 0, 0];
+        data.extend("[00:01.00]One\n[00:02.50]Two\n".as_bytes());
+        let ilst = mp4_box(b"ilst", &mp4_box(b"\xa9lyr", &mp4_box(b"data", &data)));
+        let mut meta = vec![0, 0, 0, 0];
+        meta.extend(mp4_box(b"hdlr", &[0; 25]));
+        meta.extend(ilst);
+        let moov = mp4_box(b"moov", &mp4_box(b"udta", &mp4_box(b"meta", &meta)));
+
+        let mut file = mp4_box(b"ftyp", b"M4A \x00\x00\x00\x00");
+        file.extend(mp4_box(b"mdat", &[0; 64]));
+        file.extend(moov);
+
+        let dir = temp_dir("mp4");
+        let path = dir.join("song.m4a");
+        fs::write(&path, file).unwrap();
+
+        let found = read_embedded_lyrics(&path).unwrap();
+        assert_eq!(found.sources, [EmbeddedSource::Mp4]);
+        let lyric = found.lyric.unwrap();
+        assert_eq!(lyric.lines.len(), 2);
+        assert_eq!(lyric.lines[1].start_time, 2500);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn malformed_sizes_bail_instead_of_overflowing() {
+        // 64-bit box sizes near u64::MAX, after another box
+        let mut huge = vec![0, 0, 0, 1];
+        huge.extend(b"udta");
+        huge.extend((u64::MAX - 4).to_be_bytes());
+        let mut boxes = mp4_box(b"free", &[]);
+        boxes.extend(&huge);
+        assert_eq!(mp4_child(&boxes, b"meta"), None);
+        // A box shorter than its own header
+        assert_eq!(
+            mp4_child(&[0, 0, 0, 4, b'u', b'd', b't', b'a'], b"udta"),
+            None
+        );
+
+        let mut file = mp4_box(b"ftyp", b"M4A \x00\x00\x00\x00");
+        file.extend(&huge);
+        file.extend(mp4_box(b"moov", &[]));
+        let dir = temp_dir("malformed");
+        let path = dir.join("song.m4a");
+        fs::write(&path, file).unwrap();
+        let found = read_embedded_lyrics(&path).unwrap();
+        assert!(found.sources.is_empty());
+
+        // An ID3 frame claiming more bytes than the tag holds
+        let mut frame = frame(b"USLT", b"\x00eng\x00text");
+        frame[4..8].copy_from_slice(&u32::MAX.to_be_bytes());
+        let mut found = EmbeddedLyrics::default();
+        parse_id3(&id3v23(&[frame]), &mut found);
+        assert!(found.sources.is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn timed_lyrics_win_then_preference_order() {
+        let dir = temp_dir("resolve");
+        let audio = dir.join("song.mp3");
+        let mut uslt = b"\x03eng\x00".to_vec();
+        uslt.extend("Plain embedded".as_bytes());
+        fs::write(&audio, id3v23(&[frame(b"USLT", &uslt)])).unwrap();
+
+        // Only plain embedded text
+        let resolved = resolve_lyrics(&audio, &DEFAULT_PREFERENCE).unwrap();
+        assert_eq!(resolved.origin, LyricOrigin::Embedded);
+        assert_eq!(resolved.text.as_deref(), Some("Plain embedded"));
+
+        // A timed sidecar beats plain embedded text even when embedded comes first
+        fs::write(dir.join("song.lrc"), "[00:01.00]Sidecar\n").unwrap();
+        let embedded_first = [LyricOrigin::Embedded, LyricOrigin::Sidecar];
+        let resolved = resolve_lyrics(&audio, &embedded_first).unwrap();
+        assert_eq!(resolved.origin, LyricOrigin::Sidecar);
+
+        // Both timed: preference decides
+        let mut uslt = b"\x03eng\x00".to_vec();
+        uslt.extend("[00:03.00]Embedded LRC".as_bytes());
+        fs::write(&audio, id3v23(&[frame(b"USLT", &uslt)])).unwrap();
+        let resolved = resolve_lyrics(&audio, &embedded_first).unwrap();
+        assert_eq!(resolved.origin, LyricOrigin::Embedded);
+        let resolved = resolve_lyrics(&audio, &DEFAULT_PREFERENCE).unwrap();
+        assert_eq!(resolved.origin, LyricOrigin::Sidecar);
+        assert!(resolved.path.ends_with("song.lrc"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,177 @@
+// src-tauri/src/lyric/encoding.rs
+
+//! Charset detection for lyric files.
+//!
+//! Old Chinese players and Windows tools save LRCs as GBK, Big5 or UTF-16. A BOM
+//! wins if present; BOM-less UTF-16 is recognized by its NUL byte pattern; valid
+//! UTF-8 is taken as-is; anything else goes through `chardetng` restricted to the
+//! legacy CJK encodings we actually see.
+
+use std::fs;
+use std::path::Path;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, BIG5, EUC_KR, GB18030, GBK, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8};
+use serde::{Deserialize, Serialize};
+
+/// Legacy encodings tried, in order, when the detector's guess is outside this set
+const CANDIDATES: [&Encoding; 4] = [GB18030, BIG5, SHIFT_JIS, EUC_KR];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedText {
+    pub text: String,
+    /// WHATWG encoding name (`UTF-8`, `gb18030`, `Big5`, `Shift_JIS`, `EUC-KR`, `UTF-16LE`, ...)
+    pub encoding: String,
+    pub had_bom: bool,
+    /// Whether undecodable bytes were replaced with U+FFFD
+    pub lossy: bool,
+}
+
+impl DecodedText {
+    pub fn is_utf8(&self) -> bool {
+        self.encoding == UTF_8.name() && !self.had_bom
+    }
+}
+
+pub fn detect_encoding(bytes: &[u8]) -> (&'static Encoding, usize) {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return (encoding, bom_len);
+    }
+    if let Some(encoding) = sniff_utf16(bytes) {
+        return (encoding, 0);
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return (UTF_8, 0);
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    let guess = detector.guess(None, true);
+    // GBK is a subset of GB18030; decode with the superset
+    let guess = if guess == GBK { GB18030 } else { guess };
+    if CANDIDATES.contains(&guess) {
+        return (guess, 0);
+    }
+
+    let clean = CANDIDATES.into_iter().find(|enc| {
+        let (_, had_errors) = enc.decode_without_bom_handling(bytes);
+        !had_errors
+    });
+    (clean.unwrap_or(GB18030), 0)
+}
+
+/// BOM-less UTF-16: ASCII-heavy text leaves NUL in every other byte.
+fn sniff_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(4096) & !1];
+    if sample.len() < 4 {
+        return None;
+    }
+    let pairs = sample.len() / 2;
+    let even_nul = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+    let odd_nul = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|&&b| b == 0)
+        .count();
+    // Lyrics are mostly CJK, so require a clear majority rather than all-ASCII
+    let threshold = pairs * 3 / 10;
+    if odd_nul > threshold && even_nul < pairs / 20 {
+        Some(UTF_16LE)
+    } else if even_nul > threshold && odd_nul < pairs / 20 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+pub fn decode_bytes(bytes: &[u8]) -> DecodedText {
+    let (encoding, bom_len) = detect_encoding(bytes);
+    let (text, lossy) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+    DecodedText {
+        text: text.into_owned(),
+        encoding: encoding.name().to_string(),
+        had_bom: bom_len > 0,
+        lossy,
+    }
+}
+
+pub fn read_text(path: &Path) -> std::io::Result<DecodedText> {
+    Ok(decode_bytes(&fs::read(path)?))
+}
+
+/// Rewrites the file as BOM-less UTF-8 (write to a temp file, then rename).
+pub fn rewrite_utf8(path: &Path, text: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension("utf8.tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZH_HANS: &str = "[ti:晴天]\n[ar:周杰伦]\n[00:01.00]故事的小黄花\n[00:05.00]从出生那年就飘着\n[00:09.00]童年的荡秋千\n[00:13.00]随记忆一直晃到现在\n";
+    const ZH_HANT: &str = "[ti:晴天]\n[ar:周杰倫]\n[00:01.00]故事的小黃花\n[00:05.00]從出生那年就飄著\n[00:09.00]童年的盪鞦韆\n[00:13.00]隨記憶一直晃到現在\n";
+    const JA: &str = "[00:01.00]夜に駆ける 沈むように溶けてゆくように\n[00:05.00]二人だけの空が広がる夜に\n[00:09.00]さよならだけだった その一言で全てが分かった\n";
+    const KO: &str = "[00:01.00]나의 사랑 그대 곁에 있어요\n[00:05.00]오늘 밤은 별이 빛나고 있어요\n[00:09.00]우리 함께 걸어가는 이 길 위에서\n";
+
+    fn encode(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+        let (bytes, _, had_errors) = encoding.encode(text);
+        assert!(!had_errors);
+        bytes.into_owned()
+    }
+
+    fn utf16(text: &str, le: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|u| if le { u.to_le_bytes() } else { u.to_be_bytes() })
+            .collect()
+    }
+
+    #[test]
+    fn detects_legacy_cjk_encodings() {
+        for (text, encoding) in [
+            (ZH_HANS, GB18030),
+            (ZH_HANT, BIG5),
+            (JA, SHIFT_JIS),
+            (KO, EUC_KR),
+        ] {
+            let decoded = decode_bytes(&encode(text, encoding));
+            assert_eq!(decoded.text, text, "{}", encoding.name());
+            assert!(!decoded.lossy);
+            assert!(!decoded.is_utf8());
+        }
+    }
+
+    #[test]
+    fn utf8_with_and_without_bom() {
+        let plain = decode_bytes(ZH_HANS.as_bytes());
+        assert_eq!(plain.encoding, "UTF-8");
+        assert!(plain.is_utf8());
+
+        let mut with_bom = vec![0xEF, 0xBB, 0xBF];
+        with_bom.extend_from_slice(ZH_HANS.as_bytes());
+        let decoded = decode_bytes(&with_bom);
+        assert_eq!(decoded.text, ZH_HANS);
+        assert!(decoded.had_bom && !decoded.is_utf8());
+    }
+
+    #[test]
+    fn utf16_with_and_without_bom() {
+        let mut le = vec![0xFF, 0xFE];
+        le.extend(utf16(ZH_HANS, true));
+        assert_eq!(decode_bytes(&le).text, ZH_HANS);
+        assert_eq!(decode_bytes(&le).encoding, "UTF-16LE");
+
+        let mut be = vec![0xFE, 0xFF];
+        be.extend(utf16(ZH_HANS, false));
+        assert_eq!(decode_bytes(&be).text, ZH_HANS);
+
+        // Timestamps keep enough ASCII around for BOM-less sniffing
+        let decoded = decode_bytes(&utf16(ZH_HANS, true));
+        assert_eq!(decoded.encoding, "UTF-16LE");
+        assert_eq!(decoded.text, ZH_HANS);
+        assert_eq!(decode_bytes(&utf16(ZH_HANS, false)).encoding, "UTF-16BE");
+    }
+}
@@ -0,0 +1,45 @@
+// src-tauri/src/lyric/hanzi.rs
+
+//! Traditional → simplified Chinese folding, so lyric search and matching treat
+//! both scripts alike. Uses OpenCC's `t2s` conversion, the same dictionaries the
+//! frontend converts lyrics with.
+
+use std::sync::OnceLock;
+
+use ferrous_opencc::config::BuiltinConfig;
+use ferrous_opencc::OpenCC;
+
+fn converter() -> Option<&'static OpenCC> {
+    static T2S: OnceLock<Option<OpenCC>> = OnceLock::new();
+    T2S.get_or_init(|| match OpenCC::from_config(BuiltinConfig::T2s) {
+        Ok(converter) => Some(converter),
+        Err(e) => {
+            log::warn!("Failed to load the OpenCC t2s dictionaries: {}", e);
+            None
+        }
+    })
+    .as_ref()
+}
+
+/// `s` in simplified Chinese; returned unchanged if the dictionaries can't be loaded.
+pub fn to_simplified(s: &str) -> String {
+    match converter() {
+        Some(converter) => converter.convert(s),
+        None => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_traditional_text() {
+        assert_eq!(to_simplified("我們的愛 讓時間說話"), "我们的爱 让时间说话");
+        assert_eq!(
+            to_simplified("倫偉傑劉楊吳蘇趙馮瑩"),
+            "伦伟杰刘杨吴苏赵冯莹"
+        );
+        assert_eq!(to_simplified("晴天 Sunny"), "晴天 Sunny");
+    }
+}
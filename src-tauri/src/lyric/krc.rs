@@ -0,0 +1,160 @@
+// src-tauri/src/lyric/krc.rs
+
+//! Kugou `.krc`: `krc1` magic, then the zlib stream XOR'd with a fixed 16-byte key.
+//!
+//! The decoded text is `[start,duration]<offset,duration,0>word...` lines with
+//! word offsets relative to the line. The `[language:]` header is base64 JSON
+//! carrying per-line romanization (type 0) and translation (type 1).
+
+use std::io::Read;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use flate2::read::ZlibDecoder;
+use serde::Deserialize;
+
+use super::word_line::{self, KRC};
+use super::Lyric;
+
+pub const KRC_MAGIC: &[u8] = b"krc1";
+
+const KRC_KEY: [u8; 16] = [
+    0x40, 0x47, 0x61, 0x77, 0x5e, 0x32, 0x74, 0x47, 0x51, 0x36, 0x31, 0x2d, 0xce, 0xd2, 0x6e, 0x69,
+];
+
+/// Decrypts and inflates a `.krc` file into its text form.
+pub fn decrypt(bytes: &[u8]) -> Result<String, String> {
+    let body = bytes
+        .strip_prefix(KRC_MAGIC)
+        .ok_or_else(|| "Invalid KRC: missing krc1 header".to_string())?;
+    let xored: Vec<u8> = body
+        .iter()
+        .zip(KRC_KEY.iter().cycle())
+        .map(|(b, k)| b ^ k)
+        .collect();
+
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(xored.as_slice())
+        .read_to_end(&mut inflated)
+        .map_err(|e| format!("Invalid KRC: {}", e))?;
+    Ok(String::from_utf8_lossy(&inflated).into_owned())
+}
+
+/// Parses an encrypted `.krc` file.
+pub fn parse_krc(bytes: &[u8]) -> Result<Lyric, String> {
+    Ok(parse_krc_text(&decrypt(bytes)?))
+}
+
+/// Parses already-decrypted KRC text.
+pub fn parse_krc_text(content: &str) -> Lyric {
+    let mut lyric = word_line::parse_document(content, &KRC);
+    // Consumed here; no point shipping the base64 blob to the frontend
+    let language = lyric.metadata.extra.remove("language");
+    if let Some(language) = language.as_ref().and_then(|v| v.first()) {
+        apply_language(&mut lyric, language);
+    }
+    word_line::sort_lines(&mut lyric);
+    lyric
+}
+
+#[derive(Deserialize)]
+struct LanguageDoc {
+    #[serde(default)]
+    content: Vec<LanguageTrack>,
+}
+
+#[derive(Deserialize)]
+struct LanguageTrack {
+    #[serde(rename = "type")]
+    kind: u32,
+    #[serde(rename = "lyricContent", default)]
+    lyric_content: Vec<Vec<String>>,
+}
+
+/// Attaches `[language:]` translations / romanizations, indexed by line order.
+fn apply_language(lyric: &mut Lyric, encoded: &str) {
+    let Ok(json) = STANDARD.decode(encoded.trim()) else {
+        return;
+    };
+    let Ok(doc) = serde_json::from_slice::<LanguageDoc>(&json) else {
+        return;
+    };
+
+    for track in doc.content {
+        for (line, parts) in lyric.lines.iter_mut().zip(&track.lyric_content) {
+            match track.kind {
+                0 => {
+                    if parts.len() == line.words.len() {
+                        for (word, roman) in line.words.iter_mut().zip(parts) {
+                            word.roman_word = roman.trim().to_string();
+                        }
+                    }
+                    line.roman_lyric = parts
+                        .iter()
+                        .map(|p| p.trim())
+                        .filter(|p| !p.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                }
+                1 => line.translated_lyric = parts.concat().trim().to_string(),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn encrypt(text: &str) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut out = KRC_MAGIC.to_vec();
+        out.extend(
+            compressed
+                .iter()
+                .zip(KRC_KEY.iter().cycle())
+                .map(|(b, k)| b ^ k),
+        );
+        out
+    }
+
+    #[test]
+    fn decodes_encrypted_krc_with_language() {
+        let language = STANDARD.encode(
+            r#"{"content":[{"lyricContent":[["qing","tian"],["gu","shi"]],"type":0},{"lyricContent":[["Sunny day"],["Story"]],"type":1}],"version":1}"#,
+        );
+        let text = format!(
+            "\u{feff}[ti:晴天]\n[ar:周杰伦]\n[language:{}]\n[1000,2000]<0,500,0>晴<500,1500,0>天\n[3000,1000]<0,400,0>故<400,600,0>事\n",
+            language
+        );
+
+        let lyric = parse_krc(&encrypt(&text)).unwrap();
+        assert_eq!(lyric.metadata.title.as_deref(), Some("晴天"));
+        assert!(lyric.word_timed);
+        assert_eq!(lyric.lines.len(), 2);
+
+        let first = &lyric.lines[0];
+        assert_eq!(first.text(), "晴天");
+        assert_eq!((first.start_time, first.end_time), (1_000, 3_000));
+        assert_eq!(
+            (first.words[1].start_time, first.words[1].end_time),
+            (1_500, 3_000)
+        );
+        assert_eq!(first.words[0].roman_word, "qing");
+        assert_eq!(first.roman_lyric, "qing tian");
+        assert_eq!(first.translated_lyric, "Sunny day");
+        assert_eq!(lyric.lines[1].translated_lyric, "Story");
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse_krc(b"not a krc").is_err());
+        assert!(parse_krc(b"krc1\x00\x01\x02").is_err());
+    }
+}
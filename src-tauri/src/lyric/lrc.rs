@@ -0,0 +1,538 @@
+// src-tauri/src/lyric/lrc.rs
+
+//! LRC parser: plain, multi-timestamp and enhanced (A2, `<mm:ss.xx>` word timing).
+//!
+//! Parsing never fails. Anything that isn't a recognizable time tag or header is
+//! dropped, so malformed files degrade to fewer lines instead of an error.
+
+use super::convert::ConvertOptions;
+use super::{Lyric, LyricLine, LyricMetadata, LyricWord};
+
+/// End time of the last line when neither `[length:]` nor word timing gives one
+pub(super) const LAST_LINE_FALLBACK_MS: u64 = 5000;
+
+/// Parses `mm:ss`, `mm:ss.x`/`.xx`/`.xxx` and the `mm:ss:xx` variant into ms.
+pub fn parse_timestamp(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let mut parts = s.splitn(3, ':');
+    let min = parts.next()?.trim();
+    let sec = parts.next()?.trim();
+    let frac3 = parts.next().map(str::trim);
+
+    let (sec, frac) = match frac3 {
+        Some(f) => (sec, Some(f)),
+        None => match sec.find(['.', ',']) {
+            Some(i) => (sec[..i].trim(), Some(sec[i + 1..].trim())),
+            None => (sec, None),
+        },
+    };
+
+    let digits = |v: &str| !v.is_empty() && v.bytes().all(|b| b.is_ascii_digit());
+    if !digits(min) || !digits(sec) || min.len() > 4 || sec.len() > 2 {
+        return None;
+    }
+    let min: u64 = min.parse().ok()?;
+    let sec: u64 = sec.parse().ok()?;
+
+    let frac_ms = match frac {
+        None => 0,
+        Some(f) if digits(f) => {
+            // Scale to ms by digit count, ignoring anything past the third digit
+            let f3: String = f.chars().chain("00".chars()).take(3).collect();
+            f3.parse::<u64>().ok()?
+        }
+        Some(_) => return None,
+    };
+
+    Some(min * 60_000 + sec * 1000 + frac_ms)
+}
+
+/// Formats ms as `mm:ss.xx`.
+pub fn format_timestamp(ms: u64) -> String {
+    let cs = ms.saturating_add(5) / 10;
+    format!("{:02}:{:02}.{:02}", cs / 6000, (cs / 100) % 60, cs % 100)
+}
+
+/// A single timed line before end times are known.
+struct RawLine {
+    start: u64,
+    text: String,
+    /// Enhanced word timing
+    words: Vec<RawWord>,
+}
+
+#[derive(Clone)]
+struct RawWord {
+    start: u64,
+    /// Set when a closing tag follows the word directly (`<a>word<b><c>next`)
+    end: Option<u64>,
+    text: String,
+}
+
+pub fn parse_lrc(content: &str) -> Lyric {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut metadata = LyricMetadata::default();
+    let mut raw: Vec<RawLine> = Vec::new();
+
+    for line in content.lines() {
+        let (times, rest) = split_time_tags(line);
+        if times.is_empty() {
+            if let Some((key, value)) = parse_header(line) {
+                apply_header(&mut metadata, key, value);
+            }
+            continue;
+        }
+
+        let words = parse_enhanced_words(rest);
+        let text = if words.is_empty() {
+            rest.trim().to_string()
+        } else {
+            words
+                .iter()
+                .map(|w| w.text.as_str())
+                .collect::<String>()
+                .trim()
+                .to_string()
+        };
+        for start in times {
+            raw.push(RawLine {
+                start,
+                text: text.clone(),
+                words: words.clone(),
+            });
+        }
+    }
+
+    let shift = |t: u64| (t as i64 - metadata.offset).max(0) as u64;
+    for r in &mut raw {
+        r.start = shift(r.start);
+        for w in &mut r.words {
+            w.start = shift(w.start);
+            w.end = w.end.map(shift);
+        }
+    }
+    raw.sort_by_key(|r| r.start);
+
+    let word_timed = raw.iter().any(|r| !r.words.is_empty());
+    let mut lines = Vec::with_capacity(raw.len());
+    for (i, r) in raw.iter().enumerate() {
+        // Blank timed lines only mark where the previous line ends
+        if r.text.is_empty() {
+            continue;
+        }
+        let next_start = raw[i + 1..].iter().map(|n| n.start).find(|&s| s > r.start);
+        let end = match next_start {
+            Some(s) => s,
+            None => r
+                .words
+                .last()
+                .map(|w| w.end.unwrap_or(w.start))
+                .filter(|&e| e > r.start)
+                .or(metadata.length.filter(|&l| l > r.start))
+                .unwrap_or(r.start + LAST_LINE_FALLBACK_MS),
+        };
+        lines.push(build_line(r, end));
+    }
+
+    Lyric {
+        metadata,
+        lines,
+        word_timed,
+    }
+}
+
+fn build_line(r: &RawLine, end: u64) -> LyricLine {
+    if r.words.is_empty() {
+        return LyricLine::plain(r.start, end, r.text.clone());
+    }
+
+    let mut words = Vec::with_capacity(r.words.len());
+    for (i, w) in r.words.iter().enumerate() {
+        let word_end = w
+            .end
+            .or(r.words.get(i + 1).map(|next| next.start))
+            .unwrap_or(end);
+        words.push(LyricWord {
+            start_time: w.start,
+            end_time: word_end.max(w.start),
+            word: w.text.clone(),
+            roman_word: String::new(),
+        });
+    }
+    let end = words.iter().map(|w| w.end_time).fold(end, u64::max);
+
+    LyricLine {
+        words,
+        start_time: r.start,
+        end_time: end,
+        ..Default::default()
+    }
+}
+
+/// Splits leading `[time]` tags off a line. Stops at the first tag that isn't a time.
+fn split_time_tags(line: &str) -> (Vec<u64>, &str) {
+    let mut times = Vec::new();
+    let mut rest = line;
+    loop {
+        let trimmed = rest.trim_start();
+        let Some(inner) = trimmed.strip_prefix('[') else {
+            break;
+        };
+        let Some(close) = inner.find(']') else {
+            break;
+        };
+        match parse_timestamp(&inner[..close]) {
+            Some(t) => {
+                times.push(t);
+                rest = &inner[close + 1..];
+            }
+            None => break,
+        }
+    }
+    (times, rest)
+}
+
+/// `[key:value]` header with an alphabetic key.
+pub(super) fn parse_header(line: &str) -> Option<(String, &str)> {
+    let inner = line.trim().strip_prefix('[')?;
+    let inner = &inner[..inner.rfind(']')?];
+    let (key, value) = inner.split_once(':')?;
+    let key = key.trim();
+    if key.is_empty()
+        || !key
+            .chars()
+            .all(|c| c.is_ascii_alphabetic() || c == '_' || c == '#')
+    {
+        return None;
+    }
+    Some((key.to_ascii_lowercase(), value.trim()))
+}
+
+pub(super) fn apply_header(metadata: &mut LyricMetadata, key: String, value: &str) {
+    match key.as_str() {
+        "ti" => metadata.title = Some(value.to_string()),
+        "ar" => metadata.artist = Some(value.to_string()),
+        "al" => metadata.album = Some(value.to_string()),
+        "length" => metadata.length = parse_timestamp(value),
+        "offset" => {
+            if let Ok(v) = value.trim_start_matches('+').trim().parse::<i64>() {
+                metadata.offset = v;
+            }
+        }
+        _ => metadata
+            .extra
+            .entry(key)
+            .or_default()
+            .push(value.to_string()),
+    }
+}
+
+/// Enhanced LRC words: `<00:01.00>Hel<00:01.30>lo <00:01.80>`.
+/// A tag directly after another closes the previous word, so gaps survive.
+/// Returns no words when the text has no valid `<time>` tag.
+fn parse_enhanced_words(text: &str) -> Vec<RawWord> {
+    let mut words: Vec<RawWord> = Vec::new();
+    let mut current: Option<u64> = None;
+    let mut buf = String::new();
+    let mut rest = text;
+
+    while let Some(open) = rest.find('<') {
+        let tag = rest[open + 1..].find('>').and_then(|close| {
+            parse_timestamp(&rest[open + 1..open + 1 + close]).map(|t| (t, close))
+        });
+        let Some((t, close)) = tag else {
+            buf.push_str(&rest[..open + 1]);
+            rest = &rest[open + 1..];
+            continue;
+        };
+        buf.push_str(&rest[..open]);
+        match current {
+            Some(start) if !buf.is_empty() => words.push(RawWord {
+                start,
+                end: None,
+                text: std::mem::take(&mut buf),
+            }),
+            // Text before the first tag becomes a word starting at that tag
+            None if !buf.trim().is_empty() => words.push(RawWord {
+                start: t,
+                end: None,
+                text: std::mem::take(&mut buf),
+            }),
+            // Back-to-back tags: the first one closed the previous word
+            Some(closing) => {
+                buf.clear();
+                if let Some(last) = words.last_mut().filter(|w| w.end.is_none()) {
+                    last.end = Some(closing);
+                }
+            }
+            None => buf.clear(),
+        }
+        current = Some(t);
+        rest = &rest[open + 2 + close..];
+    }
+
+    let Some(start) = current else {
+        return Vec::new();
+    };
+    buf.push_str(rest);
+    if buf.trim().is_empty() {
+        // Trailing tag closes the last word
+        if let Some(last) = words.last_mut().filter(|w| w.end.is_none()) {
+            last.end = Some(start);
+        }
+    } else {
+        words.push(RawWord {
+            start,
+            end: None,
+            text: buf,
+        });
+    }
+    words
+}
+
+/// Writes plain or enhanced (`<mm:ss.xx>` word timing) LRC.
+///
+/// A blank timed line marks each line's end when it doesn't run into the next
+/// line, so end times survive a round trip. Translation and romanization are
+/// written as extra lines sharing the original line's timestamp.
+pub fn write_lrc(lyric: &Lyric, options: &ConvertOptions, enhanced: bool) -> String {
+    let mut out = String::new();
+    let meta = &lyric.metadata;
+    for (tag, value) in [
+        ("ti", &meta.title),
+        ("ar", &meta.artist),
+        ("al", &meta.album),
+    ] {
+        if let Some(v) = value {
+            out.push_str(&format!("[{}:{}]\n", tag, v));
+        }
+    }
+    if let Some(length) = meta.length {
+        out.push_str(&format!("[length:{}]\n", format_timestamp(length)));
+    }
+
+    for (i, line) in lyric.lines.iter().enumerate() {
+        let start = format_timestamp(line.start_time);
+        out.push('[');
+        out.push_str(&start);
+        out.push(']');
+        if enhanced {
+            push_enhanced_words(&mut out, line);
+        } else {
+            out.push_str(line.text().trim());
+        }
+        out.push('\n');
+
+        for (enabled, extra) in [
+            (options.include_translation, &line.translated_lyric),
+            (options.include_roman, &line.roman_lyric),
+        ] {
+            if enabled && !extra.is_empty() {
+                out.push_str(&format!("[{}]{}\n", start, extra));
+            }
+        }
+
+        let runs_into_next = match lyric.lines.get(i + 1) {
+            Some(next) => line.end_time >= next.start_time,
+            None => false,
+        };
+        if line.end_time > line.start_time && !runs_into_next {
+            out.push_str(&format!("[{}]\n", format_timestamp(line.end_time)));
+        }
+    }
+    out
+}
+
+fn push_enhanced_words(out: &mut String, line: &LyricLine) {
+    for (i, w) in line.words.iter().enumerate() {
+        out.push_str(&format!("<{}>{}", format_timestamp(w.start_time), w.word));
+        // Close the word explicitly unless the next one starts right away
+        let next_start = line.words.get(i + 1).map(|n| n.start_time);
+        if next_start != Some(w.end_time) {
+            out.push_str(&format!("<{}>", format_timestamp(w.end_time)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn texts(lyric: &Lyric) -> Vec<String> {
+        lyric.lines.iter().map(LyricLine::text).collect()
+    }
+
+    fn starts(lyric: &Lyric) -> Vec<u64> {
+        lyric.lines.iter().map(|l| l.start_time).collect()
+    }
+
+    #[test]
+    fn timestamp_variants() {
+        assert_eq!(parse_timestamp("01:02.5"), Some(62_500));
+        assert_eq!(parse_timestamp("01:02.50"), Some(62_500));
+        assert_eq!(parse_timestamp("01:02.503"), Some(62_503));
+        assert_eq!(parse_timestamp("01:02:50"), Some(62_500));
+        assert_eq!(parse_timestamp("1:2"), Some(62_000));
+        assert_eq!(parse_timestamp(" 00 : 05 . 10 "), Some(5_100));
+        assert_eq!(parse_timestamp("ar:Someone"), None);
+        assert_eq!(parse_timestamp("-00:01.00"), None);
+        assert_eq!(format_timestamp(62_500), "01:02.50");
+    }
+
+    #[test]
+    fn fixture_netease_translation() {
+        let lyric = parse_lrc(include_str!(
+            "../../tests/fixtures/lrc/netease_translation.lrc"
+        ));
+        assert_eq!(lyric.metadata.extra["by"], ["netease"]);
+        // Duplicate timestamps (original + translation) stay in file order
+        assert_eq!(starts(&lyric), [0, 0, 15_200, 15_200, 19_800, 19_800]);
+        assert_eq!(texts(&lyric)[0], "作词 : 某人");
+        assert_eq!(lyric.lines[0].end_time, 15_200);
+        assert_eq!(lyric.lines[2].text(), "Hello darkness my old friend");
+        assert_eq!(lyric.lines[3].text(), "你好 黑暗 我的老朋友");
+    }
+
+    #[test]
+    fn fixture_multi_timestamp_chorus() {
+        let lyric = parse_lrc(include_str!("../../tests/fixtures/lrc/multi_timestamp.lrc"));
+        assert_eq!(starts(&lyric), [10_000, 20_000, 30_000, 40_000, 50_000]);
+        assert_eq!(
+            texts(&lyric),
+            [
+                "Verse one",
+                "Chorus line",
+                "Verse two",
+                "Chorus line",
+                "Outro"
+            ]
+        );
+        assert_eq!(lyric.lines[1].end_time, 30_000);
+    }
+
+    #[test]
+    fn fixture_enhanced_word_timing() {
+        let lyric = parse_lrc(include_str!("../../tests/fixtures/lrc/enhanced_a2.lrc"));
+        assert!(lyric.word_timed);
+        let first = &lyric.lines[0];
+        assert_eq!(first.text(), "Never gonna give you up");
+        assert_eq!(first.words.len(), 5);
+        assert_eq!(first.words[0].word, "Never ");
+        assert_eq!(
+            (first.words[0].start_time, first.words[0].end_time),
+            (1_000, 1_400)
+        );
+        // Trailing tag closes the last word before the next line starts
+        assert_eq!(first.words[4].end_time, 3_000);
+        assert_eq!(first.end_time, 3_500);
+        // Last line: closed by its trailing tag
+        let last = lyric.lines.last().unwrap();
+        assert_eq!(last.end_time, 6_800);
+    }
+
+    #[test]
+    fn fixture_offset_and_headers() {
+        let lyric = parse_lrc(include_str!("../../tests/fixtures/lrc/offset_headers.lrc"));
+        assert_eq!(lyric.metadata.title.as_deref(), Some("Song Title"));
+        assert_eq!(lyric.metadata.artist.as_deref(), Some("Artist Name"));
+        assert_eq!(lyric.metadata.album.as_deref(), Some("Album"));
+        assert_eq!(lyric.metadata.length, Some(200_000));
+        assert_eq!(lyric.metadata.offset, 500);
+        // Positive offset shows lyrics earlier; clamped at zero
+        assert_eq!(starts(&lyric), [0, 9_500, 19_500]);
+        assert_eq!(lyric.lines.last().unwrap().end_time, 200_000);
+    }
+
+    #[test]
+    fn fixture_malformed() {
+        let lyric = parse_lrc(include_str!("../../tests/fixtures/lrc/malformed.lrc"));
+        assert_eq!(lyric.metadata.title.as_deref(), Some("Broken"));
+        assert_eq!(lyric.metadata.artist.as_deref(), Some("Uppercase Tag"));
+        assert_eq!(
+            texts(&lyric),
+            [
+                "short time",
+                "colon centiseconds",
+                "[unterminated tag",
+                "out of order"
+            ]
+        );
+        assert_eq!(starts(&lyric), [5_100, 12_340, 20_000, 25_000]);
+        assert_eq!(lyric.metadata.extra["offset_bogus"], ["nope"]);
+    }
+
+    #[test]
+    fn fixture_blank_gaps() {
+        let lyric = parse_lrc(include_str!("../../tests/fixtures/lrc/blank_gaps.lrc"));
+        assert_eq!(texts(&lyric), ["Before the break", "After the break"]);
+        // The blank line ends the first line early instead of bridging the gap
+        assert_eq!(lyric.lines[0].end_time, 8_000);
+        assert_eq!(lyric.lines[1].start_time, 30_000);
+        assert_eq!(lyric.lines[1].end_time, 30_000 + LAST_LINE_FALLBACK_MS);
+    }
+
+    #[test]
+    fn fixture_kugou_style() {
+        let lyric = parse_lrc(include_str!("../../tests/fixtures/lrc/kugou_style.lrc"));
+        assert_eq!(lyric.metadata.extra["id"], ["$00000000"]);
+        assert_eq!(lyric.metadata.extra["total"], ["245000"]);
+        assert_eq!(texts(&lyric), ["歌手 - 歌名", "第一句歌词", "第二句歌词"]);
+        assert_eq!(lyric.lines[2].start_time, 21_450);
+    }
+
+    fn timestamp() -> impl Strategy<Value = u64> {
+        (0u64..100, 0u64..60, 0u64..100).prop_map(|(m, s, cs)| m * 60_000 + s * 1000 + cs * 10)
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics(input in "\\PC*") {
+            let _ = parse_lrc(&input);
+        }
+
+        #[test]
+        fn never_panics_on_bracket_soup(input in "[\\[\\]<>:.0-9a-z \\n-]{0,200}") {
+            let _ = parse_lrc(&input);
+        }
+
+        #[test]
+        fn lines_sorted_with_valid_ends(
+            lines in prop::collection::vec((prop::collection::vec(timestamp(), 1..3), "[a-z ]{0,12}"), 0..30),
+            offset in -5000i64..5000,
+        ) {
+            let mut src = format!("[offset:{}]\n", offset);
+            for (times, text) in &lines {
+                for t in times {
+                    src.push_str(&format!("[{}]", format_timestamp(*t)));
+                }
+                src.push_str(text);
+                src.push('\n');
+            }
+            let lyric = parse_lrc(&src);
+            for pair in lyric.lines.windows(2) {
+                prop_assert!(pair[0].start_time <= pair[1].start_time);
+            }
+            for line in &lyric.lines {
+                prop_assert!(line.end_time >= line.start_time);
+                prop_assert!(!line.text().is_empty());
+            }
+        }
+
+        #[test]
+        fn round_trips_through_format(
+            lines in prop::collection::vec((timestamp(), "[a-z][a-z ]{0,10}[a-z]"), 1..20),
+        ) {
+            let mut src = String::new();
+            for (t, text) in &lines {
+                src.push_str(&format!("[{}]{}\n", format_timestamp(*t), text));
+            }
+            let lyric = parse_lrc(&src);
+            let mut expected = lines.clone();
+            expected.sort_by_key(|(t, _)| *t);
+            let got: Vec<(u64, String)> =
+                lyric.lines.iter().map(|l| (l.start_time, l.text())).collect();
+            prop_assert_eq!(got, expected);
+        }
+    }
+}
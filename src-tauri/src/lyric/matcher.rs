@@ -0,0 +1,464 @@
+// src-tauri/src/lyric/matcher.rs
+
+//! Matches a track to local lyric files.
+//!
+//! Each lyric file (`.lrc`, `.ttml`, `.krc`, `.qrc`, `.yrc`) is indexed by its file name, embedded TTML ids
+//! (`ncmMusicId`, ...) and LRC `[ti]/[ar]/[al]/[length]` tags. Candidates are
+//! ranked by normalized title/artist similarity and duration distance; an exact id
+//! match always ranks first.
+
+use std::path::Path;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use super::scanner::LyricFile;
+use super::{hanzi, LyricMetadata};
+
+/// Metadata keys that identify a track on a streaming service
+const ID_KEYS: [&str; 3] = ["ncmMusicId", "qqMusicId", "id"];
+
+/// Durations within this distance count as a full match
+const DURATION_TOLERANCE_MS: u64 = 3_000;
+/// Beyond this distance the duration actively counts against a candidate
+const DURATION_MAX_MS: u64 = 10_000;
+
+/// Candidates below this score are not returned
+const MIN_SCORE: f32 = 0.45;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackQuery {
+    /// NetEase ids arrive as numbers, QQ Music mids (`0039MnYb0qxYhV`) as strings
+    #[serde(default, deserialize_with = "deserialize_id")]
+    pub id: Option<String>,
+    pub title: String,
+    #[serde(default)]
+    pub artist: String,
+    #[serde(default)]
+    pub album: String,
+    /// Track duration in ms
+    pub duration: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LyricIndexEntry {
+    pub path: String,
+    pub name: String,
+    pub ids: Vec<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub length: Option<u64>,
+}
+
+impl LyricIndexEntry {
+    /// Builds an entry from parsed metadata plus what the file name tells us.
+    pub fn new(path: &Path, metadata: Option<&LyricMetadata>) -> Self {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let stem = path
+            .file_stem()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let mut entry = Self {
+            path: path.to_string_lossy().into_owned(),
+            name,
+            ..Default::default()
+        };
+
+        if let Some(meta) = metadata {
+            entry.title = meta.title.clone().filter(|s| !s.is_empty());
+            entry.artist = meta.artist.clone().filter(|s| !s.is_empty());
+            entry.album = meta.album.clone().filter(|s| !s.is_empty());
+            entry.length = meta.length;
+            for key in ID_KEYS {
+                for id in meta.extra.get(key).into_iter().flatten() {
+                    let id = id.trim();
+                    if !id.is_empty() && !entry.ids.iter().any(|i| i == id) {
+                        entry.ids.push(id.to_string());
+                    }
+                }
+            }
+        }
+
+        // `123456.lrc` is an id; `Artist - Title.lrc` fills in missing tags
+        let stem = stem.trim();
+        if !stem.is_empty() && stem.bytes().all(|b| b.is_ascii_digit()) {
+            if !entry.ids.iter().any(|i| i == stem) {
+                entry.ids.push(stem.to_string());
+            }
+        } else if let Some((artist, title)) = stem.split_once(" - ") {
+            entry
+                .artist
+                .get_or_insert_with(|| artist.trim().to_string());
+            entry.title.get_or_insert_with(|| title.trim().to_string());
+        } else {
+            entry.title.get_or_insert_with(|| stem.to_string());
+        }
+
+        entry
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LyricMatch {
+    pub path: String,
+    pub name: String,
+    /// 0.0 – 1.0
+    pub score: f32,
+    pub id_match: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct LyricIndex {
+    pub entries: Vec<LyricIndexEntry>,
+}
+
+impl LyricIndex {
+    /// Builds the index from scanned lyric files.
+    pub fn from_files<'a>(files: impl IntoIterator<Item = &'a LyricFile>) -> Self {
+        let entries = files
+            .into_iter()
+            .map(|f| LyricIndexEntry::new(Path::new(&f.path), f.metadata.as_ref()))
+            .collect();
+        Self { entries }
+    }
+
+    /// Ranked candidates for `track`, best first.
+    pub fn find(&self, track: &TrackQuery) -> Vec<LyricMatch> {
+        let mut matches: Vec<LyricMatch> = self
+            .entries
+            .iter()
+            .filter_map(|entry| {
+                let (score, id_match) = score(entry, track);
+                (score >= MIN_SCORE).then(|| LyricMatch {
+                    path: entry.path.clone(),
+                    name: entry.name.clone(),
+                    score,
+                    id_match,
+                })
+            })
+            .collect();
+        matches.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        matches
+    }
+}
+
+/// Score in 0.0 – 1.0 and whether an id matched exactly.
+fn score(entry: &LyricIndexEntry, track: &TrackQuery) -> (f32, bool) {
+    if let Some(id) = track.id.as_deref().map(normalize_id) {
+        if !id.is_empty() && entry.ids.iter().any(|i| normalize_id(i) == id) {
+            return (1.0, true);
+        }
+    }
+
+    let Some(title) = entry.title.as_deref() else {
+        return (0.0, false);
+    };
+    let title_score = similarity(&normalize(title), &normalize(&track.title));
+    if title_score < 0.5 {
+        return (0.0, false);
+    }
+
+    let artist_score = match entry.artist.as_deref() {
+        Some(artist) if !track.artist.is_empty() => artist_similarity(artist, &track.artist),
+        // Unknown artist: neither confirms nor rules out
+        _ => 0.5,
+    };
+    let album_bonus = match entry.album.as_deref() {
+        Some(album) if !track.album.is_empty() && normalize(album) == normalize(&track.album) => {
+            0.05
+        }
+        _ => 0.0,
+    };
+    let duration_score = match (entry.length, track.duration) {
+        (Some(a), Some(b)) => {
+            let diff = a.abs_diff(b);
+            if diff <= DURATION_TOLERANCE_MS {
+                1.0
+            } else if diff >= DURATION_MAX_MS {
+                -1.0
+            } else {
+                1.0 - 2.0 * (diff - DURATION_TOLERANCE_MS) as f32
+                    / (DURATION_MAX_MS - DURATION_TOLERANCE_MS) as f32
+            }
+        }
+        _ => 0.0,
+    };
+
+    let score = 0.6 * title_score + 0.3 * artist_score + 0.1 * duration_score + album_bonus;
+    (score.clamp(0.0, 0.99), false)
+}
+
+fn deserialize_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Number(u64),
+        Text(String),
+    }
+    Ok(Option::<Id>::deserialize(deserializer)?.map(|id| match id {
+        Id::Number(n) => n.to_string(),
+        Id::Text(s) => s,
+    }))
+}
+
+/// Ids compare case-insensitively; all-digit ids also ignore leading zeros.
+fn normalize_id(id: &str) -> String {
+    let id = id.trim();
+    if !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()) {
+        let digits = id.trim_start_matches('0');
+        return if digits.is_empty() { "0" } else { digits }.to_string();
+    }
+    id.to_ascii_lowercase()
+}
+
+/// Folds case, full-width forms and traditional Chinese, drops bracketed parts and
+/// `feat.` credits, and strips punctuation/whitespace.
+pub fn normalize(s: &str) -> String {
+    let half_width: String = s.chars().map(fold_width).collect();
+    let folded = hanzi::to_simplified(&half_width).to_lowercase();
+
+    let mut out = String::with_capacity(folded.len());
+    let mut depth = 0usize;
+    for c in folded.chars() {
+        match c {
+            '(' | '[' | '{' | '【' | '「' | '『' | '〈' | '《' => depth += 1,
+            ')' | ']' | '}' | '】' | '」' | '』' | '〉' | '》' => {
+                depth = depth.saturating_sub(1)
+            }
+            _ if depth == 0 => out.push(c),
+            _ => {}
+        }
+    }
+
+    let out = strip_feat(&out);
+    out.chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+pub(super) fn fold_width(c: char) -> char {
+    match c {
+        '\u{3000}' => ' ',
+        '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Cuts a trailing ` feat. X` / ` ft. X` / ` featuring X` credit.
+fn strip_feat(s: &str) -> &str {
+    for marker in [" feat.", " feat ", " ft.", " ft ", " featuring "] {
+        if let Some(i) = s.find(marker) {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+/// 1.0 for equal strings, otherwise the better of the bigram Dice coefficient
+/// and, when one contains the other, 0.9 scaled by how much of the longer one
+/// the shorter covers (so "love" doesn't match every title containing it).
+fn similarity(a: &str, b: &str) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    let (a_len, b_len) = (a.chars().count(), b.chars().count());
+    let (shorter, longer, short_len, long_len) = if a_len <= b_len {
+        (a, b, a_len, b_len)
+    } else {
+        (b, a, b_len, a_len)
+    };
+    let containment = if longer.contains(shorter) {
+        0.9 * short_len as f32 / long_len as f32
+    } else {
+        0.0
+    };
+    containment.max(dice(a, b))
+}
+
+fn dice(a: &str, b: &str) -> f32 {
+    let bigrams = |s: &str| -> Vec<(char, char)> {
+        let chars: Vec<char> = s.chars().collect();
+        chars.windows(2).map(|w| (w[0], w[1])).collect()
+    };
+    let ab = bigrams(a);
+    let mut bb = bigrams(b);
+    if ab.is_empty() || bb.is_empty() {
+        return 0.0;
+    }
+    let total = ab.len() + bb.len();
+    let mut common = 0;
+    for g in &ab {
+        if let Some(i) = bb.iter().position(|x| x == g) {
+            bb.swap_remove(i);
+            common += 1;
+        }
+    }
+    2.0 * common as f32 / total as f32
+}
+
+/// Best similarity between any artist in `a` and any in `b` (split on `/ , & 、`).
+fn artist_similarity(a: &str, b: &str) -> f32 {
+    let split = |s: &str| -> Vec<String> {
+        strip_feat(&s.to_lowercase())
+            .split(['/', ',', '&', '、', ';', '，'])
+            .map(normalize)
+            .filter(|s| !s.is_empty())
+            .collect()
+    };
+    let (a, b) = (split(a), split(b));
+    a.iter()
+        .flat_map(|x| b.iter().map(move |y| similarity(x, y)))
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(
+        name: &str,
+        title: Option<&str>,
+        artist: Option<&str>,
+        length: Option<u64>,
+    ) -> LyricIndexEntry {
+        let mut e = LyricIndexEntry::new(Path::new(name), None);
+        if title.is_some() {
+            e.title = title.map(str::to_string);
+        }
+        if artist.is_some() {
+            e.artist = artist.map(str::to_string);
+        }
+        e.length = length;
+        e
+    }
+
+    #[test]
+    fn normalize_folds_case_width_brackets_and_feat() {
+        assert_eq!(normalize("Ｈｅｌｌｏ　Ｗｏｒｌｄ"), "helloworld");
+        assert_eq!(normalize("Song (Live) [Remastered 2011]"), "song");
+        assert_eq!(normalize("Song 【伴奏】 feat. Someone"), "song");
+        assert_eq!(normalize("晴天（Live）"), "晴天");
+        assert_eq!(normalize("說好的幸福呢"), normalize("说好的幸福呢"));
+        assert_eq!(normalize("Don't Stop Me Now"), "dontstopmenow");
+    }
+
+    #[test]
+    fn file_name_fills_in_missing_tags() {
+        let e = LyricIndexEntry::new(Path::new("/lyrics/周杰伦 - 晴天.lrc"), None);
+        assert_eq!(e.artist.as_deref(), Some("周杰伦"));
+        assert_eq!(e.title.as_deref(), Some("晴天"));
+
+        let e = LyricIndexEntry::new(Path::new("/lyrics/186016.ttml"), None);
+        assert_eq!(e.ids, ["186016"]);
+    }
+
+    #[test]
+    fn ranks_id_then_title_artist_duration() {
+        let index = LyricIndex {
+            entries: vec![
+                entry("other.lrc", Some("Another Song"), Some("Someone"), None),
+                entry("wrong_len.lrc", Some("晴天"), Some("周杰伦"), Some(120_000)),
+                entry(
+                    "good.lrc",
+                    Some("晴天 (Live)"),
+                    Some("周杰伦 / 五月天"),
+                    Some(269_500),
+                ),
+                entry("186016.ttml", None, None, None),
+            ],
+        };
+        let track = TrackQuery {
+            id: Some("186016".to_string()),
+            title: "晴天".to_string(),
+            artist: "周杰伦".to_string(),
+            album: "叶惠美".to_string(),
+            duration: Some(269_000),
+        };
+
+        let results = index.find(&track);
+        let names: Vec<&str> = results.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["186016.ttml", "good.lrc", "wrong_len.lrc"]);
+        assert!(results[0].id_match && results[0].score == 1.0);
+        assert!(results[1].score > results[2].score);
+
+        // Without an id the tag match wins
+        let results = index.find(&TrackQuery { id: None, ..track });
+        assert_eq!(results[0].name, "good.lrc");
+    }
+
+    #[test]
+    fn matches_alphanumeric_and_numeric_ids() {
+        let mut meta = LyricMetadata::default();
+        meta.extra
+            .insert("qqMusicId".to_string(), vec!["0039MnYb0qxYhV".to_string()]);
+        let index = LyricIndex {
+            entries: vec![
+                LyricIndexEntry::new(Path::new("qq.ttml"), Some(&meta)),
+                entry("186016.ttml", None, None, None),
+            ],
+        };
+        let find = |json: &str| {
+            let track: TrackQuery = serde_json::from_str(json).unwrap();
+            index
+                .find(&track)
+                .into_iter()
+                .find(|m| m.id_match)
+                .map(|m| m.name)
+        };
+        assert_eq!(
+            find(r#"{"id": "0039mnyb0qxyhv", "title": "晴天"}"#).as_deref(),
+            Some("qq.ttml")
+        );
+        assert_eq!(
+            find(r#"{"id": 186016, "title": "晴天"}"#).as_deref(),
+            Some("186016.ttml")
+        );
+        assert_eq!(
+            find(r#"{"id": "0186016", "title": "晴天"}"#).as_deref(),
+            Some("186016.ttml")
+        );
+        assert_eq!(find(r#"{"id": "", "title": "晴天"}"#), None);
+        assert_eq!(find(r#"{"title": "晴天"}"#), None);
+    }
+
+    #[test]
+    fn short_title_contained_in_another_is_not_a_match() {
+        let index = LyricIndex {
+            entries: vec![
+                entry("a.lrc", Some("Love Story"), Some("Taylor Swift"), None),
+                entry("b.lrc", Some("Home Sweet Home"), Some("Mötley Crüe"), None),
+                entry("c.lrc", Some("Love"), Some("Lana Del Rey"), None),
+            ],
+        };
+        let results = index.find(&TrackQuery {
+            title: "Love".to_string(),
+            artist: "Someone Else".to_string(),
+            ..Default::default()
+        });
+        let names: Vec<&str> = results.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["c.lrc"]);
+        assert!(index
+            .find(&TrackQuery {
+                title: "Home".to_string(),
+                artist: "Someone Else".to_string(),
+                ..Default::default()
+            })
+            .is_empty());
+
+        // A near-complete containment still scores high
+        assert!(similarity("晴天", "晴天2") >= 0.6);
+        assert!(similarity("love", "lovestory") < 0.6);
+    }
+}
@@ -0,0 +1,219 @@
+// src-tauri/src/lyric/merge.rs
+
+//! Merges separately delivered translation / romanization tracks (NetEase `tlyric`,
+//! `romalrc`) into the original lyric.
+//!
+//! Secondary lines are paired with primary lines whose start times are within a
+//! tolerance, closest pairs first, so each primary line takes at most one line per
+//! track. Lines that find no partner are reported instead of being dropped.
+
+use serde::{Deserialize, Serialize};
+
+use super::Lyric;
+
+/// Default maximum start time difference for two lines to be paired
+pub const DEFAULT_TOLERANCE_MS: u64 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TrackKind {
+    Translation,
+    Romanization,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnmatchedLine {
+    pub kind: TrackKind,
+    pub start_time: u64,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeResult {
+    pub lyric: Lyric,
+    /// Secondary lines with no primary line within tolerance
+    pub unmatched: Vec<UnmatchedLine>,
+}
+
+/// NetEase fills untranslated lines with `//`
+fn is_placeholder(text: &str) -> bool {
+    let text = text.trim();
+    text.is_empty() || text == "//"
+}
+
+/// Fills `kind` on the lines of `primary` from `secondary`; returns the lines that
+/// couldn't be placed. Background lines never receive secondary text.
+pub fn merge_track(
+    primary: &mut Lyric,
+    secondary: &Lyric,
+    kind: TrackKind,
+    tolerance_ms: u64,
+) -> Vec<UnmatchedLine> {
+    let texts: Vec<(u64, String)> = secondary
+        .lines
+        .iter()
+        .map(|l| (l.start_time, l.text().trim().to_string()))
+        .filter(|(_, text)| !is_placeholder(text))
+        .collect();
+
+    // Candidate pairs within tolerance; both sides are sorted, so a sliding
+    // window keeps this linear in practice
+    let mut pairs = Vec::new();
+    let mut first = 0;
+    for (si, &(start, _)) in texts.iter().enumerate() {
+        while first < primary.lines.len()
+            && primary.lines[first].start_time.saturating_add(tolerance_ms) < start
+        {
+            first += 1;
+        }
+        for (pi, line) in primary.lines.iter().enumerate().skip(first) {
+            if line.start_time > start.saturating_add(tolerance_ms) {
+                break;
+            }
+            if !line.is_bg {
+                pairs.push((line.start_time.abs_diff(start), pi, si));
+            }
+        }
+    }
+    pairs.sort_unstable();
+
+    let mut primary_taken = vec![false; primary.lines.len()];
+    let mut secondary_taken = vec![false; texts.len()];
+    for (_, pi, si) in pairs {
+        if primary_taken[pi] || secondary_taken[si] {
+            continue;
+        }
+        primary_taken[pi] = true;
+        secondary_taken[si] = true;
+        let line = &mut primary.lines[pi];
+        let text = texts[si].1.clone();
+        match kind {
+            TrackKind::Translation => line.translated_lyric = text,
+            TrackKind::Romanization => line.roman_lyric = text,
+        }
+    }
+
+    texts
+        .into_iter()
+        .zip(secondary_taken)
+        .filter(|(_, taken)| !taken)
+        .map(|((start_time, text), _)| UnmatchedLine {
+            kind,
+            start_time,
+            text,
+        })
+        .collect()
+}
+
+/// Merges the optional translation and romanization tracks into `primary`.
+pub fn merge_tracks(
+    mut primary: Lyric,
+    translation: Option<&Lyric>,
+    romanization: Option<&Lyric>,
+    tolerance_ms: u64,
+) -> MergeResult {
+    let mut unmatched = Vec::new();
+    for (track, kind) in [
+        (translation, TrackKind::Translation),
+        (romanization, TrackKind::Romanization),
+    ] {
+        if let Some(track) = track {
+            unmatched.extend(merge_track(&mut primary, track, kind, tolerance_ms));
+        }
+    }
+    MergeResult {
+        lyric: primary,
+        unmatched,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lyric::lrc::parse_lrc;
+    use crate::lyric::LyricLine;
+
+    const ORIGINAL: &str = "[00:01.00]Hello\n\
+        [00:04.00]Bright\n\
+        [00:08.00]World\n\
+        [00:12.00]Again\n";
+
+    #[test]
+    fn pairs_lines_within_tolerance_and_reports_the_rest() {
+        // Slightly off timestamps, a skipped line, a placeholder and a stray line
+        let translation = parse_lrc(
+            "[00:01.20]你好\n\
+             [00:03.90]明亮\n\
+             [00:08.00]//\n\
+             [00:20.00]多余\n",
+        );
+        let romanization = parse_lrc("[00:01.00]ni hao\n[00:12.00]zai lai\n");
+
+        let merged = merge_tracks(
+            parse_lrc(ORIGINAL),
+            Some(&translation),
+            Some(&romanization),
+            DEFAULT_TOLERANCE_MS,
+        );
+        let lines: Vec<(&str, &str)> = merged
+            .lyric
+            .lines
+            .iter()
+            .map(|l| (l.translated_lyric.as_str(), l.roman_lyric.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            [("你好", "ni hao"), ("明亮", ""), ("", ""), ("", "zai lai")]
+        );
+        assert_eq!(
+            merged.unmatched,
+            [UnmatchedLine {
+                kind: TrackKind::Translation,
+                start_time: 20_000,
+                text: "多余".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn closest_pair_wins_and_each_line_is_used_once() {
+        let mut primary = parse_lrc("[00:01.00]a\n[00:01.40]b\n");
+        // Both candidates are within tolerance of both lines
+        let translation = parse_lrc("[00:01.35]B\n[00:01.10]A\n[00:01.20]extra\n");
+        let unmatched = merge_track(&mut primary, &translation, TrackKind::Translation, 500);
+
+        assert_eq!(primary.lines[0].translated_lyric, "A");
+        assert_eq!(primary.lines[1].translated_lyric, "B");
+        assert_eq!(unmatched.len(), 1);
+        assert_eq!(unmatched[0].text, "extra");
+    }
+
+    #[test]
+    fn tolerance_is_inclusive() {
+        let mut primary = parse_lrc(ORIGINAL);
+        let translation = parse_lrc("[00:04.30]x\n[00:08.31]y\n");
+        let unmatched = merge_track(&mut primary, &translation, TrackKind::Translation, 300);
+        assert_eq!(primary.lines[1].translated_lyric, "x");
+        assert_eq!(unmatched.len(), 1);
+        assert_eq!(unmatched[0].start_time, 8_310);
+    }
+
+    #[test]
+    fn huge_times_and_tolerance_saturate() {
+        let mut primary = parse_lrc(ORIGINAL);
+        primary
+            .lines
+            .push(LyricLine::plain(u64::MAX - 1, u64::MAX, "end"));
+        let mut translation = parse_lrc("[00:01.00]x\n");
+        translation
+            .lines
+            .push(LyricLine::plain(u64::MAX, u64::MAX, "終"));
+
+        let unmatched = merge_track(&mut primary, &translation, TrackKind::Translation, u64::MAX);
+        assert!(unmatched.is_empty());
+        assert_eq!(primary.lines[0].translated_lyric, "x");
+        assert_eq!(primary.lines[4].translated_lyric, "終");
+    }
+}
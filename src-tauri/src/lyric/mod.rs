@@ -0,0 +1,152 @@
+// src-tauri/src/lyric/mod.rs
+
+//! Structured lyric model shared by all lyric parsers.
+//!
+//! The serialized shape mirrors `LyricLine` / `LyricWord` from
+//! `@applemusic-like-lyrics/lyric`, so the frontend can hand parsed lines straight to
+//! the lyric player without re-parsing. All times are in milliseconds.
+
+pub mod ass;
+pub mod commands;
+pub mod convert;
+pub mod embedded;
+pub mod encoding;
+pub mod hanzi;
+pub mod krc;
+pub mod lrc;
+pub mod matcher;
+pub mod merge;
+pub mod qrc;
+pub mod retime;
+pub mod scanner;
+pub mod search;
+pub mod srt;
+pub mod ttml;
+pub mod uri;
+mod word_line;
+pub mod yrc;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LyricWord {
+    pub start_time: u64,
+    pub end_time: u64,
+    pub word: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub roman_word: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LyricLine {
+    /// Word-level timing. Lines without it carry a single word spanning the line.
+    pub words: Vec<LyricWord>,
+    #[serde(default)]
+    pub translated_lyric: String,
+    #[serde(default)]
+    pub roman_lyric: String,
+    pub start_time: u64,
+    pub end_time: u64,
+    #[serde(default, rename = "isBG")]
+    pub is_bg: bool,
+    #[serde(default)]
+    pub is_duet: bool,
+}
+
+impl LyricLine {
+    /// A line without word timing.
+    pub fn plain(start_time: u64, end_time: u64, text: impl Into<String>) -> Self {
+        Self {
+            words: vec![LyricWord {
+                start_time,
+                end_time,
+                word: text.into(),
+                roman_word: String::new(),
+            }],
+            start_time,
+            end_time,
+            ..Default::default()
+        }
+    }
+
+    /// Full line text (all words joined).
+    pub fn text(&self) -> String {
+        self.words.iter().map(|w| w.word.as_str()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LyricMetadata {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    /// Declared track length (`[length:]`)
+    pub length: Option<u64>,
+    /// `[offset:]` in ms as found in the file; already applied to all line times
+    pub offset: i64,
+    /// Any other metadata: LRC header tags (`by`, `re`, ...) keyed in lowercase,
+    /// TTML `amll:meta` entries (`ncmMusicId`, `qqMusicId`, ...) and `songwriters`
+    pub extra: BTreeMap<String, Vec<String>>,
+    /// Language of the translations (TTML `xml:lang`), when the source says
+    pub translation_lang: Option<String>,
+}
+
+impl LyricMetadata {
+    /// First value of an `extra` entry.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.extra.get(key)?.first().map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Lyric {
+    pub metadata: LyricMetadata,
+    /// Sorted by `start_time`
+    pub lines: Vec<LyricLine>,
+    /// Whether the source carried real word-level timing
+    pub word_timed: bool,
+}
+
+/// File extensions picked up by lyric folder scans
+pub const LYRIC_EXTENSIONS: [&str; 5] = ["lrc", "ttml", "krc", "qrc", "yrc"];
+
+pub fn is_lyric_extension(ext: &str) -> bool {
+    LYRIC_EXTENSIONS.iter().any(|e| e.eq_ignore_ascii_case(ext))
+}
+
+/// Parses decoded lyric text, picking the format from the file extension.
+pub fn parse_by_extension(ext: &str, content: &str) -> Result<Lyric, String> {
+    match ext.to_ascii_lowercase().as_str() {
+        "ttml" | "xml" => ttml::parse_ttml(content),
+        "krc" => Ok(krc::parse_krc_text(content)),
+        "qrc" => Ok(qrc::parse_qrc(content)),
+        "yrc" => Ok(yrc::parse_yrc(content)),
+        "srt" => Ok(srt::parse_srt(content)),
+        "ass" | "ssa" => Ok(ass::parse_ass(content)),
+        _ => Ok(lrc::parse_lrc(content)),
+    }
+}
+
+/// Reads and parses a lyric file of any supported format. Encrypted KRC is
+/// decrypted and encrypted QRC rejected; everything else goes through charset
+/// detection first.
+pub fn read_lyric(path: &Path) -> Result<Lyric, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if bytes.starts_with(krc::KRC_MAGIC) {
+        return krc::parse_krc(&bytes);
+    }
+    if ext.eq_ignore_ascii_case("qrc") && qrc::is_encrypted(&bytes) {
+        return Err(qrc::ENCRYPTED_ERROR.to_string());
+    }
+    let decoded = encoding::decode_bytes(&bytes);
+    parse_by_extension(ext, &decoded.text)
+}
@@ -0,0 +1,130 @@
+// src-tauri/src/lyric/qrc.rs
+
+//! QQ Music QRC (decrypted form): an XML wrapper whose `LyricContent` attribute
+//! holds `[start,duration]word(start,duration)...` lines with absolute word times.
+//! Bare QRC text without the wrapper is accepted too.
+//!
+//! The attribute is extracted by hand rather than with an XML parser because real
+//! files often contain unescaped quotes inside it.
+//!
+//! Encrypted QRC (QQ Music's cache files and the hex strings its API returns) is
+//! not decrypted: that takes QQ Music's non-standard triple DES plus, for cache
+//! files, an XOR key from the client. [`is_encrypted`] recognizes it from the first
+//! bytes so scans can skip such files instead of indexing them as empty lyrics.
+
+use super::word_line::{self, QRC};
+use super::Lyric;
+
+/// Header of QQ Music's locally cached, encrypted QRC files
+const LOCAL_MAGIC: [u8; 11] = [
+    0x98, 0x25, 0xB0, 0xAC, 0xE3, 0x02, 0x83, 0x68, 0xE8, 0xFC, 0x6C,
+];
+
+/// How many leading bytes [`is_encrypted`] needs to decide
+pub const SNIFF_LEN: usize = 64;
+
+pub const ENCRYPTED_ERROR: &str = "Skipped: encrypted QRC (not supported)";
+
+/// Whether a QRC file starting with `head` is still encrypted: a QQ Music cache
+/// file, or the hex-encoded ciphertext the API returns.
+pub fn is_encrypted(head: &[u8]) -> bool {
+    if head.starts_with(&LOCAL_MAGIC) {
+        return true;
+    }
+    let head = head.trim_ascii();
+    let head = &head[..head.len().min(SNIFF_LEN)];
+    head.len() >= 32 && head.iter().all(u8::is_ascii_hexdigit)
+}
+
+pub fn parse_qrc(content: &str) -> Lyric {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let text = if content.trim_start().starts_with('<') {
+        extract_lyric_content(content).unwrap_or_default()
+    } else {
+        content.to_string()
+    };
+    let mut lyric = word_line::parse_document(&text, &QRC);
+    word_line::sort_lines(&mut lyric);
+    lyric
+}
+
+/// The first `LyricContent="..."` attribute value, unescaped.
+fn extract_lyric_content(xml: &str) -> Option<String> {
+    const ATTR: &str = "LyricContent=\"";
+    let start = xml.find(ATTR)? + ATTR.len();
+    let rest = &xml[start..];
+    // The value ends at the quote that closes the element, not at the first quote
+    let end = ["\"/>", "\" />", "\">"]
+        .iter()
+        .filter_map(|m| rest.find(m))
+        .min()?;
+    Some(unescape(&rest[..end]))
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&#10;", "\n")
+        .replace("&#13;", "\r")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_xml_wrapped_qrc() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<QrcInfos>
+<QrcHeadInfo SaveTime="1700000000" Version="100"/>
+<LyricInfo LyricCount="1">
+<Lyric_1 LyricType="1" LyricContent="[ti:Song]
+[ar:Singer]
+[offset:0]
+[1000,2000]Hello (1000,800)&quot;world&quot;(1800,1200)
+[3500,1500](ooh) (3500,700)yeah(4200,800)
+"/>
+</LyricInfo>
+</QrcInfos>"#;
+
+        let lyric = parse_qrc(xml);
+        assert_eq!(lyric.metadata.title.as_deref(), Some("Song"));
+        assert_eq!(lyric.lines.len(), 2);
+
+        let first = &lyric.lines[0];
+        assert_eq!(first.words.len(), 2);
+        assert_eq!(first.words[0].word, "Hello ");
+        assert_eq!(first.words[1].word, "\"world\"");
+        assert_eq!(
+            (first.words[1].start_time, first.words[1].end_time),
+            (1_800, 3_000)
+        );
+
+        // Parentheses that aren't timing tags stay part of the word
+        assert_eq!(lyric.lines[1].text(), "(ooh) yeah");
+    }
+
+    #[test]
+    fn recognizes_encrypted_qrc() {
+        let mut cache_file = LOCAL_MAGIC.to_vec();
+        cache_file.extend_from_slice(&[0x1f, 0x8b, 0x00, 0x7a]);
+        assert!(is_encrypted(&cache_file));
+        assert!(is_encrypted(
+            b"A0B1C2D3E4F5061728394A5B6C7D8E9FA0B1C2D3E4F5061728394A5B6C7D8E9F\n"
+        ));
+
+        assert!(!is_encrypted(b"<?xml version=\"1.0\" encoding=\"utf-8\"?>"));
+        assert!(!is_encrypted(b"[0,1000]a(0,500)b(500,500)"));
+        assert!(!is_encrypted(b"deadbeef"));
+    }
+
+    #[test]
+    fn parses_bare_qrc_text() {
+        let lyric = parse_qrc("[0,1000]a(0,500)b(500,500)\n");
+        assert_eq!(lyric.lines[0].text(), "ab");
+        assert_eq!(lyric.lines[0].end_time, 1_000);
+    }
+}
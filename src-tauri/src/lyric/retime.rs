@@ -0,0 +1,338 @@
+// src-tauri/src/lyric/retime.rs
+
+//! Retiming for lyrics that are off against the track being played: a constant
+//! shift, a linear stretch between two durations, or re-anchoring on two lines
+//! whose real times are known.
+//!
+//! Every operation is an affine map applied to line and word times alike, so word
+//! timing stays proportional within its line.
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::convert::{self, ConvertOptions, LyricFormat};
+use super::{lrc, Lyric, LyricMetadata};
+
+/// A line and the time it actually occurs at.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Anchor {
+    /// Index into `Lyric::lines`
+    pub line: usize,
+    pub time_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Retime {
+    /// Adds `offset_ms` to every timestamp (negative moves lyrics earlier)
+    Shift { offset_ms: i64 },
+    /// Maps `0..from_ms` onto `0..to_ms`
+    Stretch { from_ms: u64, to_ms: u64 },
+    /// Moves two lines to their real times, interpolating everything else
+    Reanchor { first: Anchor, second: Anchor },
+}
+
+/// `t' = origin' + (t - origin) * scale`
+struct Affine {
+    origin: f64,
+    target: f64,
+    scale: f64,
+}
+
+impl Affine {
+    fn apply(&self, t: u64) -> u64 {
+        let mapped = self.target + (t as f64 - self.origin) * self.scale;
+        mapped.round().max(0.0) as u64
+    }
+}
+
+fn affine(lyric: &Lyric, op: &Retime) -> Result<Affine, String> {
+    Ok(match *op {
+        Retime::Shift { offset_ms } => Affine {
+            origin: 0.0,
+            target: offset_ms as f64,
+            scale: 1.0,
+        },
+        Retime::Stretch { from_ms, to_ms } => {
+            if from_ms == 0 {
+                return Err("Stretch source duration must be positive".to_string());
+            }
+            Affine {
+                origin: 0.0,
+                target: 0.0,
+                scale: to_ms as f64 / from_ms as f64,
+            }
+        }
+        Retime::Reanchor { first, second } => {
+            let line_time = |anchor: Anchor| {
+                lyric
+                    .lines
+                    .get(anchor.line)
+                    .map(|l| l.start_time)
+                    .ok_or_else(|| format!("Anchor line {} out of range", anchor.line))
+            };
+            let (a, b) = (line_time(first)?, line_time(second)?);
+            if a == b {
+                return Err("Anchor lines must start at different times".to_string());
+            }
+            let scale = (second.time_ms as f64 - first.time_ms as f64) / (b as f64 - a as f64);
+            if scale <= 0.0 {
+                return Err("Anchors would reverse the line order".to_string());
+            }
+            Affine {
+                origin: a as f64,
+                target: first.time_ms as f64,
+                scale,
+            }
+        }
+    })
+}
+
+/// Applies `op` to every line and word time, and to `[length:]`.
+pub fn retime(lyric: &Lyric, op: &Retime) -> Result<Lyric, String> {
+    let map = affine(lyric, op)?;
+    let mut lyric = lyric.clone();
+    for line in &mut lyric.lines {
+        line.start_time = map.apply(line.start_time);
+        line.end_time = map.apply(line.end_time).max(line.start_time);
+        for w in &mut line.words {
+            w.start_time = map.apply(w.start_time);
+            w.end_time = map.apply(w.end_time).max(w.start_time);
+        }
+    }
+    if let (Some(length), Retime::Stretch { .. }) = (lyric.metadata.length, op) {
+        lyric.metadata.length = Some(map.apply(length));
+    }
+    Ok(lyric)
+}
+
+/// Parses `input`, retimes it and writes it back in the same format.
+pub fn retime_text(input: &str, format: LyricFormat, op: &Retime) -> Result<String, String> {
+    let lyric = retime(&convert::parse(input, format)?, op)?;
+    write_back(input, &lyric, format)
+}
+
+/// Retimes an LRC or TTML file in place. Word-timed LRC stays enhanced LRC.
+/// Returns the retimed lyric.
+pub fn retime_file(path: &Path, op: &Retime) -> Result<Lyric, String> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    if ext != "ttml" && ext != "lrc" {
+        return Err(format!("Cannot write back .{} lyrics", ext));
+    }
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let source = super::encoding::decode_bytes(&bytes).text;
+    let lyric = retime(&super::parse_by_extension(&ext, &source)?, op)?;
+    let format = match ext.as_str() {
+        "ttml" => LyricFormat::Ttml,
+        _ if lyric.word_timed => LyricFormat::EnhancedLrc,
+        _ => LyricFormat::Lrc,
+    };
+    let out = write_back(&source, &lyric, format)?;
+    super::encoding::rewrite_utf8(path, &out).map_err(|e| e.to_string())?;
+    Ok(lyric)
+}
+
+/// Writes `lyric` in `format`. LRC keeps the header lines of `source` (`[by:]`,
+/// `[re:]`, ...) verbatim and in order, except `[offset:]`, which is already
+/// applied to the times, and `[length:]`, which is written from the lyric.
+fn write_back(source: &str, lyric: &Lyric, format: LyricFormat) -> Result<String, String> {
+    let options = write_back_options(format);
+    if format == LyricFormat::Ttml {
+        return convert::export(lyric, format, &options);
+    }
+
+    let mut out = String::new();
+    for line in source.lines() {
+        if let Some((key, _)) = lrc::parse_header(line) {
+            if key != "offset" && key != "length" {
+                out.push_str(line.trim());
+                out.push('\n');
+            }
+        }
+    }
+    let body = Lyric {
+        metadata: LyricMetadata {
+            length: lyric.metadata.length,
+            ..Default::default()
+        },
+        ..lyric.clone()
+    };
+    out.push_str(&convert::export(&body, format, &options)?);
+    Ok(out)
+}
+
+fn write_back_options(format: LyricFormat) -> ConvertOptions {
+    match format {
+        // Parsed LRC keeps translation rows as lines of their own; writing them
+        // again as translations would duplicate them
+        LyricFormat::Lrc | LyricFormat::EnhancedLrc => ConvertOptions {
+            include_translation: false,
+            ..Default::default()
+        },
+        _ => ConvertOptions {
+            include_roman: true,
+            ..Default::default()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lyric::lrc::parse_lrc;
+
+    const ENHANCED: &str = "[ti:Drift]\n\
+        [00:10.00]<00:10.00>one <00:11.00>two <00:12.00>three<00:14.00>\n\
+        [00:20.00]<00:20.00>four <00:22.00>five<00:24.00>\n";
+
+    fn spans(lyric: &Lyric) -> Vec<(u64, u64)> {
+        lyric
+            .lines
+            .iter()
+            .flat_map(|l| l.words.iter().map(|w| (w.start_time, w.end_time)))
+            .collect()
+    }
+
+    #[test]
+    fn shift_moves_everything_and_clamps_at_zero() {
+        let lyric = parse_lrc(ENHANCED);
+        let shifted = retime(&lyric, &Retime::Shift { offset_ms: 1500 }).unwrap();
+        assert_eq!(shifted.lines[0].start_time, 11_500);
+        assert_eq!(spans(&shifted)[2], (13_500, 15_500));
+
+        let early = retime(&lyric, &Retime::Shift { offset_ms: -15_000 }).unwrap();
+        assert_eq!(early.lines[0].start_time, 0);
+        assert_eq!(early.lines[1].start_time, 5_000);
+    }
+
+    #[test]
+    fn stretch_scales_words_proportionally() {
+        let lyric = parse_lrc(ENHANCED);
+        let op = Retime::Stretch {
+            from_ms: 200_000,
+            to_ms: 210_000,
+        };
+        let stretched = retime(&lyric, &op).unwrap();
+        assert_eq!(
+            spans(&stretched),
+            [
+                (10_500, 11_550),
+                (11_550, 12_600),
+                (12_600, 14_700),
+                (21_000, 23_100),
+                (23_100, 25_200)
+            ]
+        );
+        assert!(retime(
+            &lyric,
+            &Retime::Stretch {
+                from_ms: 0,
+                to_ms: 1
+            }
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn reanchor_interpolates_between_marked_lines() {
+        let lyric = parse_lrc("[00:10.00]a\n[00:20.00]b\n[00:30.00]c\n[00:40.00]d\n");
+        let op = Retime::Reanchor {
+            first: Anchor {
+                line: 0,
+                time_ms: 12_000,
+            },
+            second: Anchor {
+                line: 3,
+                time_ms: 45_000,
+            },
+        };
+        let starts: Vec<u64> = retime(&lyric, &op)
+            .unwrap()
+            .lines
+            .iter()
+            .map(|l| l.start_time)
+            .collect();
+        assert_eq!(starts, [12_000, 23_000, 34_000, 45_000]);
+
+        let reversed = Retime::Reanchor {
+            first: Anchor {
+                line: 0,
+                time_ms: 45_000,
+            },
+            second: Anchor {
+                line: 3,
+                time_ms: 12_000,
+            },
+        };
+        assert!(retime(&lyric, &reversed).is_err());
+    }
+
+    #[test]
+    fn writes_back_lrc_and_ttml() {
+        let op = Retime::Shift { offset_ms: 250 };
+        let out = retime_text(ENHANCED, LyricFormat::EnhancedLrc, &op).unwrap();
+        assert!(out.contains("[00:10.25]<00:10.25>one <00:11.25>two <00:12.25>three<00:14.25>"));
+        assert!(out.contains("[ti:Drift]"));
+
+        let ttml = convert::export(
+            &parse_lrc(ENHANCED),
+            LyricFormat::Ttml,
+            &ConvertOptions::default(),
+        )
+        .unwrap();
+        let out = retime_text(&ttml, LyricFormat::Ttml, &op).unwrap();
+        let reparsed = convert::parse(&out, LyricFormat::Ttml).unwrap();
+        assert_eq!(reparsed.lines[1].words[1].start_time, 22_250);
+    }
+
+    #[test]
+    fn keeps_lrc_header_lines() {
+        let dir = std::env::temp_dir().join(format!("splayer-retime-hdr-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("song.lrc");
+        let source = "[ti:Drift]\n[ar:Someone]\n[by:lrc maker]\n[re:Some Editor]\n[ve:1.0]\n\
+                      [offset:500]\n[00:01.50]a\n[00:02.50]b\n";
+        fs::write(&path, source).unwrap();
+
+        retime_file(&path, &Retime::Shift { offset_ms: 0 }).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with(
+            "[ti:Drift]\n[ar:Someone]\n[by:lrc maker]\n[re:Some Editor]\n[ve:1.0]\n\
+             [00:01.00]a\n[00:02.00]b\n"
+        ));
+
+        // Retiming again round-trips through the written file unchanged
+        retime_file(&path, &Retime::Shift { offset_ms: 0 }).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), written);
+        let reparsed = crate::lyric::read_lyric(&path).unwrap();
+        assert_eq!(reparsed.metadata.extra["by"], ["lrc maker"]);
+        assert_eq!(reparsed.metadata.extra["re"], ["Some Editor"]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn retimes_files_in_place() {
+        let dir = std::env::temp_dir().join(format!("splayer-retime-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("song.lrc");
+        fs::write(&path, "[00:01.00]a\n[00:02.00]b\n").unwrap();
+
+        retime_file(&path, &Retime::Shift { offset_ms: 1000 }).unwrap();
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with("[00:02.00]a\n[00:03.00]b\n"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
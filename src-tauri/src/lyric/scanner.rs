@@ -0,0 +1,345 @@
+// src-tauri/src/lyric/scanner.rs
+
+//! Incremental lyric folder scanner.
+//!
+//! Walks a folder for lyric files with a depth limit and cycle detection (symlinked
+//! directories are resolved and visited at most once). Subdirectories containing
+//! `.nomedia` are skipped; the chosen root itself is always scanned. Parsed metadata
+//! is cached per file by mtime + size, so unchanged files aren't re-read. Problems
+//! are reported per path instead of aborting the scan. Encrypted QRC files can't be
+//! parsed, so they are reported and left out of the results.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+
+use super::LyricMetadata;
+
+pub const DEFAULT_MAX_DEPTH: usize = 12;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LyricFile {
+    pub name: String,
+    pub path: String,
+    /// Parsed metadata (title, artist, `ncmMusicId`, `qqMusicId`, ...); `None` if unparsable
+    pub metadata: Option<LyricMetadata>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanError {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanResult {
+    pub files: Vec<LyricFile>,
+    pub errors: Vec<ScanError>,
+    /// Files whose cached metadata was reused
+    pub reused: usize,
+    /// Files (re)parsed in this scan
+    pub parsed: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedFile {
+    mtime_ms: u64,
+    size: u64,
+    metadata: Option<LyricMetadata>,
+}
+
+/// Per-file metadata cache, persisted as JSON.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScanCache {
+    files: BTreeMap<String, CachedFile>,
+}
+
+impl ScanCache {
+    /// Loads the cache; a missing or corrupt file yields an empty cache.
+    pub fn load(path: &Path) -> Self {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the cache (write to a temp file, then rename).
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string(self).map_err(std::io::Error::other)?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, json)?;
+        fs::rename(&tmp, path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+struct Scanner<'a> {
+    cache: &'a mut ScanCache,
+    max_depth: usize,
+    visited: HashSet<PathBuf>,
+    seen: HashSet<String>,
+    result: ScanResult,
+}
+
+/// Scans `root` for lyric files, reusing and updating `cache`.
+/// Cache entries under `root` that no longer exist are dropped.
+pub fn scan_dir(root: &Path, cache: &mut ScanCache, max_depth: usize) -> ScanResult {
+    let mut scanner = Scanner {
+        cache,
+        max_depth,
+        visited: HashSet::new(),
+        seen: HashSet::new(),
+        result: ScanResult::default(),
+    };
+    scanner.visit(root, 0);
+
+    let seen = scanner.seen;
+    scanner
+        .cache
+        .files
+        .retain(|path, _| !Path::new(path).starts_with(root) || seen.contains(path));
+
+    let mut result = scanner.result;
+    result.files.sort_by(|a, b| a.path.cmp(&b.path));
+    result
+}
+
+impl Scanner<'_> {
+    fn error(&mut self, path: &Path, message: impl ToString) {
+        self.result.errors.push(ScanError {
+            path: path.to_string_lossy().into_owned(),
+            message: message.to_string(),
+        });
+    }
+
+    fn visit(&mut self, dir: &Path, depth: usize) {
+        // Resolve symlinks so a link back up the tree is recognized as visited
+        let canonical = match fs::canonicalize(dir) {
+            Ok(c) => c,
+            Err(e) => return self.error(dir, e),
+        };
+        if !self.visited.insert(canonical) {
+            return;
+        }
+        if depth > 0 && dir.join(".nomedia").exists() {
+            return;
+        }
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => return self.error(dir, e),
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.error(dir, e);
+                    continue;
+                }
+            };
+            let path = entry.path();
+            // Follows symlinks; a dangling link is reported rather than skipped silently
+            let meta = match fs::metadata(&path) {
+                Ok(meta) => meta,
+                Err(e) => {
+                    self.error(&path, e);
+                    continue;
+                }
+            };
+
+            if meta.is_dir() {
+                if depth + 1 > self.max_depth {
+                    self.error(
+                        &path,
+                        format!("Skipped: deeper than {} levels", self.max_depth),
+                    );
+                } else {
+                    self.visit(&path, depth + 1);
+                }
+            } else if meta.is_file() {
+                let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+                if !super::is_lyric_extension(ext) {
+                    continue;
+                }
+                if ext.eq_ignore_ascii_case("qrc") && is_encrypted_qrc(&path) {
+                    self.error(&path, super::qrc::ENCRYPTED_ERROR);
+                    continue;
+                }
+                self.index_file(&path, &meta);
+            }
+        }
+    }
+
+    fn index_file(&mut self, path: &Path, meta: &fs::Metadata) {
+        let key = path.to_string_lossy().into_owned();
+        let mtime_ms = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_millis() as u64);
+        let size = meta.len();
+
+        let metadata = match self.cache.files.get(&key) {
+            Some(cached) if cached.mtime_ms == mtime_ms && cached.size == size => {
+                self.result.reused += 1;
+                cached.metadata.clone()
+            }
+            _ => {
+                self.result.parsed += 1;
+                let metadata = match super::read_lyric(path) {
+                    Ok(lyric) => Some(lyric.metadata),
+                    Err(e) => {
+                        self.error(path, e);
+                        None
+                    }
+                };
+                self.cache.files.insert(
+                    key.clone(),
+                    CachedFile {
+                        mtime_ms,
+                        size,
+                        metadata: metadata.clone(),
+                    },
+                );
+                metadata
+            }
+        };
+
+        self.seen.insert(key.clone());
+        self.result.files.push(LyricFile {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            path: key,
+            metadata,
+        });
+    }
+}
+
+/// Encrypted QRC can't be parsed; they are left out of the scan (see `qrc`).
+fn is_encrypted_qrc(path: &Path) -> bool {
+    let mut head = Vec::with_capacity(super::qrc::SNIFF_LEN);
+    fs::File::open(path)
+        .and_then(|f| f.take(super::qrc::SNIFF_LEN as u64).read_to_end(&mut head))
+        .is_ok_and(|_| super::qrc::is_encrypted(&head))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("splayer-scan-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn scans_incrementally_and_reports_errors() {
+        let root = temp_dir("incremental");
+        fs::write(root.join("a.lrc"), "[ti:A]\n[00:01.00]a\n").unwrap();
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub/b.ttml"), "<tt><body><p>").unwrap();
+        fs::write(root.join("sub/notes.txt"), "ignored").unwrap();
+        fs::create_dir_all(root.join("hidden")).unwrap();
+        fs::write(root.join("hidden/.nomedia"), "").unwrap();
+        fs::write(root.join("hidden/c.lrc"), "[00:01.00]c\n").unwrap();
+
+        let mut cache = ScanCache::default();
+        let first = scan_dir(&root, &mut cache, DEFAULT_MAX_DEPTH);
+        let names: Vec<&str> = first.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["a.lrc", "b.ttml"]);
+        assert_eq!(first.parsed, 2);
+        assert_eq!(
+            first.files[0].metadata.as_ref().unwrap().title.as_deref(),
+            Some("A")
+        );
+        // Broken TTML is listed, with its error reported
+        assert!(first.files[1].metadata.is_none());
+        assert_eq!(first.errors.len(), 1);
+        assert!(first.errors[0].path.ends_with("b.ttml"));
+
+        // Round trip through disk, then rescan: nothing re-parsed
+        let cache_path = root.join("cache/scan.json");
+        cache.save(&cache_path).unwrap();
+        let mut cache = ScanCache::load(&cache_path);
+        let second = scan_dir(&root, &mut cache, DEFAULT_MAX_DEPTH);
+        assert_eq!((second.reused, second.parsed), (2, 0));
+
+        // Changed and deleted files are picked up
+        fs::write(root.join("a.lrc"), "[ti:A2]\n[00:01.00]a\n[00:02.00]b\n").unwrap();
+        fs::remove_file(root.join("sub/b.ttml")).unwrap();
+        let third = scan_dir(&root, &mut cache, DEFAULT_MAX_DEPTH);
+        assert_eq!((third.reused, third.parsed), (0, 1));
+        assert_eq!(
+            third.files[0].metadata.as_ref().unwrap().title.as_deref(),
+            Some("A2")
+        );
+        assert_eq!(cache.len(), 1);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn leaves_out_encrypted_qrc() {
+        let root = temp_dir("qrc");
+        fs::write(root.join("plain.qrc"), "[0,1000]a(0,500)b(500,500)\n").unwrap();
+        fs::write(root.join("encrypted.qrc"), "0123456789ABCDEF".repeat(8)).unwrap();
+
+        let mut cache = ScanCache::default();
+        let result = scan_dir(&root, &mut cache, DEFAULT_MAX_DEPTH);
+        let names: Vec<&str> = result.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["plain.qrc"]);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.errors[0].path.ends_with("encrypted.qrc"));
+        assert_eq!(cache.len(), 1);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn depth_limit_is_reported() {
+        let root = temp_dir("depth");
+        let deep = root.join("1/2/3");
+        fs::create_dir_all(&deep).unwrap();
+        fs::write(root.join("1/x.lrc"), "[00:01.00]x\n").unwrap();
+        fs::write(deep.join("y.lrc"), "[00:01.00]y\n").unwrap();
+
+        let result = scan_dir(&root, &mut ScanCache::default(), 1);
+        assert_eq!(result.files.len(), 1);
+        assert!(result.errors[0].message.contains("deeper than 1"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycles_terminate() {
+        let root = temp_dir("cycle");
+        fs::create_dir_all(root.join("a")).unwrap();
+        fs::write(root.join("a/x.lrc"), "[00:01.00]x\n").unwrap();
+        std::os::unix::fs::symlink(&root, root.join("a/loop")).unwrap();
+
+        let result = scan_dir(&root, &mut ScanCache::default(), 64);
+        assert_eq!(result.files.len(), 1);
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
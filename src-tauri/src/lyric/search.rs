@@ -0,0 +1,313 @@
+// src-tauri/src/lyric/search.rs
+
+//! Full-text search over local lyric files.
+//!
+//! Lines are folded before indexing: case and full-width forms, traditional
+//! Chinese mapped to simplified, punctuation and spaces dropped. Candidate lines
+//! come from a character-bigram index and are confirmed by a substring check on
+//! the folded text.
+//!
+//! The scan cache only holds metadata, so line text is read from the files
+//! themselves when the index is first searched after a scan.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::scanner::LyricFile;
+use super::{hanzi, matcher, Lyric};
+
+pub const DEFAULT_LIMIT: usize = 50;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub path: String,
+    pub name: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    /// Index of the matched line in the parsed lyric
+    pub line: usize,
+    /// Start of the matched line in ms, to play from
+    pub start_time: u64,
+    /// The matched line as written in the file
+    pub text: String,
+}
+
+struct IndexedFile {
+    path: String,
+    name: String,
+    title: Option<String>,
+    artist: Option<String>,
+}
+
+struct IndexedLine {
+    file: usize,
+    line: usize,
+    start_time: u64,
+    text: String,
+    folded: String,
+}
+
+#[derive(Default)]
+pub struct LyricSearchIndex {
+    files: Vec<IndexedFile>,
+    lines: Vec<IndexedLine>,
+    /// Folded character bigram → ids of the lines containing it, ascending
+    bigrams: HashMap<(char, char), Vec<usize>>,
+}
+
+/// Search form of a text: folded case/width/script, letters and digits only.
+pub fn fold(text: &str) -> String {
+    let kept: String = text
+        .chars()
+        .map(matcher::fold_width)
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+    hanzi::to_simplified(&kept)
+        .chars()
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn bigrams(folded: &str) -> Vec<(char, char)> {
+    let chars: Vec<char> = folded.chars().collect();
+    let mut grams: Vec<(char, char)> = chars.windows(2).map(|w| (w[0], w[1])).collect();
+    grams.sort_unstable();
+    grams.dedup();
+    grams
+}
+
+impl LyricSearchIndex {
+    /// Reads and indexes `files`; files that failed to parse during the scan are
+    /// skipped.
+    pub fn build(files: &[LyricFile]) -> Self {
+        let mut index = Self::default();
+        for file in files.iter().filter(|f| f.metadata.is_some()) {
+            match super::read_lyric(Path::new(&file.path)) {
+                Ok(lyric) => index.add(file, &lyric),
+                Err(e) => log::warn!("Failed to index lyric {}: {}", file.path, e),
+            }
+        }
+        index
+    }
+
+    pub fn add(&mut self, file: &LyricFile, lyric: &Lyric) {
+        let file_id = self.files.len();
+        let metadata = file.metadata.as_ref();
+        self.files.push(IndexedFile {
+            path: file.path.clone(),
+            name: file.name.clone(),
+            title: metadata.and_then(|m| m.title.clone()),
+            artist: metadata.and_then(|m| m.artist.clone()),
+        });
+
+        for (i, line) in lyric.lines.iter().enumerate() {
+            let text = line.text().trim().to_string();
+            let folded = fold(&text);
+            if folded.is_empty() {
+                continue;
+            }
+            let id = self.lines.len();
+            for gram in bigrams(&folded) {
+                self.bigrams.entry(gram).or_default().push(id);
+            }
+            self.lines.push(IndexedLine {
+                file: file_id,
+                line: i,
+                start_time: line.start_time,
+                text,
+                folded,
+            });
+        }
+    }
+
+    /// Number of indexed files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Ids of lines that contain every bigram of `folded`, ascending.
+    fn candidates(&self, folded: &str) -> Vec<usize> {
+        let grams = bigrams(folded);
+        if grams.is_empty() {
+            // Single character: no bigram to look up
+            return (0..self.lines.len()).collect();
+        }
+        let mut postings = Vec::with_capacity(grams.len());
+        for gram in &grams {
+            match self.bigrams.get(gram) {
+                Some(ids) => postings.push(ids),
+                None => return Vec::new(),
+            }
+        }
+        postings.sort_by_key(|ids| ids.len());
+        let mut ids = postings[0].clone();
+        for other in &postings[1..] {
+            ids.retain(|id| other.binary_search(id).is_ok());
+        }
+        ids
+    }
+
+    /// Files with a line containing `query`, one hit per file. Whole-line
+    /// matches rank before prefix matches, which rank before the rest; ties
+    /// keep file order and the earliest line.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query = fold(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let mut best: HashMap<usize, (u8, usize)> = HashMap::new();
+        for id in self.candidates(&query) {
+            let line = &self.lines[id];
+            let rank = if line.folded == query {
+                0
+            } else if line.folded.starts_with(&query) {
+                1
+            } else if line.folded.contains(&query) {
+                2
+            } else {
+                continue;
+            };
+            best.entry(line.file)
+                .and_modify(|b| *b = (*b).min((rank, id)))
+                .or_insert((rank, id));
+        }
+
+        let mut ranked: Vec<(u8, usize)> = best.into_values().collect();
+        ranked.sort_unstable();
+        ranked
+            .into_iter()
+            .take(limit)
+            .map(|(_, id)| {
+                let line = &self.lines[id];
+                let file = &self.files[line.file];
+                SearchHit {
+                    path: file.path.clone(),
+                    name: file.name.clone(),
+                    title: file.title.clone(),
+                    artist: file.artist.clone(),
+                    line: line.line,
+                    start_time: line.start_time,
+                    text: line.text.clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The files of the last scan, indexed on first search.
+#[derive(Default)]
+pub struct LazySearchIndex {
+    files: Vec<LyricFile>,
+    index: Option<LyricSearchIndex>,
+}
+
+impl LazySearchIndex {
+    pub fn new(files: Vec<LyricFile>) -> Self {
+        Self { files, index: None }
+    }
+
+    /// The index, built from the files on the first call.
+    pub fn get(&mut self) -> &LyricSearchIndex {
+        self.index
+            .get_or_insert_with(|| LyricSearchIndex::build(&self.files))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lyric::lrc::parse_lrc;
+    use crate::lyric::scanner::{self, ScanCache};
+    use std::fs;
+
+    fn index() -> LyricSearchIndex {
+        let mut index = LyricSearchIndex::default();
+        for (name, lrc) in [
+            (
+                "qingtian.lrc",
+                "[ti:晴天]\n[00:29.00]故事的小黃花\n[00:33.00]從出生那年就飄著\n[01:40.00]故事的小黃花\n",
+            ),
+            (
+                "other.lrc",
+                "[00:05.00]Don't stop me now\n[00:12.00]小黄花开了\n",
+            ),
+            ("exact.lrc", "[00:07.00]小黄花\n"),
+        ] {
+            let lyric = parse_lrc(lrc);
+            let file = LyricFile {
+                name: name.to_string(),
+                path: format!("/l/{}", name),
+                metadata: Some(lyric.metadata.clone()),
+            };
+            index.add(&file, &lyric);
+        }
+        index
+    }
+
+    #[test]
+    fn folds_script_width_case_and_punctuation() {
+        assert_eq!(fold("從出生那年，就飄著！"), "从出生那年就飘着");
+        assert_eq!(fold("ＤＯＮ'T  Stop"), "dontstop");
+    }
+
+    #[test]
+    fn finds_lines_across_scripts_and_ranks_whole_line_matches_first() {
+        let index = index();
+        let hits = index.search("小黃花", DEFAULT_LIMIT);
+        let files: Vec<&str> = hits.iter().map(|h| h.name.as_str()).collect();
+        assert_eq!(files, ["exact.lrc", "other.lrc", "qingtian.lrc"]);
+
+        // One hit per file: the first of the repeated chorus lines
+        let qingtian = &hits[2];
+        assert_eq!((qingtian.line, qingtian.start_time), (0, 29_000));
+        assert_eq!(qingtian.text, "故事的小黃花");
+        assert_eq!(qingtian.title.as_deref(), Some("晴天"));
+
+        let hits = index.search("从出生那年", DEFAULT_LIMIT);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].start_time, 33_000);
+    }
+
+    #[test]
+    fn latin_queries_ignore_case_and_spacing() {
+        let index = index();
+        let hits = index.search("STOP me", DEFAULT_LIMIT);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].text, "Don't stop me now");
+
+        assert!(index.search("stop you", DEFAULT_LIMIT).is_empty());
+        assert!(index.search("，", DEFAULT_LIMIT).is_empty());
+        assert_eq!(index.search("花", 2).len(), 2);
+    }
+
+    #[test]
+    fn lazy_index_reads_scanned_files_on_first_search() {
+        let root = std::env::temp_dir().join(format!("splayer-search-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("a.lrc"), "[ti:A]\n[00:01.00]故事的小黃花\n").unwrap();
+        fs::write(root.join("broken.ttml"), "<tt><body><p>").unwrap();
+
+        let scanned = scanner::scan_dir(&root, &mut ScanCache::default(), 1);
+        let mut lazy = LazySearchIndex::new(scanned.files);
+        // Files are only read now, so a later edit is what gets indexed
+        fs::write(root.join("a.lrc"), "[ti:A]\n[00:02.00]小黄花\n").unwrap();
+        let index = lazy.get();
+        assert_eq!(index.len(), 1);
+        let hits = index.search("小黃花", DEFAULT_LIMIT);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            (hits[0].start_time, hits[0].text.as_str()),
+            (2_000, "小黄花")
+        );
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
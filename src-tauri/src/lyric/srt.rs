@@ -0,0 +1,105 @@
+// src-tauri/src/lyric/srt.rs
+
+//! SubRip (`.srt`). Each cue is one line; the first text row is the lyric, a
+//! second row the translation and a third the romanization, matching what
+//! `write_srt` produces.
+
+use super::convert::ConvertOptions;
+use super::{Lyric, LyricLine};
+
+/// Parses `hh:mm:ss,mmm` (a `.` separator is accepted too).
+fn parse_srt_time(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let (hms, ms) = s.split_once([',', '.']).unwrap_or((s, "0"));
+    let mut parts = hms.split(':');
+    let h: u64 = parts.next()?.trim().parse().ok()?;
+    let m: u64 = parts.next()?.trim().parse().ok()?;
+    let sec: u64 = parts.next()?.trim().parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let ms: u64 = ms.trim().parse().ok()?;
+    h.checked_mul(3_600_000)?
+        .checked_add(m.checked_mul(60_000)?)?
+        .checked_add(sec.checked_mul(1000)?)?
+        .checked_add(ms)
+}
+
+fn format_srt_time(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+pub fn parse_srt(content: &str) -> Lyric {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut lines = Vec::new();
+    let mut rows = content.lines().peekable();
+    // Only ASCII whitespace counts, so the U+3000 placeholder row isn't a cue break
+    let is_blank = |r: &&str| r.trim_matches(|c: char| c.is_ascii_whitespace()).is_empty();
+
+    while rows.peek().is_some() {
+        // One cue: rows up to the next blank row
+        let cue: Vec<&str> = rows.by_ref().take_while(|r| !is_blank(r)).collect();
+        let Some(timing) = cue.iter().position(|r| r.contains("-->")) else {
+            continue;
+        };
+        let Some((start, end)) = cue[timing].split_once("-->") else {
+            continue;
+        };
+        // Position hints (`X1:...`) may follow the end time
+        let end = end.split_whitespace().next().unwrap_or("");
+        let (Some(start), Some(end)) = (parse_srt_time(start), parse_srt_time(end)) else {
+            continue;
+        };
+
+        let text = &cue[timing + 1..];
+        let Some(first) = text.first().filter(|t| !t.trim().is_empty()) else {
+            continue;
+        };
+        let mut line = LyricLine::plain(start, end.max(start), first.trim());
+        line.translated_lyric = text.get(1).map_or("", |t| t.trim()).to_string();
+        line.roman_lyric = text.get(2).map_or("", |t| t.trim()).to_string();
+        lines.push(line);
+    }
+
+    lines.sort_by_key(|l| l.start_time);
+    Lyric {
+        lines,
+        ..Default::default()
+    }
+}
+
+pub fn write_srt(lyric: &Lyric, options: &ConvertOptions) -> String {
+    let mut out = String::new();
+    for (i, line) in lyric.lines.iter().enumerate() {
+        out.push_str(&format!(
+            "{}\n{} --> {}\n{}\n",
+            i + 1,
+            format_srt_time(line.start_time),
+            format_srt_time(line.end_time),
+            line.text().trim()
+        ));
+        let translation = options.include_translation && !line.translated_lyric.is_empty();
+        let roman = options.include_roman && !line.roman_lyric.is_empty();
+        if translation || roman {
+            // Keep the row position meaningful when only romanization is included
+            out.push_str(if translation {
+                &line.translated_lyric
+            } else {
+                "　"
+            });
+            out.push('\n');
+        }
+        if roman {
+            out.push_str(&line.roman_lyric);
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
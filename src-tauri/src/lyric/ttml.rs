@@ -0,0 +1,563 @@
+// src-tauri/src/lyric/ttml.rs
+
+//! TTML parser for AMLL / Apple Music style lyrics.
+//!
+//! `<p>` elements become lines and timed `<span>`s become words. Spans with
+//! `ttm:role="x-bg"` become a separate background line right after their parent.
+//! `x-translation` / `x-roman` spans fill the line's translation and romanization.
+//! Head metadata (`amll:meta`, `ttm:agent`, `songwriters`) lands in
+//! `LyricMetadata`, with `amll:meta` keys kept verbatim in `extra`.
+
+use roxmltree::{Document, Node};
+
+use super::convert::ConvertOptions;
+use super::{Lyric, LyricLine, LyricMetadata, LyricWord};
+
+/// Parses `hh:mm:ss.fff`, `mm:ss.fff`, `ss.fff` and `12.5s` clock values into ms.
+pub fn parse_ttml_time(s: &str) -> Option<u64> {
+    let s = s.trim();
+    if let Some(secs) = s.strip_suffix("ms") {
+        return secs
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|v| *v >= 0.0)
+            .map(|v| v as u64);
+    }
+    if let Some(secs) = s.strip_suffix('s') {
+        return secs_to_ms(secs.trim());
+    }
+
+    let mut total = 0u64;
+    let mut parts = s.rsplit(':');
+    let secs = secs_to_ms(parts.next()?)?;
+    for (i, part) in parts.enumerate() {
+        if i > 1 {
+            return None;
+        }
+        let v: u64 = part.trim().parse().ok()?;
+        let unit = if i == 0 { 60_000 } else { 3_600_000 };
+        total = total.checked_add(v.checked_mul(unit)?)?;
+    }
+    total.checked_add(secs)
+}
+
+fn secs_to_ms(s: &str) -> Option<u64> {
+    let (whole, frac) = s.split_once('.').unwrap_or((s, ""));
+    if whole.is_empty() || !whole.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let frac_ms: u64 = if frac.is_empty() {
+        0
+    } else {
+        frac.chars()
+            .chain("00".chars())
+            .take(3)
+            .collect::<String>()
+            .parse()
+            .ok()?
+    };
+    whole
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(1000)?
+        .checked_add(frac_ms)
+}
+
+/// Attribute lookup by local name, ignoring namespaces (`ttm:role`, `xml:id`, ...).
+fn attr<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|a| a.name() == name)
+        .map(|a| a.value())
+}
+
+fn is(node: Node, name: &str) -> bool {
+    node.is_element() && node.tag_name().name() == name
+}
+
+/// Concatenated text of a node and its descendants.
+fn text_of(node: Node) -> String {
+    node.descendants()
+        .filter(|n| n.is_text())
+        .filter_map(|n| n.text())
+        .collect()
+}
+
+pub fn parse_ttml(content: &str) -> Result<Lyric, String> {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let doc = Document::parse(content).map_err(|e| format!("Invalid TTML: {}", e))?;
+    let root = doc.root_element();
+    if !is(root, "tt") {
+        return Err("Invalid TTML: root element is not <tt>".to_string());
+    }
+
+    let (metadata, main_agent) = match root.children().find(|n| is(*n, "head")) {
+        Some(head) => parse_head(head),
+        None => (LyricMetadata::default(), None),
+    };
+    let main_agent = main_agent.unwrap_or_else(|| "v1".to_string());
+
+    // Each `<p>` with its background line, ordered by the main line's start so
+    // a background line that starts early still follows its parent
+    let mut groups: Vec<(u64, Vec<LyricLine>)> = Vec::new();
+    let mut word_timed = false;
+    let mut metadata = metadata;
+    if let Some(body) = root.children().find(|n| is(*n, "body")) {
+        for p in body.descendants().filter(|n| is(*n, "p")) {
+            let agent = attr(p, "agent");
+            let is_duet = agent.is_some_and(|a| a != main_agent);
+            let (line, bg, timed) = parse_line(p, is_duet);
+            word_timed |= timed;
+            let group: Vec<LyricLine> = line.into_iter().chain(bg).collect();
+            if let Some(first) = group.first() {
+                groups.push((first.start_time, group));
+            }
+        }
+        metadata.translation_lang = body
+            .descendants()
+            .find(|n| is(*n, "span") && attr(*n, "role") == Some("x-translation"))
+            .and_then(|n| attr(n, "lang"))
+            .map(str::to_string);
+    }
+    groups.sort_by_key(|(start, _)| *start);
+    let lines = groups.into_iter().flat_map(|(_, group)| group).collect();
+
+    Ok(Lyric {
+        metadata,
+        lines,
+        word_timed,
+    })
+}
+
+/// Head metadata and the id of the main (first `person`) agent.
+fn parse_head(head: Node) -> (LyricMetadata, Option<String>) {
+    let mut metadata = LyricMetadata::default();
+    let mut main_agent = None;
+    let mut first_agent = None;
+
+    for node in head.descendants().filter(Node::is_element) {
+        match node.tag_name().name() {
+            "meta" => {
+                let (Some(key), Some(value)) = (attr(node, "key"), attr(node, "value")) else {
+                    continue;
+                };
+                match key {
+                    "musicName" if metadata.title.is_none() => {
+                        metadata.title = Some(value.to_string())
+                    }
+                    "artists" if metadata.artist.is_none() => {
+                        metadata.artist = Some(value.to_string())
+                    }
+                    "album" if metadata.album.is_none() => metadata.album = Some(value.to_string()),
+                    _ => {}
+                }
+                metadata
+                    .extra
+                    .entry(key.to_string())
+                    .or_default()
+                    .push(value.to_string());
+            }
+            "agent" => {
+                let Some(id) = attr(node, "id") else {
+                    continue;
+                };
+                first_agent.get_or_insert_with(|| id.to_string());
+                if main_agent.is_none() && attr(node, "type") == Some("person") {
+                    main_agent = Some(id.to_string());
+                }
+            }
+            "songwriter" => {
+                let name = text_of(node).trim().to_string();
+                if !name.is_empty() {
+                    metadata
+                        .extra
+                        .entry("songwriters".to_string())
+                        .or_default()
+                        .push(name);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    (metadata, main_agent.or(first_agent))
+}
+
+/// Returns the main line, its background line, and whether either had word timing.
+fn parse_line(p: Node, is_duet: bool) -> (Option<LyricLine>, Option<LyricLine>, bool) {
+    let mut main = LineBuilder::default();
+    let mut bg = LineBuilder::default();
+
+    for child in p.children() {
+        main.push_node(child, &mut bg);
+    }
+
+    let begin = attr(p, "begin").and_then(parse_ttml_time);
+    let end = attr(p, "end").and_then(parse_ttml_time);
+    let timed = main.timed || bg.timed;
+
+    let mut line = main.finish(begin, end);
+    if let Some(l) = &mut line {
+        l.is_duet = is_duet;
+    }
+    let mut bg_line = bg.finish(None, None);
+    if let Some(l) = &mut bg_line {
+        l.is_bg = true;
+        l.is_duet = is_duet;
+    }
+    (line, bg_line, timed)
+}
+
+#[derive(Default)]
+struct LineBuilder {
+    words: Vec<LyricWord>,
+    /// Untimed text directly inside the line (line-level timing only)
+    loose_text: String,
+    translation: String,
+    roman: String,
+    timed: bool,
+}
+
+impl LineBuilder {
+    fn push_node(&mut self, node: Node, bg: &mut LineBuilder) {
+        if node.is_text() {
+            let text = node.text().unwrap_or_default();
+            if text.trim().is_empty() {
+                // Whitespace between spans separates words
+                if !text.is_empty() {
+                    if let Some(last) = self.words.last_mut() {
+                        if !last.word.ends_with(' ') {
+                            last.word.push(' ');
+                        }
+                    }
+                }
+            } else {
+                self.loose_text.push_str(text);
+            }
+            return;
+        }
+        if !is(node, "span") {
+            return;
+        }
+
+        match attr(node, "role") {
+            Some("x-translation") => append_text(&mut self.translation, node),
+            Some("x-roman") => append_text(&mut self.roman, node),
+            Some("x-bg") => {
+                for child in node.children() {
+                    // Nested background spans are flattened into the same bg line
+                    bg.push_node(child, &mut LineBuilder::default());
+                }
+            }
+            _ => {
+                let begin = attr(node, "begin").and_then(parse_ttml_time);
+                let end = attr(node, "end").and_then(parse_ttml_time);
+                let text = text_of(node);
+                match (begin, end) {
+                    (Some(b), Some(e)) => {
+                        self.timed = true;
+                        self.words.push(LyricWord {
+                            start_time: b,
+                            end_time: e.max(b),
+                            word: text,
+                            roman_word: String::new(),
+                        });
+                    }
+                    // Untimed span: glue its text onto the previous word
+                    _ => match self.words.last_mut() {
+                        Some(last) => last.word.push_str(&text),
+                        None => self.loose_text.push_str(&text),
+                    },
+                }
+            }
+        }
+    }
+
+    fn finish(mut self, begin: Option<u64>, end: Option<u64>) -> Option<LyricLine> {
+        if let Some(last) = self.words.last_mut() {
+            let trimmed = last.word.trim_end().len();
+            last.word.truncate(trimmed);
+        }
+
+        let words_start = self.words.iter().map(|w| w.start_time).min();
+        let words_end = self.words.iter().map(|w| w.end_time).max();
+        let start = begin.or(words_start)?;
+        let end = end.or(words_end).unwrap_or(start).max(start);
+
+        let text = self.loose_text.trim();
+        if self.words.is_empty() {
+            if text.is_empty() {
+                return None;
+            }
+            self.words.push(LyricWord {
+                start_time: start,
+                end_time: end,
+                word: text.to_string(),
+                roman_word: String::new(),
+            });
+        }
+
+        Some(LyricLine {
+            words: self.words,
+            translated_lyric: self.translation.trim().to_string(),
+            roman_lyric: self.roman.trim().to_string(),
+            start_time: start,
+            end_time: end,
+            ..Default::default()
+        })
+    }
+}
+
+fn append_text(target: &mut String, node: Node) {
+    let text = text_of(node);
+    let text = text.trim();
+    if text.is_empty() {
+        return;
+    }
+    if !target.is_empty() {
+        target.push(' ');
+    }
+    target.push_str(text);
+}
+
+/// Formats ms as `mm:ss.mmm`, or `h:mm:ss.mmm` past an hour.
+pub fn format_ttml_time(ms: u64) -> String {
+    let (h, m, s, f) = (ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000);
+    if h > 0 {
+        format!("{}:{:02}:{:02}.{:03}", h, m, s, f)
+    } else {
+        format!("{:02}:{:02}.{:03}", m, s, f)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Writes AMLL-style TTML. Background lines are nested as `x-bg` spans in the
+/// preceding main line; duet lines are assigned to agent `v2`.
+pub fn write_ttml(lyric: &Lyric, options: &ConvertOptions) -> String {
+    let meta = &lyric.metadata;
+    let translation_lang = options
+        .translation_lang
+        .as_deref()
+        .or(meta.translation_lang.as_deref());
+    let has_duet = lyric.lines.iter().any(|l| l.is_duet);
+
+    let mut out = String::from(
+        r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata" xmlns:amll="http://www.example.com/ns/amll" xmlns:itunes="http://music.apple.com/lyric-ttml-internal">"#,
+    );
+    out.push_str(r#"<head><metadata><ttm:agent type="person" xml:id="v1"/>"#);
+    if has_duet {
+        out.push_str(r#"<ttm:agent type="other" xml:id="v2"/>"#);
+    }
+    let mut push_meta = |key: &str, value: &str| {
+        out.push_str(&format!(
+            r#"<amll:meta key="{}" value="{}"/>"#,
+            escape(key),
+            escape(value)
+        ));
+    };
+    for (key, value) in [
+        ("musicName", &meta.title),
+        ("artists", &meta.artist),
+        ("album", &meta.album),
+    ] {
+        if let (Some(v), false) = (value, meta.extra.contains_key(key)) {
+            push_meta(key, v);
+        }
+    }
+    for (key, values) in &meta.extra {
+        for v in values {
+            push_meta(key, v);
+        }
+    }
+    out.push_str("</metadata></head>");
+
+    // Group each main line with the background lines that follow it
+    let mut groups: Vec<(&LyricLine, Vec<&LyricLine>)> = Vec::new();
+    for line in &lyric.lines {
+        match groups.last_mut() {
+            Some((_, bgs)) if line.is_bg => bgs.push(line),
+            _ => groups.push((line, Vec::new())),
+        }
+    }
+
+    let end = lyric.lines.iter().map(|l| l.end_time).max().unwrap_or(0);
+    out.push_str(&format!(
+        r#"<body dur="{}"><div begin="{}" end="{}">"#,
+        format_ttml_time(end),
+        format_ttml_time(lyric.lines.first().map_or(0, |l| l.start_time)),
+        format_ttml_time(end)
+    ));
+    for (i, (line, bgs)) in groups.iter().enumerate() {
+        out.push_str(&format!(
+            r#"<p begin="{}" end="{}" ttm:agent="{}" itunes:key="L{}">"#,
+            format_ttml_time(line.start_time),
+            format_ttml_time(line.end_time),
+            if line.is_duet { "v2" } else { "v1" },
+            i + 1
+        ));
+        push_line_content(&mut out, line, options, translation_lang);
+        for bg in bgs {
+            out.push_str(&format!(
+                r#"<span ttm:role="x-bg" begin="{}" end="{}">"#,
+                format_ttml_time(bg.start_time),
+                format_ttml_time(bg.end_time)
+            ));
+            push_line_content(&mut out, bg, options, translation_lang);
+            out.push_str("</span>");
+        }
+        out.push_str("</p>");
+    }
+    out.push_str("</div></body></tt>");
+    out
+}
+
+fn push_line_content(
+    out: &mut String,
+    line: &LyricLine,
+    options: &ConvertOptions,
+    translation_lang: Option<&str>,
+) {
+    for w in &line.words {
+        let text = w.word.trim_end();
+        out.push_str(&format!(
+            r#"<span begin="{}" end="{}">{}</span>"#,
+            format_ttml_time(w.start_time),
+            format_ttml_time(w.end_time),
+            escape(text)
+        ));
+        if text.len() != w.word.len() {
+            out.push(' ');
+        }
+    }
+    if options.include_translation && !line.translated_lyric.is_empty() {
+        match translation_lang {
+            Some(lang) => out.push_str(&format!(
+                r#"<span ttm:role="x-translation" xml:lang="{}">{}</span>"#,
+                escape(lang),
+                escape(&line.translated_lyric)
+            )),
+            None => out.push_str(&format!(
+                r#"<span ttm:role="x-translation">{}</span>"#,
+                escape(&line.translated_lyric)
+            )),
+        }
+    }
+    if options.include_roman && !line.roman_lyric.is_empty() {
+        out.push_str(&format!(
+            r#"<span ttm:role="x-roman">{}</span>"#,
+            escape(&line.roman_lyric)
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clock_values() {
+        assert_eq!(parse_ttml_time("00:01.5"), Some(1_500));
+        assert_eq!(parse_ttml_time("01:02.345"), Some(62_345));
+        assert_eq!(parse_ttml_time("1:01:02.345"), Some(3_662_345));
+        assert_eq!(parse_ttml_time("12.5"), Some(12_500));
+        assert_eq!(parse_ttml_time("12.5s"), Some(12_500));
+        assert_eq!(parse_ttml_time("250ms"), Some(250));
+        assert_eq!(parse_ttml_time("abc"), None);
+        assert_eq!(parse_ttml_time("1:2:3:4"), None);
+        // Out-of-range values are rejected instead of overflowing
+        assert_eq!(parse_ttml_time("99999999999999999999.5"), None);
+        assert_eq!(parse_ttml_time("18446744073709552.000"), None);
+        assert_eq!(parse_ttml_time("5124095576031:00:00"), None);
+        assert_eq!(parse_ttml_time("307445734561826:00"), None);
+        assert_eq!(parse_ttml_time("5124095576030:25:51.615"), Some(u64::MAX));
+        assert_eq!(parse_ttml_time("5124095576030:25:51.616"), None);
+    }
+
+    #[test]
+    fn background_line_stays_after_its_parent() {
+        let lyric = parse_ttml(
+            r#"<tt xmlns="http://www.w3.org/ns/ttml" xmlns:ttm="http://www.w3.org/ns/ttml#metadata"><body><div>
+            <p begin="00:01.000" end="00:03.000"><span begin="00:01.000" end="00:03.000">First</span></p>
+            <p begin="00:05.000" end="00:07.000"><span begin="00:05.000" end="00:07.000">Main</span><span ttm:role="x-bg"><span begin="00:04.500" end="00:05.500">(early)</span></span></p>
+            </div></body></tt>"#,
+        )
+        .unwrap();
+        let texts: Vec<String> = lyric.lines.iter().map(|l| l.text()).collect();
+        assert_eq!(texts, ["First", "Main", "(early)"]);
+        assert!(lyric.lines[2].is_bg);
+    }
+
+    #[test]
+    fn translation_language_round_trips() {
+        let lyric = parse_ttml(include_str!("../../tests/fixtures/ttml/amll.ttml")).unwrap();
+        assert_eq!(lyric.metadata.translation_lang.as_deref(), Some("zh-CN"));
+        let out = write_ttml(&lyric, &ConvertOptions::default());
+        assert!(out.contains(r#"<span ttm:role="x-translation" xml:lang="zh-CN">你好世界"#));
+
+        let options = ConvertOptions {
+            translation_lang: Some("ja".to_string()),
+            ..Default::default()
+        };
+        assert!(write_ttml(&lyric, &options).contains(r#"x-translation" xml:lang="ja""#));
+
+        // Unknown language: no attribute at all
+        let mut unknown = lyric.clone();
+        unknown.metadata.translation_lang = None;
+        let out = write_ttml(&unknown, &ConvertOptions::default());
+        assert!(out.contains(r#"<span ttm:role="x-translation">你好世界"#));
+        assert!(!out.contains("xml:lang"));
+    }
+
+    #[test]
+    fn fixture_amll() {
+        let lyric = parse_ttml(include_str!("../../tests/fixtures/ttml/amll.ttml")).unwrap();
+        let meta = &lyric.metadata;
+        assert_eq!(meta.title.as_deref(), Some("Test Song"));
+        assert_eq!(meta.artist.as_deref(), Some("Singer A"));
+        assert_eq!(meta.album.as_deref(), Some("Test Album"));
+        assert_eq!(meta.extra["ncmMusicId"], ["1234567"]);
+        assert_eq!(meta.extra["qqMusicId"], ["002abcDEF"]);
+        assert_eq!(meta.extra["songwriters"], ["Writer One", "Writer Two"]);
+        assert!(lyric.word_timed);
+
+        assert_eq!(lyric.lines.len(), 4);
+        let first = &lyric.lines[0];
+        assert_eq!(first.text(), "Hello world");
+        assert_eq!(first.words[0].word, "Hello ");
+        assert_eq!((first.start_time, first.end_time), (1_000, 3_000));
+        assert_eq!(first.translated_lyric, "你好世界");
+        assert_eq!(first.roman_lyric, "ni hao shi jie");
+        assert!(!first.is_bg && !first.is_duet);
+
+        let bg = &lyric.lines[1];
+        assert!(bg.is_bg);
+        assert_eq!(bg.text(), "(ooh ah)");
+        assert_eq!((bg.start_time, bg.end_time), (2_000, 3_200));
+        assert_eq!(bg.translated_lyric, "（噢 啊）");
+
+        let duet = &lyric.lines[2];
+        assert!(duet.is_duet);
+        assert_eq!(duet.text(), "Second singer");
+
+        // Line-level timing only
+        let plain = &lyric.lines[3];
+        assert_eq!(plain.words.len(), 1);
+        assert_eq!(plain.text(), "No word timing here");
+        assert_eq!((plain.start_time, plain.end_time), (8_000, 10_500));
+    }
+
+    #[test]
+    fn rejects_invalid_xml() {
+        assert!(parse_ttml("<tt><body><p>").is_err());
+        assert!(parse_ttml("<html></html>").is_err());
+    }
+}
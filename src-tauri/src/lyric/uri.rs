@@ -0,0 +1,171 @@
+// src-tauri/src/lyric/uri.rs
+
+//! Turns what the frontend passes as a lyric location into a filesystem path.
+//!
+//! Plain paths and `file://` URIs work on every target. Android document-tree URIs
+//! for external storage (`content://com.android.externalstorage.documents/...`)
+//! go through the Android adapter; other `content://` providers have no path.
+
+use std::path::PathBuf;
+
+/// Resolves a plain path, `file://` URI or (on Android) external-storage
+/// `content://` URI to a path.
+pub fn to_path(uri: &str) -> Result<PathBuf, String> {
+    let uri = uri.trim();
+    if uri.is_empty() {
+        return Err("Empty path".to_string());
+    }
+    if uri.starts_with("content://") {
+        return android::content_uri_to_path(uri)
+            .ok_or_else(|| format!("Unsupported content URI: {}", uri));
+    }
+    let Some(rest) = uri.strip_prefix("file://") else {
+        return Ok(PathBuf::from(uri));
+    };
+
+    let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+    let path = percent_decode(rest);
+    // `file:///C:/Music` → `C:/Music`
+    let bytes = path.as_bytes();
+    if bytes.len() >= 3 && bytes[0] == b'/' && bytes[1].is_ascii_alphabetic() && bytes[2] == b':' {
+        return Ok(PathBuf::from(&path[1..]));
+    }
+    Ok(PathBuf::from(path))
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok());
+            if let Some(b) = hex {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Maps `.../tree/primary%3AMusic%2FLyrics[/document/primary%3AMusic%2FLyrics%2Fa.lrc]`
+/// onto `/storage/emulated/0/Music/Lyrics[/a.lrc]`; other volumes live under `/storage/<id>`.
+#[cfg(any(target_os = "android", test))]
+fn external_storage_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("content://com.android.externalstorage.documents/")?;
+    // A document id is more specific than the tree it was opened from
+    let doc_id = match rest.split_once("/document/") {
+        Some((_, doc)) => doc,
+        None => rest.strip_prefix("tree/")?,
+    };
+    let doc_id = percent_decode(doc_id.split('/').next()?);
+    let (volume, relative) = doc_id.split_once(':')?;
+    let mut path = match volume {
+        "primary" => PathBuf::from("/storage/emulated/0"),
+        "" => return None,
+        id => PathBuf::from("/storage").join(id),
+    };
+    if !relative.is_empty() {
+        path.push(relative);
+    }
+    Some(path)
+}
+
+#[cfg(target_os = "android")]
+mod android {
+    pub fn content_uri_to_path(uri: &str) -> Option<std::path::PathBuf> {
+        super::external_storage_path(uri)
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+mod android {
+    /// Content URIs only exist on Android
+    pub fn content_uri_to_path(_uri: &str) -> Option<std::path::PathBuf> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn resolves_plain_paths_and_file_uris() {
+        assert_eq!(to_path("/music/a.lrc").unwrap(), Path::new("/music/a.lrc"));
+        assert_eq!(
+            to_path("file:///music/My%20Lyrics/%E6%AD%8C.lrc").unwrap(),
+            Path::new("/music/My Lyrics/歌.lrc")
+        );
+        assert_eq!(
+            to_path("file://localhost/music/a.lrc").unwrap(),
+            Path::new("/music/a.lrc")
+        );
+        assert_eq!(
+            to_path("file:///C:/Music/a.lrc").unwrap(),
+            Path::new("C:/Music/a.lrc")
+        );
+        // A stray `%` isn't an escape
+        assert_eq!(to_path("file:///a%zz").unwrap(), Path::new("/a%zz"));
+        assert!(to_path("  ").is_err());
+    }
+
+    #[test]
+    fn maps_external_storage_document_uris() {
+        let tree = "content://com.android.externalstorage.documents/tree/primary%3AMusic%2FLyrics";
+        assert_eq!(
+            external_storage_path(tree).unwrap(),
+            Path::new("/storage/emulated/0/Music/Lyrics")
+        );
+        let doc = format!("{}/document/primary%3AMusic%2FLyrics%2Fa.lrc", tree);
+        assert_eq!(
+            external_storage_path(&doc).unwrap(),
+            Path::new("/storage/emulated/0/Music/Lyrics/a.lrc")
+        );
+        assert_eq!(
+            external_storage_path(
+                "content://com.android.externalstorage.documents/tree/1A2B-3C4D%3A"
+            )
+            .unwrap(),
+            Path::new("/storage/1A2B-3C4D")
+        );
+        assert!(external_storage_path("content://media/external/audio/1").is_none());
+    }
+
+    #[test]
+    fn scans_and_reads_through_file_uris() {
+        let root = std::env::temp_dir().join(format!("splayer uri {}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("sub dir")).unwrap();
+        fs::write(root.join("sub dir/1.lrc"), "[00:01.00]a\n").unwrap();
+        let gbk = encoding_rs::GBK
+            .encode("[00:01.00]窗外的麻雀在电线杆上多嘴\n")
+            .0;
+        fs::write(root.join("2.lrc"), gbk).unwrap();
+
+        let uri = format!("file://{}", root.to_string_lossy().replace(' ', "%20"));
+        let dir = to_path(&uri).unwrap();
+        let scanned = crate::lyric::scanner::scan_dir(
+            &dir,
+            &mut crate::lyric::scanner::ScanCache::default(),
+            crate::lyric::scanner::DEFAULT_MAX_DEPTH,
+        );
+        let names: Vec<&str> = scanned.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["2.lrc", "1.lrc"]);
+        assert!(scanned.errors.is_empty());
+
+        let file = format!("file://{}", scanned.files[0].path);
+        let text = crate::lyric::encoding::read_text(&to_path(&file).unwrap()).unwrap();
+        assert_eq!(text.text, "[00:01.00]窗外的麻雀在电线杆上多嘴\n");
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
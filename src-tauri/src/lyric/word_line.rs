@@ -0,0 +1,240 @@
+// src-tauri/src/lyric/word_line.rs
+
+//! Shared parser for the `[start,duration]` + per-word tag line formats used by
+//! KRC, QRC and YRC. They differ only in the word tag brackets, whether the tag
+//! comes before or after its word, and whether word times are line-relative.
+
+use super::{lrc, Lyric, LyricLine, LyricMetadata, LyricWord};
+
+pub(super) struct WordLineFormat {
+    pub open: char,
+    pub close: char,
+    /// `true`: `<tag>word` (KRC, YRC); `false`: `word(tag)` (QRC)
+    pub tag_before_word: bool,
+    /// Word start is relative to the line start (KRC)
+    pub relative: bool,
+}
+
+pub(super) const KRC: WordLineFormat = WordLineFormat {
+    open: '<',
+    close: '>',
+    tag_before_word: true,
+    relative: true,
+};
+
+pub(super) const QRC: WordLineFormat = WordLineFormat {
+    open: '(',
+    close: ')',
+    tag_before_word: false,
+    relative: false,
+};
+
+pub(super) const YRC: WordLineFormat = WordLineFormat {
+    open: '(',
+    close: ')',
+    tag_before_word: true,
+    relative: false,
+};
+
+/// Comma-separated unsigned integers, e.g. `1000,500,0`.
+fn parse_numbers(s: &str) -> Option<Vec<u64>> {
+    let nums: Option<Vec<u64>> = s.split(',').map(|n| n.trim().parse().ok()).collect();
+    nums.filter(|n| (2..=3).contains(&n.len()))
+}
+
+/// `[start,duration]` at the start of a line; returns the times and the rest.
+fn split_line_tag(line: &str) -> Option<(u64, u64, &str)> {
+    let inner = line.trim_start().strip_prefix('[')?;
+    let close = inner.find(']')?;
+    let nums = parse_numbers(&inner[..close])?;
+    Some((nums[0], nums[1], &inner[close + 1..]))
+}
+
+/// Word tags as (tag start byte, tag end byte, start, duration).
+fn find_tags(text: &str, format: &WordLineFormat) -> Vec<(usize, usize, u64, u64)> {
+    let mut tags = Vec::new();
+    let mut from = 0;
+    while let Some(rel) = text[from..].find(format.open) {
+        let open = from + rel;
+        let body_start = open + format.open.len_utf8();
+        let parsed = text[body_start..].find(format.close).and_then(|close| {
+            parse_numbers(&text[body_start..body_start + close]).map(|n| (close, n))
+        });
+        match parsed {
+            Some((close, nums)) => {
+                let end = body_start + close + format.close.len_utf8();
+                tags.push((open, end, nums[0], nums[1]));
+                from = end;
+            }
+            None => from = body_start,
+        }
+    }
+    tags
+}
+
+fn parse_words(text: &str, line_start: u64, format: &WordLineFormat) -> Vec<LyricWord> {
+    let tags = find_tags(text, format);
+    let base = if format.relative { line_start } else { 0 };
+
+    let mut words = Vec::with_capacity(tags.len());
+    for (i, &(tag_start, tag_end, start, duration)) in tags.iter().enumerate() {
+        let word = if format.tag_before_word {
+            let next = tags.get(i + 1).map_or(text.len(), |t| t.0);
+            &text[tag_end..next]
+        } else {
+            let prev = if i == 0 { 0 } else { tags[i - 1].1 };
+            &text[prev..tag_start]
+        };
+        if word.is_empty() {
+            continue;
+        }
+        // Times come straight from the file; saturate rather than overflow
+        let start_time = base.saturating_add(start);
+        words.push(LyricWord {
+            start_time,
+            end_time: start_time.saturating_add(duration),
+            word: word.to_string(),
+            roman_word: String::new(),
+        });
+    }
+    words
+}
+
+/// Parses one `[start,duration]` line. `None` for headers and anything else.
+pub(super) fn parse_line(line: &str, format: &WordLineFormat) -> Option<LyricLine> {
+    let (start, duration, rest) = split_line_tag(line)?;
+    let mut words = parse_words(rest, start, format);
+    if words.is_empty() {
+        let text = rest.trim();
+        if text.is_empty() {
+            return None;
+        }
+        words.push(LyricWord {
+            start_time: start,
+            end_time: start.saturating_add(duration),
+            word: text.to_string(),
+            roman_word: String::new(),
+        });
+    }
+    if words.iter().all(|w| w.word.trim().is_empty()) {
+        return None;
+    }
+
+    let end = words
+        .iter()
+        .map(|w| w.end_time)
+        .fold(start.saturating_add(duration), u64::max);
+    Some(LyricLine {
+        words,
+        start_time: start,
+        end_time: end,
+        ..Default::default()
+    })
+}
+
+/// Parses a whole word-line document. Lines that are neither timed nor LRC-style
+/// headers are ignored. Lines stay in file order; callers sort after attaching
+/// any per-line extras (KRC translations are indexed by file order).
+pub(super) fn parse_document(content: &str, format: &WordLineFormat) -> Lyric {
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+    let mut metadata = LyricMetadata::default();
+    let mut lines = Vec::new();
+
+    for raw in content.lines() {
+        if let Some(line) = parse_line(raw, format) {
+            lines.push(line);
+        } else if let Some((key, value)) = lrc::parse_header(raw) {
+            lrc::apply_header(&mut metadata, key, value);
+        }
+    }
+
+    if metadata.offset != 0 {
+        let offset = metadata.offset;
+        let shift = |t: u64| {
+            if offset >= 0 {
+                t.saturating_sub(offset.unsigned_abs())
+            } else {
+                t.saturating_add(offset.unsigned_abs())
+            }
+        };
+        for line in &mut lines {
+            line.start_time = shift(line.start_time);
+            line.end_time = shift(line.end_time);
+            for w in &mut line.words {
+                w.start_time = shift(w.start_time);
+                w.end_time = shift(w.end_time);
+            }
+        }
+    }
+
+    let word_timed = !lines.is_empty();
+    Lyric {
+        metadata,
+        lines,
+        word_timed,
+    }
+}
+
+/// Stable sort by start time, as every `Lyric` promises.
+pub(super) fn sort_lines(lyric: &mut Lyric) {
+    lyric.lines.sort_by_key(|l| l.start_time);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn huge_timestamps_saturate() {
+        let line = parse_line("[18446744073709551615,1]word", &YRC).unwrap();
+        assert_eq!(line.start_time, u64::MAX);
+        assert_eq!(line.end_time, u64::MAX);
+
+        let line = parse_line(
+            "[18446744073709551000,1000]<0,18446744073709551615,0>a<1000,5,0>b",
+            &KRC,
+        )
+        .unwrap();
+        assert_eq!(line.words.len(), 2);
+        assert!(line.words.iter().all(|w| w.end_time == u64::MAX));
+        assert_eq!(line.end_time, u64::MAX);
+
+        let lyric = parse_document(
+            "[offset:-9223372036854775808]\n[18446744073709551615,1]x",
+            &QRC,
+        );
+        assert_eq!(lyric.lines[0].start_time, u64::MAX);
+    }
+
+    proptest! {
+        #[test]
+        fn never_panics_on_any_numbers(
+            start in any::<u64>(),
+            duration in any::<u64>(),
+            words in prop::collection::vec((any::<u64>(), any::<u64>(), "[a-z]{1,4}"), 0..6),
+            offset in any::<i64>(),
+        ) {
+            for format in [&KRC, &QRC, &YRC] {
+                let mut src = format!("[offset:{}]\n[{},{}]", offset, start, duration);
+                for (word_start, word_duration, text) in &words {
+                    let tag = format!("{}{},{},0{}", format.open, word_start, word_duration, format.close);
+                    if format.tag_before_word {
+                        src.push_str(&tag);
+                        src.push_str(text);
+                    } else {
+                        src.push_str(text);
+                        src.push_str(&tag);
+                    }
+                }
+                let lyric = parse_document(&src, format);
+                for line in &lyric.lines {
+                    prop_assert!(line.end_time >= line.start_time);
+                    for w in &line.words {
+                        prop_assert!(w.end_time >= w.start_time);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// src-tauri/src/lyric/yrc.rs
+
+//! NetEase YRC: `[start,duration](start,duration,0)word...` lines with absolute
+//! word times, interleaved with JSON credit lines (`{"t":0,"c":[{"tx":"作词: "}]}`).
+//! Credits go to `metadata.extra["credits"]`.
+
+use serde::Deserialize;
+
+use super::word_line::{self, YRC};
+use super::Lyric;
+
+#[derive(Deserialize)]
+struct CreditLine {
+    #[serde(default)]
+    c: Vec<CreditPart>,
+}
+
+#[derive(Deserialize)]
+struct CreditPart {
+    #[serde(default)]
+    tx: String,
+}
+
+pub fn parse_yrc(content: &str) -> Lyric {
+    let mut lyric = word_line::parse_document(content, &YRC);
+
+    for line in content.lines() {
+        let line = line.trim();
+        if !line.starts_with('{') {
+            continue;
+        }
+        if let Ok(credit) = serde_json::from_str::<CreditLine>(line) {
+            let text: String = credit.c.iter().map(|p| p.tx.as_str()).collect();
+            let text = text.trim();
+            if !text.is_empty() {
+                lyric
+                    .metadata
+                    .extra
+                    .entry("credits".to_string())
+                    .or_default()
+                    .push(text.to_string());
+            }
+        }
+    }
+
+    word_line::sort_lines(&mut lyric);
+    lyric
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_words_and_credits() {
+        let content = r#"{"t":0,"c":[{"tx":"作词: "},{"tx":"方文山","li":"x"}]}
+{"t":1000,"c":[{"tx":"作曲: "},{"tx":"周杰伦"}]}
+[16210,3460](16210,670,0)故(16880,410,0)事(17290,1000,0)的 (18290,1380,0)小黄花
+[20000,1000](20000,1000,0)从
+"#;
+        let lyric = parse_yrc(content);
+        assert_eq!(
+            lyric.metadata.extra["credits"],
+            ["作词: 方文山", "作曲: 周杰伦"]
+        );
+        assert_eq!(lyric.lines.len(), 2);
+
+        let first = &lyric.lines[0];
+        assert_eq!(first.text(), "故事的 小黄花");
+        assert_eq!(first.words.len(), 4);
+        assert_eq!(
+            (first.words[3].start_time, first.words[3].end_time),
+            (18_290, 19_670)
+        );
+        assert_eq!((first.start_time, first.end_time), (16_210, 19_670));
+    }
+}
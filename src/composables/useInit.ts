@@ -47,6 +47,8 @@ export const useInit = () => {
     // 初始化播放器
     // 移动端/Tauri 环境下禁止启动自动播放（避免系统限制和用户体验问题）
     const shouldAutoPlay = isTauri ? false : settingStore.autoPlay;
+    // Android 进程被杀后，原生会话中的进度比 localStorage 更新
+    if (isTauri && settingStore.memoryLastSeek) await player.restoreNativeSession();
     player.playSong({
       autoPlay: shouldAutoPlay,
       seek: settingStore.memoryLastSeek ? statusStore.currentTime : 0,
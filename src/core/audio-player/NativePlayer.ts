@@ -9,6 +9,22 @@ import {
 } from "./IPlaybackEngine";
 import { AUDIO_EVENTS, type AudioEventMap } from "./BaseAudioPlayer";
 
+/** 随原生会话保存的播放队列信息（原生端原样存储） */
+export interface NativeSessionQueue {
+  playIndex: number;
+  songId: number;
+}
+
+/** 原生端持久化的播放会话（restore_session 返回值） */
+export interface NativeSession {
+  url: string | null;
+  position_secs: number;
+  duration_secs: number;
+  volume: number;
+  queue: NativeSessionQueue | null;
+  saved_at: number;
+}
+
 /**
  * 原生播放器 (Android)
  *
@@ -124,9 +140,45 @@ export class NativePlayer extends TypedEventTarget<AudioEventMap> implements IPl
   private _switching: boolean = false;
   /** 上次同步 MediaSession 的时间 */
   private _lastSyncTime: number = 0;
+  /** restore_session 已在原生端加载（暂停）、尚未被 play() 接管的曲目 */
+  private _restoredUrl: string | null = null;
+
+  /**
+   * 恢复原生端持久化的会话（Android 进程被系统杀死后）
+   * 原生端以暂停状态加载上次的曲目并定位到保存的进度
+   */
+  public async restoreSession(): Promise<NativeSession | null> {
+    try {
+      const session = await invoke<NativeSession | null>("restore_session");
+      if (session?.url) {
+        this._src = session.url;
+        this._restoredUrl = session.url;
+        this._currentTime = session.position_secs;
+        this._paused = true;
+      }
+      return session;
+    } catch (e) {
+      console.error("[NativePlayer] restore session failed:", e);
+      return null;
+    }
+  }
+
+  /** 将播放队列信息保存到原生会话 */
+  public setSessionQueue(queue: NativeSessionQueue): void {
+    invoke("set_session_queue", { queue }).catch(console.error);
+  }
 
   public async play(url?: string, options?: PlayOptions): Promise<void> {
     const shouldPlay = options?.autoPlay ?? true;
+    // 恢复的会话已在原生端加载同一曲目并定位好进度，直接接管
+    if (url && url === this._restoredUrl) {
+      this._restoredUrl = null;
+      this._paused = true;
+      this.dispatch(AUDIO_EVENTS.CAN_PLAY, undefined);
+      if (shouldPlay) await this.resume();
+      return;
+    }
+    this._restoredUrl = null;
     if (url) {
       // 递增版本号，使旧的 play() 续体失效
       const gen = ++this._playGen;
@@ -139,7 +191,7 @@ export class NativePlayer extends TypedEventTarget<AudioEventMap> implements IPl
       try {
         this.dispatch(AUDIO_EVENTS.LOAD_START, undefined);
 
-        await invoke("play_audio", { url, paused: !shouldPlay });
+        await invoke("play_audio", { url, paused: !shouldPlay, seek: options?.seek });
 
         // 如果在等待 play_audio 期间已经切歌，放弃
         if (gen !== this._playGen) return;
@@ -186,6 +238,8 @@ export class NativePlayer extends TypedEventTarget<AudioEventMap> implements IPl
   }
 
   public stop(): void {
+    // 保留恢复的曲目，等待 play() 接管（play_audio 本身会替换当前流）
+    if (this._restoredUrl) return;
     this._switching = true;
     invoke("stop_audio").catch(console.error);
     this._paused = true;
@@ -1,7 +1,11 @@
 import { TypedEventTarget } from "@/utils/TypedEventTarget";
 import { AudioElementPlayer } from "../audio-player/AudioElementPlayer";
 import { AUDIO_EVENTS, type AudioEventMap } from "../audio-player/BaseAudioPlayer";
-import { NativePlayer } from "../audio-player/NativePlayer";
+import {
+  NativePlayer,
+  type NativeSession,
+  type NativeSessionQueue,
+} from "../audio-player/NativePlayer";
 import { isTauri } from "@/utils/env";
 import type {
   EngineCapabilities,
@@ -345,6 +349,23 @@ class AudioManager extends TypedEventTarget<AudioEventMap> implements IPlaybackE
     }
   }
 
+  /**
+   * 恢复原生会话（仅 NativePlayer）
+   */
+  public async restoreSession(): Promise<NativeSession | null> {
+    if (!(this.engine instanceof NativePlayer)) return null;
+    return this.engine.restoreSession();
+  }
+
+  /**
+   * 保存播放队列信息到原生会话（仅 NativePlayer）
+   */
+  public setSessionQueue(queue: NativeSessionQueue): void {
+    if (this.engine instanceof NativePlayer) {
+      this.engine.setSessionQueue(queue);
+    }
+  }
+
   /**
    * 获取当前音量
    */
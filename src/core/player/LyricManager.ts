@@ -23,8 +23,32 @@ import { invoke } from "@tauri-apps/api/core";
 interface LyricFile {
   name: string;
   path: string;
+  /** TTML 头部元数据（ncmMusicId、qqMusicId 等） */
+  metadata: {
+    title: string | null;
+    artist: string | null;
+    album: string | null;
+    extra: Record<string, string[]>;
+  } | null;
 }
 
+/** read_lyric_dir_android 的扫描结果 */
+interface LyricScanResult {
+  files: LyricFile[];
+  /** 单个文件/目录的错误（解析失败、超出深度等），不影响其余结果 */
+  errors: { path: string; message: string }[];
+  /** 命中缓存（未改动）的文件数 */
+  reused: number;
+  /** 本次重新解析的文件数 */
+  parsed: number;
+}
+
+/** 文件名为 `${id}.ttml`，或 TTML 头部声明了该 ncmMusicId */
+const isTtmlForSong = (file: LyricFile, id: number | string): boolean =>
+  file.name.toLowerCase() === `${id}.ttml` ||
+  (file.name.toLowerCase().endsWith(".ttml") &&
+    !!file.metadata?.extra?.ncmMusicId?.includes(String(id)));
+
 interface LyricFetchResult {
   data: SongLyric;
   meta: {
@@ -673,14 +697,17 @@ class LyricManager {
         ttml = result.ttml;
       } else if (isTauri) {
         // Android: 递归扫描 URI 指定的目录 (通常是 DocumentTree URI)
-        // 注意：目前 read_lyric_dir_android 返回的是 Vec<LyricFile>
+        // read_lyric_dir_android 返回扫描结果（文件列表 + 单文件错误）
         // 我们需要找到匹配歌曲 ID 的歌词文件并读取
         for (const dirUri of lyricDirs) {
           try {
-            const files = await invoke<LyricFile[]>("read_lyric_dir_android", { uri: dirUri });
+            const { files, errors } = await invoke<LyricScanResult>("read_lyric_dir_android", {
+              uri: dirUri,
+            });
+            if (errors.length) console.warn("[LyricManager] 歌词扫描错误:", errors);
             // 查找匹配 id.lrc 或 id.ttml 的文件
             const lrcFile = files.find((f) => f.name === `${id}.lrc`);
-            const ttmlFile = files.find((f) => f.name === `${id}.ttml`);
+            const ttmlFile = files.find((f) => isTtmlForSong(f, id));
 
             if (lrcFile) {
               lrc = await invoke<string>("read_lyric_file_android", { uri: lrcFile.path });
@@ -1134,7 +1161,7 @@ class LyricManager {
   }
 
   /**
-   * 从 Tauri 本地文件夹获取歌词 (Android 支持)
+   * 从 Tauri 本地文件夹获取歌词（安卓与桌面端）
    */
   private async fetchTauriLocalLyric(song: SongType): Promise<LyricFetchResult | null> {
     const settingStore = useSettingStore();
@@ -1158,37 +1185,37 @@ class LyricManager {
       targetSuffixTtml,
     );
 
-    // 🌟 关键：判断是否为安卓，不再判断 content://
-    const isAndroid = navigator.userAgent.toLowerCase().includes("android");
-    console.log("[LyricManager] isAndroid:", isAndroid);
-
     for (const dir of localLyricPath) {
       console.log("[LyricManager] 扫描目录:", dir);
       if (lrcContent && ttmlContent) break;
 
-      if (isAndroid) {
-        try {
-          // 只要是安卓，无脑调用 Rust 接口
-          console.log("[LyricManager] 调用 Rust read_lyric_dir_android, dir:", dir);
-          const files: LyricFile[] = await invoke("read_lyric_dir_android", { uri: dir });
-          console.log("[LyricManager] Rust 返回文件数:", files?.length);
-          for (const file of files) {
-            const fileName = file.name.toLowerCase();
-            if (!lrcContent && fileName.endsWith(targetSuffixLrc)) {
-              console.log("[LyricManager] 找到 LRC 文件:", file.name);
-              lrcContent = await invoke("read_lyric_file_android", { uri: file.path });
-            } else if (!ttmlContent && fileName.endsWith(targetSuffixTtml)) {
-              console.log("[LyricManager] 找到 TTML 文件:", file.name);
-              ttmlContent = await invoke("read_lyric_file_android", { uri: file.path });
-            }
-            if (lrcContent && ttmlContent) break;
+      try {
+        // Rust 端同时支持安卓与桌面端（普通路径、file:// 与安卓外部存储 URI）
+        console.log("[LyricManager] 调用 Rust read_lyric_dir_android, dir:", dir);
+        const { files, errors, reused, parsed } = await invoke<LyricScanResult>(
+          "read_lyric_dir_android",
+          { uri: dir },
+        );
+        console.log(
+          `[LyricManager] Rust 返回文件数: ${files.length} (缓存 ${reused}, 解析 ${parsed})`,
+        );
+        if (errors.length) console.warn("[LyricManager] 歌词扫描错误:", errors);
+        for (const file of files) {
+          const fileName = file.name.toLowerCase();
+          if (!lrcContent && fileName.endsWith(targetSuffixLrc)) {
+            console.log("[LyricManager] 找到 LRC 文件:", file.name);
+            lrcContent = await invoke("read_lyric_file_android", { uri: file.path });
+          } else if (
+            !ttmlContent &&
+            (fileName.endsWith(targetSuffixTtml) || isTtmlForSong(file, song.id))
+          ) {
+            console.log("[LyricManager] 找到 TTML 文件:", file.name);
+            ttmlContent = await invoke("read_lyric_file_android", { uri: file.path });
           }
-        } catch (e) {
-          console.error(`[SPlayer] 安卓歌词匹配失败:`, e);
+          if (lrcContent && ttmlContent) break;
         }
-      } else {
-        // 电脑端逻辑保持不变
-        // ... (原有的 searchDir 逻辑)
+      } catch (e) {
+        console.error(`[SPlayer] 本地歌词匹配失败:`, e);
       }
     }
 
@@ -979,6 +979,21 @@ class PlayerController {
     this.rateRampFrame = requestAnimationFrame(tick);
   }
 
+  /**
+   * 恢复 Android 原生会话（进程被系统杀死后）
+   * 原生端以暂停状态重新加载上次的曲目；若会话记录的歌曲仍在播放列表中，同步播放索引与进度
+   */
+  public async restoreNativeSession() {
+    const dataStore = useDataStore();
+    const statusStore = useStatusStore();
+    const session = await useAudioManager().restoreSession();
+    const queue = session?.queue;
+    if (!session || !queue) return;
+    if (dataStore.playList[queue.playIndex]?.id !== queue.songId) return;
+    statusStore.playIndex = queue.playIndex;
+    statusStore.currentTime = session.position_secs * 1000;
+  }
+
   /**
    * 播放成功后的后续设置
    * @param song 歌曲
@@ -988,8 +1003,13 @@ class PlayerController {
     const musicStore = useMusicStore();
     const settingStore = useSettingStore();
     const songManager = useSongManager();
+    const statusStore = useStatusStore();
     // 记录播放历史 (非电台)
     if (song.type !== "radio") dataStore.setHistory(song);
+    // 同步播放队列到原生会话，供进程被杀后恢复
+    if (isTauri) {
+      useAudioManager().setSessionQueue({ playIndex: statusStore.playIndex, songId: song.id });
+    }
     // 更新歌曲数据
     if (!song.path || song.type === "streaming") {
       mediaSessionManager.updateMetadata();